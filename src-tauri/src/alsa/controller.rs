@@ -1,7 +1,14 @@
 use crate::AppState;
+use crate::history::journal::{self, Origin, Setting};
 use std::collections::HashMap;
-use tauri::State;
-use super::{general, input_gain, switches, volume};
+use tauri::{AppHandle, State};
+use super::{db_scale, general, input_gain, phantom, ramp, safety, stereo_link, switches, volume};
+use super::db_scale::{DbRangeInfo, DbValue};
+use super::phantom::PhantomInterlocks;
+use super::ramp::RampSettings;
+use super::safety::OutputLimit;
+use super::stereo_link::StereoLinkSettings;
+use super::volume::OutputPair;
 
 #[tauri::command]
 pub fn get_soundcard_controls(state: State<AppState>) -> Result<HashMap<String, Vec<String>>, String> {
@@ -18,7 +25,21 @@ pub fn get_input_gain(state: State<AppState>, control_name: String) -> Result<i3
 #[tauri::command]
 pub fn set_input_gain(state: State<AppState>, control_name: String, gain: i32) -> Result<(), String> {
     let card_number = &state.alsa_card_number;
-    input_gain::set_input_gain(&card_number, &control_name, gain)
+    let controls = stereo_link::affected_controls(&control_name);
+    journal::record(Origin::Ui, card_number, &controls, || stereo_link::set_input_gain(card_number, &control_name, gain))
+}
+
+#[tauri::command]
+pub fn get_input_gain_db(state: State<AppState>, control_name: String) -> Result<DbValue, String> {
+    let card_number = &state.alsa_card_number;
+    input_gain::get_input_gain_db(card_number, &control_name)
+}
+
+#[tauri::command]
+pub fn set_input_gain_db(state: State<AppState>, control_name: String, db: f64) -> Result<DbValue, String> {
+    let card_number = &state.alsa_card_number;
+    let controls = stereo_link::affected_controls(&control_name);
+    journal::record(Origin::Ui, card_number, &controls, || input_gain::set_input_gain_db(card_number, &control_name, db))
 }
 
 #[tauri::command]
@@ -30,7 +51,8 @@ pub fn get_pad_state(state: State<AppState>, control_name: String) -> Result<boo
 #[tauri::command]
 pub fn set_pad_state(state: State<AppState>, control_name: String, new_state: bool) -> Result<(), String> {
     let card_number = &state.alsa_card_number;
-    switches::set_pad_state(&card_number, &control_name, new_state)
+    let controls = stereo_link::affected_controls(&control_name);
+    journal::record(Origin::Ui, card_number, &controls, || stereo_link::set_pad_state(card_number, &control_name, new_state))
 }
 
 #[tauri::command]
@@ -40,9 +62,28 @@ pub fn get_phantom_power_state(state: State<AppState>, control_name: String) ->
 }
 
 #[tauri::command]
-pub fn set_phantom_power(state: State<AppState>, control_name: String, new_state: bool) -> Result<(), String> {
+pub fn set_phantom_power(app_handle: AppHandle, state: State<AppState>, control_name: String, new_state: bool, confirmed: Option<bool>) -> Result<(), String> {
     let card_number = &state.alsa_card_number;
-    switches::set_phantom_power(&card_number, &control_name, new_state)
+    let controls = stereo_link::affected_controls(&control_name);
+    journal::record(Origin::Ui, card_number, &controls, || stereo_link::set_phantom_power(&app_handle, card_number, &control_name, new_state, confirmed.unwrap_or(false)))
+}
+
+#[tauri::command]
+pub fn get_phantom_interlocks(app_handle: AppHandle) -> Result<PhantomInterlocks, String> {
+    phantom::get_interlocks(&app_handle)
+}
+
+#[tauri::command]
+pub fn set_phantom_interlocks(app_handle: AppHandle, state: State<AppState>, interlocks: PhantomInterlocks) -> Result<(), String> {
+    let card_number = &state.alsa_card_number;
+    journal::record_settings(&app_handle, Origin::Ui, card_number, &[Setting::PhantomInterlocks], &[], || {
+        phantom::set_interlocks(&app_handle, interlocks)
+    })
+}
+
+#[tauri::command]
+pub fn get_phantom_log(app_handle: AppHandle) -> Result<Vec<String>, String> {
+    phantom::get_phantom_log(&app_handle)
 }
 
 #[tauri::command]
@@ -54,7 +95,8 @@ pub fn get_line_input_sensitivity(state: State<AppState>, line_input_name: Strin
 #[tauri::command]
 pub fn set_line_input_sensitivity(state: State<AppState>, line_input_name: String, sensitivity: String) -> Result<(), String> {
     let card_number = &state.alsa_card_number;
-    switches::set_line_input_sensitivity(&card_number, &line_input_name, &sensitivity)
+    let controls = stereo_link::affected_controls(&line_input_name);
+    journal::record(Origin::Ui, card_number, &controls, || stereo_link::set_line_input_sensitivity(card_number, &line_input_name, &sensitivity))
 }
 
 #[tauri::command]
@@ -66,5 +108,62 @@ pub fn get_alsa_volume(state: State<AppState>, control_name: String) -> Result<i
 #[tauri::command]
 pub fn set_alsa_volume(state: State<AppState>, control_name: String, volume: i32) -> Result<(), String> {
     let card_number = &state.alsa_card_number;
-    volume::set_volume(&card_number, &control_name, volume)
-}
\ No newline at end of file
+    let controls = stereo_link::affected_controls(&control_name);
+    journal::record(Origin::Ui, card_number, &controls, || stereo_link::set_volume(card_number, &control_name, volume))
+}
+
+#[tauri::command]
+pub fn get_volume_db(state: State<AppState>, control_name: String) -> Result<DbValue, String> {
+    let card_number = &state.alsa_card_number;
+    volume::get_volume_db(card_number, &control_name)
+}
+
+#[tauri::command]
+pub fn set_volume_db(state: State<AppState>, control_name: String, db: f64) -> Result<DbValue, String> {
+    let card_number = &state.alsa_card_number;
+    let controls = stereo_link::affected_controls(&control_name);
+    journal::record(Origin::Ui, card_number, &controls, || volume::set_volume_db(card_number, &control_name, db))
+}
+
+#[tauri::command]
+pub fn get_control_db_range(control_name: String) -> DbRangeInfo {
+    db_scale::get_range(&control_name)
+}
+
+#[tauri::command]
+pub fn get_output_limits(app_handle: AppHandle) -> Result<HashMap<OutputPair, OutputLimit>, String> {
+    safety::get_limits(&app_handle)
+}
+
+#[tauri::command]
+pub fn set_output_limit(app_handle: AppHandle, state: State<AppState>, output: OutputPair, limit: OutputLimit) -> Result<(), String> {
+    let card_number = &state.alsa_card_number;
+    // A new cap can bring the output down with it
+    let (left, right) = output.control_names();
+    let controls = [left.to_string(), right.to_string()];
+    journal::record_settings(&app_handle, Origin::Ui, card_number, &[Setting::OutputLimit(output)], &controls, || {
+        safety::set_limit(&app_handle, card_number, output, limit)
+    })
+}
+
+#[tauri::command]
+pub fn get_ramp_settings() -> RampSettings {
+    ramp::settings()
+}
+
+#[tauri::command]
+pub fn set_ramp_settings(app_handle: AppHandle, state: State<AppState>, settings: RampSettings) -> Result<(), String> {
+    journal::record_settings(&app_handle, Origin::Ui, &state.alsa_card_number, &[Setting::Ramp], &[], || ramp::set_settings(&app_handle, settings))
+}
+
+#[tauri::command]
+pub fn get_stereo_link_settings() -> StereoLinkSettings {
+    stereo_link::get_settings()
+}
+
+#[tauri::command]
+pub fn set_stereo_link_settings(app_handle: AppHandle, state: State<AppState>, settings: StereoLinkSettings) -> Result<(), String> {
+    journal::record_settings(&app_handle, Origin::Ui, &state.alsa_card_number, &[Setting::StereoLink], &[], || {
+        stereo_link::set_settings(&app_handle, settings)
+    })
+}
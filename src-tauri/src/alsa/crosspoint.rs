@@ -0,0 +1,40 @@
+pub const OUTPUT_ROUTES: [&str; 12] = [
+    "AN1", "AN2", "PH3", "PH4", "AS1", "AS2", "ADAT3", "ADAT4", "ADAT5", "ADAT6", "ADAT7", "ADAT8",
+];
+
+/// A mixer crosspoint, e.g. "Mic-AN1-PH3" routes source "Mic-AN1" to output "PH3"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Crosspoint {
+    pub control_name: String,
+    pub source: String,
+    pub output: String,
+}
+
+pub fn parse_crosspoint(control_name: &str) -> Option<Crosspoint> {
+    let (source, output) = control_name.rsplit_once('-')?;
+
+    // Sources are always "<type>-<input>", which also keeps "Main-Out AN1" from matching
+    if !source.contains('-') || !OUTPUT_ROUTES.contains(&output) {
+        return None;
+    }
+
+    Some(Crosspoint {
+        control_name: control_name.to_string(),
+        source: source.to_string(),
+        output: output.to_string(),
+    })
+}
+
+/// Crosspoint control names for every source that feeds both outputs of a pair, as (left, right)
+pub fn get_stereo_crosspoints(crosspoints: &[Crosspoint], left_route: &str, right_route: &str) -> Vec<(String, String)> {
+    crosspoints
+        .iter()
+        .filter(|xp| xp.output == left_route)
+        .filter_map(|left| {
+            crosspoints
+                .iter()
+                .find(|right| right.output == right_route && right.source == left.source)
+                .map(|right| (left.control_name.clone(), right.control_name.clone()))
+        })
+        .collect()
+}
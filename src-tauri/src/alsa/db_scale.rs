@@ -0,0 +1,295 @@
+use super::volume;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::Mutex;
+
+// The kernel encodes a muted minimum as -9999999 (1/100 dB), amixer prints it as is
+const TLV_MUTE_DB: f64 = -99999.0;
+
+/// A control's dB scale as reported by its TLV data
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DbScale {
+    /// dB rises by `step_db` per raw step
+    Scale { min_db: f64, step_db: f64, mute: bool },
+    /// dB is spread evenly over the raw range
+    MinMax { min_db: f64, max_db: f64, mute: bool },
+    /// Raw values are linear amplitude between the two dB levels
+    Linear { min_db: f64, max_db: f64 },
+    /// Different scales for different raw ranges
+    Range { ranges: Vec<DbRange> },
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DbRange {
+    pub raw_min: i32,
+    pub raw_max: i32,
+    pub scale: DbScale,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ControlScale {
+    pub raw_min: i32,
+    pub raw_max: i32,
+    pub scale: DbScale,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DbValue {
+    pub raw: i32,
+    pub db: f64,
+    pub muted: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DbRangeInfo {
+    pub raw_min: i32,
+    pub raw_max: i32,
+    pub min_db: f64,
+    pub max_db: f64,
+    /// False when the control has no TLV and the frontend's old 32768-is-unity guess is used
+    pub from_tlv: bool,
+}
+
+impl DbScale {
+    /// dB for a raw value within [raw_min, raw_max], NEG_INFINITY when muted
+    fn to_db(&self, raw: i32, raw_min: i32, raw_max: i32) -> f64 {
+        let raw = raw.clamp(raw_min, raw_max);
+        let span = (raw_max - raw_min).max(1) as f64;
+        let position = (raw - raw_min) as f64 / span;
+
+        match self {
+            DbScale::Scale { min_db, step_db, mute } => {
+                if *mute && raw == raw_min {
+                    return f64::NEG_INFINITY;
+                }
+                min_db + (raw - raw_min) as f64 * step_db
+            }
+            DbScale::MinMax { min_db, max_db, mute } => {
+                if *mute && raw == raw_min {
+                    return f64::NEG_INFINITY;
+                }
+                min_db + (max_db - min_db) * position
+            }
+            DbScale::Linear { min_db, max_db } => {
+                if raw == raw_min {
+                    return if *min_db <= TLV_MUTE_DB { f64::NEG_INFINITY } else { *min_db };
+                }
+                if *min_db <= TLV_MUTE_DB {
+                    return 20.0 * position.log10() + max_db;
+                }
+                let low = 10f64.powf(min_db / 20.0);
+                let high = 10f64.powf(max_db / 20.0);
+                20.0 * ((high - low) * position + low).log10()
+            }
+            DbScale::Range { ranges } => ranges
+                .iter()
+                .find(|range| raw >= range.raw_min && raw <= range.raw_max)
+                .map(|range| range.scale.to_db(raw, range.raw_min, range.raw_max))
+                .unwrap_or(f64::NEG_INFINITY),
+        }
+    }
+}
+
+impl ControlScale {
+    pub fn raw_to_db(&self, raw: i32) -> f64 {
+        self.scale.to_db(raw, self.raw_min, self.raw_max)
+    }
+
+    /// Lowest audible level, i.e. the dB of the first raw step above a muted minimum
+    pub fn min_db(&self) -> f64 {
+        let db = self.raw_to_db(self.raw_min);
+        if db.is_finite() {
+            db
+        } else {
+            self.raw_to_db((self.raw_min + 1).min(self.raw_max))
+        }
+    }
+
+    pub fn max_db(&self) -> f64 {
+        self.raw_to_db(self.raw_max)
+    }
+
+    /// Nearest raw step to `db`. Anything below the lowest level gives the raw minimum.
+    pub fn db_to_raw(&self, db: f64) -> i32 {
+        if db.is_nan() || db <= self.raw_to_db(self.raw_min) {
+            return self.raw_min;
+        }
+
+        // The scale only ever rises, so find the first raw value at or above the target
+        let (mut low, mut high) = (self.raw_min, self.raw_max);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.raw_to_db(mid) < db {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        if low > self.raw_min && (db - self.raw_to_db(low - 1)) < (self.raw_to_db(low) - db) {
+            low - 1
+        } else {
+            low
+        }
+    }
+}
+
+// Scales per simple mixer control name, read once since TLV data never changes
+static SCALES: Lazy<Mutex<HashMap<String, ControlScale>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// "Foo Playback Volume" is shown as "Foo" by the simple mixer interface the rest of the app uses
+fn simple_control_name(ctl_name: &str) -> &str {
+    for suffix in [" Playback Volume", " Capture Volume", " Playback Switch", " Capture Switch", " Volume", " Switch"] {
+        if let Some(name) = ctl_name.strip_suffix(suffix) {
+            return name;
+        }
+    }
+    ctl_name
+}
+
+fn parse_db(value: &str) -> Option<f64> {
+    value.trim_end_matches("dB").parse::<f64>().ok()
+}
+
+fn parse_scale_spec(spec: &str) -> Option<DbScale> {
+    let spec_re = Regex::new(r"(dBscale|dBminmaxmute|dBminmax|dBlinear)-min=([^,]+),(?:step|max)=([^,\s]+)(?:,mute=(\d))?").unwrap();
+    let caps = spec_re.captures(spec)?;
+    let first = parse_db(&caps[2])?;
+    let second = parse_db(&caps[3])?;
+
+    Some(match &caps[1] {
+        "dBscale" => DbScale::Scale {
+            min_db: first,
+            step_db: second,
+            mute: caps.get(4).is_some_and(|m| m.as_str() == "1"),
+        },
+        "dBminmaxmute" => DbScale::MinMax { min_db: first, max_db: second, mute: true },
+        "dBminmax" => DbScale::MinMax { min_db: first, max_db: second, mute: false },
+        _ => DbScale::Linear { min_db: first, max_db: second },
+    })
+}
+
+/// Parses one control's block from `amixer contents`
+fn parse_control_block(block: &str) -> Option<(String, ControlScale)> {
+    let name_re = Regex::new(r"name='([^']+)'").unwrap();
+    let limits_re = Regex::new(r"type=INTEGER,.*min=(-?\d+),max=(-?\d+)").unwrap();
+    let range_re = Regex::new(r"rangemin=(\d+),+rangemax=(\d+)\s*\n\s*\|?\s*(dB\S+)").unwrap();
+
+    let name = name_re.captures(block)?[1].to_string();
+    let limits = limits_re.captures(block)?;
+    let raw_min = limits[1].parse::<i32>().ok()?;
+    let raw_max = limits[2].parse::<i32>().ok()?;
+
+    let ranges: Vec<DbRange> = range_re
+        .captures_iter(block)
+        .filter_map(|caps| {
+            Some(DbRange {
+                raw_min: caps[1].parse().ok()?,
+                raw_max: caps[2].parse().ok()?,
+                scale: parse_scale_spec(&caps[3])?,
+            })
+        })
+        .collect();
+
+    let scale = if !ranges.is_empty() {
+        DbScale::Range { ranges }
+    } else {
+        parse_scale_spec(block)?
+    };
+
+    Some((simple_control_name(&name).to_string(), ControlScale { raw_min, raw_max, scale }))
+}
+
+/// Reads the dB scale of every control on the card in one go.
+pub fn load_scales(card_index: &str) -> Result<usize, String> {
+    let output = Command::new("amixer")
+        .args(["-c", card_index, "contents"])
+        .output()
+        .map_err(|e| format!("Failed to execute amixer: {}", e))?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).to_string());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut blocks: Vec<String> = Vec::new();
+    for line in stdout.lines() {
+        if line.starts_with("numid=") || blocks.is_empty() {
+            blocks.push(String::new());
+        }
+        if let Some(block) = blocks.last_mut() {
+            block.push_str(line);
+            block.push('\n');
+        }
+    }
+
+    let mut scales = SCALES.lock().map_err(|e| e.to_string())?;
+    scales.clear();
+    scales.extend(blocks.iter().filter_map(|block| parse_control_block(block)));
+    Ok(scales.len())
+}
+
+pub fn get_scale(control_name: &str) -> Option<ControlScale> {
+    SCALES.lock().ok()?.get(control_name).cloned()
+}
+
+/// dB for a raw value, using the control's TLV scale when there is one. A muted minimum
+/// comes back as the lowest dB so the result is always usable in arithmetic.
+pub fn raw_to_db(control_name: &str, raw: i32) -> f64 {
+    match get_scale(control_name) {
+        Some(scale) => {
+            let db = scale.raw_to_db(raw);
+            if db.is_finite() { db } else { scale.min_db() }
+        }
+        None => volume::raw_to_db(raw),
+    }
+}
+
+/// Nearest raw step for a dB level, using the control's TLV scale when there is one.
+pub fn db_to_raw(control_name: &str, db: f64) -> i32 {
+    match get_scale(control_name) {
+        Some(scale) => scale.db_to_raw(db),
+        None => volume::db_to_raw(db),
+    }
+}
+
+pub fn get_db_value(control_name: &str, raw: i32) -> DbValue {
+    match get_scale(control_name) {
+        Some(scale) => {
+            let db = scale.raw_to_db(raw);
+            DbValue {
+                raw,
+                db: if db.is_finite() { db } else { scale.min_db() },
+                muted: !db.is_finite(),
+            }
+        }
+        None => DbValue {
+            raw,
+            db: volume::raw_to_db(raw),
+            muted: raw == 0,
+        },
+    }
+}
+
+pub fn get_range(control_name: &str) -> DbRangeInfo {
+    match get_scale(control_name) {
+        Some(scale) => DbRangeInfo {
+            raw_min: scale.raw_min,
+            raw_max: scale.raw_max,
+            min_db: scale.min_db(),
+            max_db: scale.max_db(),
+            from_tlv: true,
+        },
+        None => DbRangeInfo {
+            raw_min: 0,
+            raw_max: volume::MAX_RAW,
+            min_db: volume::MIN_DB,
+            max_db: volume::MAX_DB,
+            from_tlv: false,
+        },
+    }
+}
@@ -1,6 +1,7 @@
 use std::process::Command;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::fs;
 
 pub fn get_card_number_by_name(card_name: &str) -> Result<String, String> {
@@ -113,4 +114,83 @@ pub fn find_babyface_card() -> Result<String, String> {
     }
 
     Err("No RME Babyface Pro found".to_string())
-}
\ No newline at end of file
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlValue {
+    Volume(i32),
+    Switch(bool),
+    Item(String),
+}
+
+impl ControlValue {
+    fn to_amixer_arg(&self) -> String {
+        match self {
+            ControlValue::Volume(volume) => volume.to_string(),
+            ControlValue::Switch(on) => if *on { "on".to_string() } else { "off".to_string() },
+            ControlValue::Item(item) => item.clone(),
+        }
+    }
+}
+
+pub fn parse_control_value(info: &[String]) -> Option<ControlValue> {
+    let volume_re = Regex::new(r"^Mono:(?: Playback| Capture)? (\d+)").unwrap();
+
+    for line in info {
+        let line = line.trim();
+        if let Some(item) = line.strip_prefix("Item0:") {
+            return Some(ControlValue::Item(item.trim().trim_matches('\'').to_string()));
+        }
+        if let Some(caps) = volume_re.captures(line) {
+            return caps[1].parse::<i32>().ok().map(ControlValue::Volume);
+        }
+        if line.starts_with("Mono:") {
+            if line.contains("[on]") {
+                return Some(ControlValue::Switch(true));
+            }
+            if line.contains("[off]") {
+                return Some(ControlValue::Switch(false));
+            }
+        }
+    }
+
+    None
+}
+
+pub fn get_control_values(card_index: &str) -> Result<BTreeMap<String, ControlValue>, String> {
+    let controls = get_soundcard_controls(card_index)?;
+
+    Ok(controls
+        .iter()
+        .filter_map(|(name, info)| parse_control_value(info).map(|value| (name.clone(), value)))
+        .collect())
+}
+
+pub fn get_control_value(card_index: &str, control_name: &str) -> Result<ControlValue, String> {
+    let output = Command::new("amixer")
+        .args(["-c", card_index, "sget", control_name])
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).to_string());
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let info: Vec<String> = stdout.lines().skip(1).map(|line| line.to_string()).collect();
+    parse_control_value(&info).ok_or_else(|| format!("Could not read a value for {}", control_name))
+}
+
+pub fn set_control_value(card_index: &str, control_name: &str, value: &ControlValue) -> Result<(), String> {
+    let output = Command::new("amixer")
+        .args(["-c", card_index, "sset", control_name, "--", &value.to_amixer_arg()])
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).to_string())
+    }
+}
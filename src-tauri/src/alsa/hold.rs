@@ -0,0 +1,30 @@
+use once_cell::sync::OnceCell;
+
+// A layer above the mixer (the monitor section) can hold controls at an override of its own.
+// Volume writes ask it first, so alsa doesn't depend on whatever does the holding.
+
+pub struct HoldHook {
+    /// The value a control returns to once released, if it is held
+    pub held_value: fn(&str) -> Option<i32>,
+    /// Takes a new value for a held control. Returns false if the control isn't held.
+    pub update_held_value: fn(&str, &str, i32) -> Result<bool, String>,
+}
+
+static HOOK: OnceCell<HoldHook> = OnceCell::new();
+
+pub fn register(hook: HoldHook) {
+    if HOOK.set(hook).is_err() {
+        eprintln!("A hold hook is already registered");
+    }
+}
+
+pub fn held_value(control_name: &str) -> Option<i32> {
+    HOOK.get().and_then(|hook| (hook.held_value)(control_name))
+}
+
+pub fn update_held_value(card_index: &str, control_name: &str, value: i32) -> Result<bool, String> {
+    match HOOK.get() {
+        Some(hook) => (hook.update_held_value)(card_index, control_name, value),
+        None => Ok(false),
+    }
+}
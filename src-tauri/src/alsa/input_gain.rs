@@ -1,24 +1,24 @@
-use super::general;
+use super::{db_scale, general, ramp, stereo_link};
+use super::ramp::RampCurve;
 use std::process::Command;
 
 pub fn set_input_gain(card_index: &str, control_name: &str, gain: i32) -> Result<(), String> {
+    // Gain steps are already in dB, so a straight line in raw values is a straight line in dB
+    ramp::start(card_index, control_name, gain, ramp::settings().control_duration(), RampCurve::Linear)?;
+    Ok(())
+}
 
-    let output = Command::new("amixer")
-        .args(&[
-            "-c",
-            card_index,
-            "set",
-            control_name,
-            &format!("{}", gain),
-        ])
-        .output()
-        .map_err(|e| e.to_string())?;
+/// Sets a gain in dB, rounded to the nearest raw step, and returns the dB actually applied.
+/// A linked partner follows.
+pub fn set_input_gain_db(card_index: &str, control_name: &str, db: f64) -> Result<db_scale::DbValue, String> {
+    let gain = db_scale::db_to_raw(control_name, db);
+    stereo_link::set_input_gain(card_index, control_name, gain)?;
+    Ok(db_scale::get_db_value(control_name, gain))
+}
 
-    if output.status.success() {
-        Ok(())
-    } else {
-        Err(String::from_utf8_lossy(&output.stderr).to_string())
-    }
+pub fn get_input_gain_db(card_index: &str, control_name: &str) -> Result<db_scale::DbValue, String> {
+    let gain = get_input_gain(card_index, control_name)?;
+    Ok(db_scale::get_db_value(control_name, gain))
 }
 
 pub fn get_input_gain(card_index: &str, control_name: &str) -> Result<i32, String> {
@@ -1,5 +1,13 @@
 pub mod controller;
+pub mod crosspoint;
+pub mod db_scale;
 pub mod general;
+pub mod hold;
+pub mod phantom;
+pub mod ramp;
+pub mod safety;
+pub mod stereo_link;
 pub mod switches;
+pub mod virtual_volume;
 pub mod volume;
 pub mod input_gain;
\ No newline at end of file
@@ -0,0 +1,226 @@
+use super::{crosspoint, general, ramp, switches};
+use super::ramp::RampCurve;
+use super::general::ControlValue;
+use crate::storage::config::ConfigStorage;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+/// Prefix of the error returned when 48V on a ribbon mic channel needs confirming
+pub const RIBBON_CONFIRMATION_REQUIRED: &str = "ribbon_confirmation_required";
+
+// How long after a PAD change the input is considered unsettled
+const PAD_SETTLE: Duration = Duration::from_millis(300);
+// The thump happens while the phantom supply charges or discharges
+const PHANTOM_SETTLE: Duration = Duration::from_millis(1500);
+const FADE_IN: Duration = Duration::from_millis(300);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PhantomInterlocks {
+    /// Mute the input's crosspoints while 48V switches and fade them back in afterwards
+    #[serde(default = "enabled")]
+    pub mute_crosspoints: bool,
+    /// Require confirmation before turning on 48V for channels marked as ribbon mics
+    #[serde(default = "enabled")]
+    pub confirm_ribbon: bool,
+    /// Refuse 48V while a PAD change on the same input hasn't settled
+    #[serde(default = "enabled")]
+    pub block_during_pad: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for PhantomInterlocks {
+    fn default() -> Self {
+        Self {
+            mute_crosspoints: true,
+            confirm_ribbon: true,
+            block_during_pad: true,
+        }
+    }
+}
+
+static PAD_BUSY_UNTIL: Lazy<Mutex<HashMap<String, Instant>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static PHANTOM_IN_FLIGHT: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// "Mic-AN1 48V" and "Mic-AN1 PAD" both belong to input "Mic-AN1"
+pub fn input_name(control_name: &str) -> &str {
+    control_name.split_whitespace().next().unwrap_or(control_name)
+}
+
+/// Called around every PAD change so phantom power can wait for it to settle.
+pub fn mark_pad_change(control_name: &str) {
+    if let Ok(mut busy) = PAD_BUSY_UNTIL.lock() {
+        busy.insert(input_name(control_name).to_string(), Instant::now() + PAD_SETTLE);
+    }
+}
+
+fn pad_busy(input: &str) -> bool {
+    PAD_BUSY_UNTIL
+        .lock()
+        .map(|busy| busy.get(input).is_some_and(|until| Instant::now() < *until))
+        .unwrap_or(false)
+}
+
+pub fn get_interlocks(app: &AppHandle) -> Result<PhantomInterlocks, String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    Ok(config.settings.phantom_interlocks)
+}
+
+pub fn set_interlocks(app: &AppHandle, interlocks: PhantomInterlocks) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    storage.update(|config| config.settings.phantom_interlocks = interlocks).map_err(|e| e.to_string())
+}
+
+/// The checks that refuse a 48V change outright, without touching the device.
+pub fn check_interlocks(app: &AppHandle, control_name: &str, new_state: bool, confirmed: bool) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    let interlocks = &config.settings.phantom_interlocks;
+    let input = input_name(control_name);
+
+    let is_ribbon = config.channels.get(input).is_some_and(|channel| channel.ribbon_mic);
+    if new_state && is_ribbon && interlocks.confirm_ribbon && !confirmed {
+        return Err(format!(
+            "{}: {} is marked as a ribbon mic, turning on 48V needs to be confirmed",
+            RIBBON_CONFIRMATION_REQUIRED, input
+        ));
+    }
+
+    if interlocks.block_during_pad && pad_busy(input) {
+        return Err(format!("PAD on {} is still changing, try again in a moment", input));
+    }
+
+    Ok(())
+}
+
+pub fn set_phantom_power(app: &AppHandle, card_index: &str, control_name: &str, new_state: bool, confirmed: bool) -> Result<(), String> {
+    check_interlocks(app, control_name, new_state, confirmed)?;
+
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    let interlocks = &config.settings.phantom_interlocks;
+    let input = input_name(control_name);
+
+    {
+        let mut in_flight = PHANTOM_IN_FLIGHT.lock().map_err(|e| e.to_string())?;
+        if !in_flight.insert(input.to_string()) {
+            return Err(format!("48V on {} is still switching", input));
+        }
+    }
+
+    let muted = if interlocks.mute_crosspoints {
+        match mute_crosspoints(card_index, input) {
+            Ok(muted) => muted,
+            Err(e) => {
+                finish(input);
+                return Err(e);
+            }
+        }
+    } else {
+        Vec::new()
+    };
+
+    if let Err(e) = switches::set_phantom_power(card_index, control_name, new_state) {
+        fade_in(card_index, &muted);
+        finish(input);
+        return Err(e);
+    }
+
+    log_phantom_change(app, input, new_state);
+
+    if muted.is_empty() {
+        finish(input);
+        return Ok(());
+    }
+
+    let card_index = card_index.to_string();
+    let input = input.to_string();
+    thread::spawn(move || {
+        thread::sleep(PHANTOM_SETTLE);
+        fade_in(&card_index, &muted);
+        finish(&input);
+    });
+
+    Ok(())
+}
+
+fn finish(input: &str) {
+    if let Ok(mut in_flight) = PHANTOM_IN_FLIGHT.lock() {
+        in_flight.remove(input);
+    }
+}
+
+/// Silences every crosspoint fed by the input and returns their previous levels.
+fn mute_crosspoints(card_index: &str, input: &str) -> Result<Vec<(String, i32)>, String> {
+    let mut muted = Vec::new();
+
+    for (name, value) in general::get_control_values(card_index)? {
+        let ControlValue::Volume(level) = value else {
+            continue;
+        };
+        let feeds_input = crosspoint::parse_crosspoint(&name).is_some_and(|xp| xp.source == input);
+        if feeds_input && level > 0 {
+            ramp::start_from(card_index, &name, level, 0, Duration::ZERO, RampCurve::Decibel)?;
+            muted.push((name, level));
+        }
+    }
+
+    Ok(muted)
+}
+
+fn fade_in(card_index: &str, levels: &[(String, i32)]) {
+    let mut handles = Vec::new();
+
+    for (name, level) in levels {
+        match ramp::start_from(card_index, name, 0, *level, FADE_IN, RampCurve::Decibel) {
+            Ok(handle) => handles.push(handle),
+            Err(e) => eprintln!("Failed to fade in {}: {}", name, e),
+        }
+    }
+
+    // Hold the input as in flight until every crosspoint is back where it was
+    for handle in handles {
+        handle.wait();
+    }
+}
+
+fn log_path(app: &AppHandle) -> Result<std::path::PathBuf, String> {
+    let log_dir = app.path().app_log_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&log_dir).map_err(|e| e.to_string())?;
+    Ok(log_dir.join("phantom-power.log"))
+}
+
+fn log_phantom_change(app: &AppHandle, input: &str, new_state: bool) {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let line = format!("{} {} 48V {}", timestamp, input, if new_state { "on" } else { "off" });
+    println!("Phantom power: {}", line);
+
+    let result = log_path(app).and_then(|path| {
+        let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line).map_err(|e| e.to_string())
+    });
+    if let Err(e) = result {
+        eprintln!("Failed to write phantom power log: {}", e);
+    }
+}
+
+/// Logged phantom changes, one "<unix time> <input> 48V <on|off>" line each.
+pub fn get_phantom_log(app: &AppHandle) -> Result<Vec<String>, String> {
+    let path = log_path(app)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let log = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    Ok(log.lines().map(|line| line.to_string()).collect())
+}
@@ -0,0 +1,226 @@
+use super::{db_scale, volume};
+use crate::storage::config::ConfigStorage;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tauri::AppHandle;
+
+pub const STEP_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RampCurve {
+    /// Straight line in raw values, right for controls that are already in dB steps like gains
+    Linear,
+    /// Straight line in dB, sounds even on volume and crosspoint controls
+    Decibel,
+    /// Eased start and end
+    SCurve,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RampSettings {
+    /// Smoothing for volume and gain changes from faders and other clients
+    #[serde(default = "default_control_ms")]
+    pub control_ms: u64,
+    /// Used for switched changes like mute, dim and preset recall
+    #[serde(default = "default_fade_ms")]
+    pub fade_ms: u64,
+    #[serde(default = "default_curve")]
+    pub curve: RampCurve,
+}
+
+fn default_control_ms() -> u64 {
+    30
+}
+
+fn default_fade_ms() -> u64 {
+    150
+}
+
+fn default_curve() -> RampCurve {
+    RampCurve::Decibel
+}
+
+impl Default for RampSettings {
+    fn default() -> Self {
+        Self {
+            control_ms: default_control_ms(),
+            fade_ms: default_fade_ms(),
+            curve: default_curve(),
+        }
+    }
+}
+
+impl RampSettings {
+    pub fn control_duration(&self) -> Duration {
+        Duration::from_millis(self.control_ms)
+    }
+
+    pub fn fade_duration(&self) -> Duration {
+        Duration::from_millis(self.fade_ms)
+    }
+}
+
+/// Lets a caller wait for a ramp to reach its target. Dropping it leaves the ramp running.
+pub struct RampHandle(Option<JoinHandle<()>>);
+
+impl RampHandle {
+    /// A handle for a change that has already been written.
+    pub fn done() -> Self {
+        RampHandle(None)
+    }
+
+    pub fn wait(self) {
+        if let Some(handle) = self.0 {
+            let _ = handle.join();
+        }
+    }
+}
+
+static SETTINGS: Lazy<Mutex<RampSettings>> = Lazy::new(|| Mutex::new(RampSettings::default()));
+
+// The generation that currently owns each control, a ramp stops as soon as it is superseded
+static OWNERS: Lazy<Mutex<HashMap<String, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+// Where each running ramp is headed, by generation so a finished ramp can't clear its successor's
+static TARGETS: Lazy<Mutex<HashMap<String, (u64, i32)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// One per control, held across the ownership check and the write so a superseded ramp can't
+// land a stale step after its successor has written
+static WRITE_LOCKS: Lazy<Mutex<HashMap<String, Arc<Mutex<()>>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn settings() -> RampSettings {
+    SETTINGS.lock().map(|s| s.clone()).unwrap_or_default()
+}
+
+pub fn load_settings(app: &AppHandle) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    *SETTINGS.lock().map_err(|e| e.to_string())? = config.settings.ramp;
+    Ok(())
+}
+
+pub fn set_settings(app: &AppHandle, ramp_settings: RampSettings) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    storage.update(|config| config.settings.ramp = ramp_settings.clone()).map_err(|e| e.to_string())?;
+
+    *SETTINGS.lock().map_err(|e| e.to_string())? = ramp_settings;
+    Ok(())
+}
+
+fn claim(control_name: &str) -> u64 {
+    let generation = NEXT_GENERATION.fetch_add(1, Ordering::SeqCst);
+    if let Ok(mut owners) = OWNERS.lock() {
+        owners.insert(control_name.to_string(), generation);
+    }
+    generation
+}
+
+fn owns(control_name: &str, generation: u64) -> bool {
+    OWNERS.lock().map(|owners| owners.get(control_name) == Some(&generation)).unwrap_or(false)
+}
+
+/// Writes a value if `generation` still owns the control. Returns false if it has been superseded.
+fn write_owned(card_index: &str, control_name: &str, generation: u64, value: i32) -> Result<bool, String> {
+    let lock = WRITE_LOCKS.lock().map_err(|e| e.to_string())?.entry(control_name.to_string()).or_default().clone();
+    let _guard = lock.lock().map_err(|e| e.to_string())?;
+
+    if !owns(control_name, generation) {
+        return Ok(false);
+    }
+    volume::write_volume(card_index, control_name, value)?;
+    Ok(true)
+}
+
+fn set_target(control_name: &str, generation: u64, target: i32) {
+    if let Ok(mut targets) = TARGETS.lock() {
+        targets.insert(control_name.to_string(), (generation, target));
+    }
+}
+
+fn clear_target(control_name: &str, generation: u64) {
+    if let Ok(mut targets) = TARGETS.lock() {
+        if targets.get(control_name).is_some_and(|(owner, _)| *owner == generation) {
+            targets.remove(control_name);
+        }
+    }
+}
+
+/// The value a control is ramping towards, if a ramp is still running on it.
+pub fn pending_target(control_name: &str) -> Option<i32> {
+    let (generation, target) = *TARGETS.lock().ok()?.get(control_name)?;
+    owns(control_name, generation).then_some(target)
+}
+
+pub fn interpolate(control_name: &str, from: i32, to: i32, t: f64, curve: RampCurve) -> i32 {
+    let t = t.clamp(0.0, 1.0);
+
+    match curve {
+        RampCurve::Linear => from + ((to - from) as f64 * t).round() as i32,
+        RampCurve::SCurve => {
+            let eased = t * t * (3.0 - 2.0 * t);
+            from + ((to - from) as f64 * eased).round() as i32
+        }
+        RampCurve::Decibel => {
+            let from_db = db_scale::raw_to_db(control_name, from);
+            let to_db = db_scale::raw_to_db(control_name, to);
+            db_scale::db_to_raw(control_name, from_db + (to_db - from_db) * t)
+        }
+    }
+}
+
+/// Ramps a control from its current value to `target`.
+pub fn start(card_index: &str, control_name: &str, target: i32, duration: Duration, curve: RampCurve) -> Result<RampHandle, String> {
+    let from = volume::read_volume(card_index, control_name)?;
+    start_from(card_index, control_name, from, target, duration, curve)
+}
+
+/// Ramps a control from `from` to `target`, taking over from any ramp already running on it.
+/// Durations shorter than one step are written immediately.
+pub fn start_from(card_index: &str, control_name: &str, from: i32, target: i32, duration: Duration, curve: RampCurve) -> Result<RampHandle, String> {
+    let generation = claim(control_name);
+    let steps = (duration.as_millis() / STEP_INTERVAL.as_millis()) as u32;
+
+    if steps <= 1 || from == target {
+        write_owned(card_index, control_name, generation, target)?;
+        return Ok(RampHandle::done());
+    }
+
+    set_target(control_name, generation, target);
+    let card_index = card_index.to_string();
+    let control_name = control_name.to_string();
+
+    let handle = thread::spawn(move || {
+        run_steps(&card_index, &control_name, generation, from, target, steps, curve);
+        clear_target(&control_name, generation);
+    });
+
+    Ok(RampHandle(Some(handle)))
+}
+
+fn run_steps(card_index: &str, control_name: &str, generation: u64, from: i32, target: i32, steps: u32, curve: RampCurve) {
+    for step in 1..=steps {
+        let value = if step == steps {
+            target
+        } else {
+            interpolate(control_name, from, target, step as f64 / steps as f64, curve)
+        };
+
+        match write_owned(card_index, control_name, generation, value) {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                eprintln!("Ramp on {} stopped: {}", control_name, e);
+                return;
+            }
+        }
+
+        if step < steps {
+            thread::sleep(STEP_INTERVAL);
+        }
+    }
+}
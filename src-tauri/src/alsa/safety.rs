@@ -0,0 +1,133 @@
+use super::{db_scale, ramp, virtual_volume};
+use super::ramp::RampCurve;
+use super::volume::{self, OutputPair};
+use crate::storage::config::ConfigStorage;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::AppHandle;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OutputLimit {
+    /// Highest level the output can be set to
+    #[serde(default)]
+    pub max_db: Option<f64>,
+    /// Increases larger than this are ramped in steps of this size
+    #[serde(default)]
+    pub max_step_db: Option<f64>,
+    /// Level the output is brought down to at startup if it is above it
+    #[serde(default)]
+    pub power_on_db: Option<f64>,
+}
+
+// Limits per volume control name, both sides of a pair share the pair's limit
+static LIMITS: Lazy<Mutex<HashMap<String, OutputLimit>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn install_limits(limits: &HashMap<OutputPair, OutputLimit>) -> Result<(), String> {
+    let mut installed = LIMITS.lock().map_err(|e| e.to_string())?;
+    installed.clear();
+
+    for (output, limit) in limits {
+        let (left, right) = output.control_names();
+        installed.insert(left.to_string(), limit.clone());
+        installed.insert(right.to_string(), limit.clone());
+    }
+    Ok(())
+}
+
+pub fn load_limits(app: &AppHandle) -> Result<(), String> {
+    install_limits(&get_limits(app)?)
+}
+
+pub fn get_limits(app: &AppHandle) -> Result<HashMap<OutputPair, OutputLimit>, String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    Ok(config.settings.output_limits)
+}
+
+/// Stores a limit and fades the output down to a new cap if it is above it.
+pub fn set_limit(app: &AppHandle, card_index: &str, output: OutputPair, limit: OutputLimit) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let limits = storage
+        .update(|config| {
+            config.settings.output_limits.insert(output, limit);
+            config.settings.output_limits.clone()
+        })
+        .map_err(|e| e.to_string())?;
+
+    install_limits(&limits)?;
+
+    let (left, right) = output.control_names();
+    for control_name in [left, right] {
+        let current = volume::get_volume(card_index, control_name)?;
+        // A virtual output is reapplied as a whole, its crosspoints may be above the cap on their own
+        if clamp_volume(control_name, current) < current || virtual_volume::level(control_name).is_some() {
+            volume::fade_volume(card_index, control_name, current)?;
+        }
+    }
+    Ok(())
+}
+
+fn limit_for(control_name: &str) -> Option<OutputLimit> {
+    LIMITS.lock().ok()?.get(control_name).cloned()
+}
+
+pub fn clamp_volume(control_name: &str, volume: i32) -> i32 {
+    match limit_for(control_name).and_then(|limit| limit.max_db) {
+        Some(max_db) => volume.min(db_scale::db_to_raw(control_name, max_db)),
+        None => volume,
+    }
+}
+
+/// Caps what the device gets for a crosspoint feeding a virtual output. Without an output volume
+/// control each crosspoint is all that reaches the output, so each one is held to the output's cap.
+pub fn clamp_crosspoint(output_control: &str, control_name: &str, volume: i32) -> i32 {
+    match limit_for(output_control).and_then(|limit| limit.max_db) {
+        Some(max_db) => volume.min(db_scale::db_to_raw(control_name, max_db)),
+        None => volume,
+    }
+}
+
+/// Shortest ramp that keeps an increase within the output's step limit when ramped in dB.
+/// Decreases are never slowed down, turning things down fast is what you want when something is too loud.
+pub fn min_ramp_duration(control_name: &str, from: i32, to: i32) -> Duration {
+    let Some(max_step_db) = limit_for(control_name).and_then(|limit| limit.max_step_db) else {
+        return Duration::ZERO;
+    };
+
+    let distance = db_scale::raw_to_db(control_name, to) - db_scale::raw_to_db(control_name, from);
+    if to <= from || max_step_db <= 0.0 || distance <= max_step_db {
+        return Duration::ZERO;
+    }
+
+    let steps = (distance / max_step_db).ceil() as u32;
+    ramp::STEP_INTERVAL * steps
+}
+
+/// Brings every output with a power-on level down to it, outputs already below are left alone.
+pub fn apply_power_on_levels(card_index: &str) -> Result<(), String> {
+    let limits = LIMITS.lock().map_err(|e| e.to_string())?.clone();
+
+    for (control_name, limit) in limits {
+        let Some(power_on_db) = limit.power_on_db else {
+            continue;
+        };
+
+        let safe_volume = db_scale::db_to_raw(&control_name, power_on_db);
+        // A fade still running, e.g. from restoring the last state, counts at its target
+        let current = match virtual_volume::level(&control_name).or_else(|| ramp::pending_target(&control_name)) {
+            Some(level) => level,
+            None => volume::read_volume(card_index, &control_name)?,
+        };
+        if current > safe_volume {
+            println!("Lowering {} to its power-on level of {} dB", control_name, power_on_db);
+            // A virtual output volume is lowered by scaling its crosspoints
+            if !virtual_volume::set_output(card_index, &control_name, safe_volume, Duration::ZERO)? {
+                ramp::start_from(card_index, &control_name, current, safe_volume, Duration::ZERO, RampCurve::Decibel)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,249 @@
+use super::{crosspoint, db_scale, general, input_gain, phantom, switches, volume};
+use super::general::ControlValue;
+use crate::storage::config::{ConfigStorage, InputChannelConfig};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+/// Inputs that can be linked, channel configs are stored under the left one
+pub const STEREO_PAIRS: [(&str, &str); 2] = [("Mic-AN1", "Mic-AN2"), ("Line-IN3", "Line-IN4")];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct StereoLinkSettings {
+    /// Move the partner's gain by the same amount instead of matching it, keeping any offset between them
+    #[serde(default)]
+    pub preserve_gain_offset: bool,
+}
+
+#[derive(Default)]
+struct Links {
+    linked: HashSet<String>,
+    settings: StereoLinkSettings,
+}
+
+static LINKS: Lazy<Mutex<Links>> = Lazy::new(|| Mutex::new(Links::default()));
+
+// Held while both sides of a pair are written so two clients can't interleave
+static APPLY: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Reads which pairs are linked from the channel configs.
+pub fn load_links(app: &AppHandle) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+
+    let mut links = LINKS.lock().map_err(|e| e.to_string())?;
+    links.linked = STEREO_PAIRS
+        .iter()
+        .filter(|(left, _)| config.channels.get(*left).is_some_and(|channel| channel.stereo_coupled))
+        .map(|(left, _)| left.to_string())
+        .collect();
+    links.settings = config.settings.stereo_link;
+    Ok(())
+}
+
+pub fn get_settings() -> StereoLinkSettings {
+    LINKS.lock().map(|links| links.settings.clone()).unwrap_or_default()
+}
+
+pub fn set_settings(app: &AppHandle, settings: StereoLinkSettings) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    storage.update(|config| config.settings.stereo_link = settings.clone()).map_err(|e| e.to_string())?;
+
+    LINKS.lock().map_err(|e| e.to_string())?.settings = settings;
+    Ok(())
+}
+
+/// Stores an input's channel config, or removes it if `channel` is None, and links the pair if
+/// that makes it newly linked.
+pub fn save_channel(app: &AppHandle, card_index: &str, control_name: &str, channel: Option<InputChannelConfig>) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+
+    let newly_linked = storage
+        .update(|config| {
+            let was_linked = config.channels.get(control_name).is_some_and(|previous| previous.stereo_coupled);
+            let newly_linked = !was_linked && channel.as_ref().is_some_and(|channel| channel.stereo_coupled);
+            match channel {
+                Some(channel) => config.channels.insert(control_name.to_string(), channel),
+                None => config.channels.remove(control_name),
+            };
+            newly_linked
+        })
+        .map_err(|e| e.to_string())?;
+    load_links(app)?;
+
+    if newly_linked {
+        link_pair(card_index, control_name)?;
+    }
+    Ok(())
+}
+
+/// Pairs that are linked, left input first
+pub fn linked_pairs() -> Vec<(&'static str, &'static str)> {
+    let Ok(links) = LINKS.lock() else {
+        return Vec::new();
+    };
+    STEREO_PAIRS.iter().filter(|(left, _)| links.linked.contains(*left)).copied().collect()
+}
+
+fn partner_input(input: &str) -> Option<&'static str> {
+    let links = LINKS.lock().ok()?;
+    STEREO_PAIRS.iter().find_map(|(left, right)| {
+        if !links.linked.contains(*left) {
+            None
+        } else if input == *left {
+            Some(*right)
+        } else if input == *right {
+            Some(*left)
+        } else {
+            None
+        }
+    })
+}
+
+/// The other output of a stereo pair, "AN1" <-> "AN2", "PH3" <-> "PH4"
+fn sibling_route(route: &str) -> Option<&'static str> {
+    let index = crosspoint::OUTPUT_ROUTES.iter().position(|r| *r == route)?;
+    crosspoint::OUTPUT_ROUTES.get(index ^ 1).copied()
+}
+
+/// The control on the linked partner that mirrors this one, if the input is linked.
+/// "Mic-AN1 Gain" pairs with "Mic-AN2 Gain", and a crosspoint is mirrored across the
+/// output pair, so "Mic-AN1-AN2" pairs with "Mic-AN2-AN1". That mirrors the pan: a left
+/// input sent only to the left output puts its partner only on the right.
+pub fn partner_control(control_name: &str) -> Option<String> {
+    if let Some(xp) = crosspoint::parse_crosspoint(control_name) {
+        let partner = partner_input(&xp.source)?;
+        return Some(format!("{}-{}", partner, sibling_route(&xp.output)?));
+    }
+
+    let input = phantom::input_name(control_name);
+    let partner = partner_input(input)?;
+    Some(control_name.replacen(input, partner, 1))
+}
+
+/// The control plus its linked partner, everything a linked change can touch.
+pub fn affected_controls(control_name: &str) -> Vec<String> {
+    let mut controls = vec![control_name.to_string()];
+    controls.extend(partner_control(control_name));
+    controls
+}
+
+pub fn set_volume(card_index: &str, control_name: &str, value: i32) -> Result<(), String> {
+    let _guard = APPLY.lock().map_err(|e| e.to_string())?;
+
+    volume::set_volume(card_index, control_name, value)?;
+    if let Some(partner) = partner_control(control_name) {
+        volume::set_volume(card_index, &partner, value)?;
+    }
+    Ok(())
+}
+
+pub fn set_input_gain(card_index: &str, control_name: &str, gain: i32) -> Result<(), String> {
+    let _guard = APPLY.lock().map_err(|e| e.to_string())?;
+
+    let Some(partner) = partner_control(control_name) else {
+        return input_gain::set_input_gain(card_index, control_name, gain);
+    };
+
+    let partner_gain = if get_settings().preserve_gain_offset {
+        let current = input_gain::get_input_gain(card_index, control_name)?;
+        let partner_current = input_gain::get_input_gain(card_index, &partner)?;
+        let range = db_scale::get_range(&partner);
+        (partner_current + gain - current).clamp(range.raw_min, range.raw_max)
+    } else {
+        gain
+    };
+
+    input_gain::set_input_gain(card_index, control_name, gain)?;
+    input_gain::set_input_gain(card_index, &partner, partner_gain)
+}
+
+pub fn set_pad_state(card_index: &str, control_name: &str, new_state: bool) -> Result<(), String> {
+    let _guard = APPLY.lock().map_err(|e| e.to_string())?;
+
+    switches::set_pad_state(card_index, control_name, new_state)?;
+    if let Some(partner) = partner_control(control_name) {
+        switches::set_pad_state(card_index, &partner, new_state)?;
+    }
+    Ok(())
+}
+
+pub fn set_line_input_sensitivity(card_index: &str, control_name: &str, sensitivity: &str) -> Result<(), String> {
+    let _guard = APPLY.lock().map_err(|e| e.to_string())?;
+
+    switches::set_line_input_sensitivity(card_index, control_name, sensitivity)?;
+    if let Some(partner) = partner_control(control_name) {
+        switches::set_line_input_sensitivity(card_index, &partner, sensitivity)?;
+    }
+    Ok(())
+}
+
+/// Both sides have to pass the interlocks before either is switched, so a ribbon mic on
+/// the partner can't leave the pair half powered.
+pub fn set_phantom_power(app: &AppHandle, card_index: &str, control_name: &str, new_state: bool, confirmed: bool) -> Result<(), String> {
+    let _guard = APPLY.lock().map_err(|e| e.to_string())?;
+
+    let Some(partner) = partner_control(control_name) else {
+        return phantom::set_phantom_power(app, card_index, control_name, new_state, confirmed);
+    };
+
+    phantom::check_interlocks(app, control_name, new_state, confirmed)?;
+    phantom::check_interlocks(app, &partner, new_state, confirmed)?;
+
+    phantom::set_phantom_power(app, card_index, control_name, new_state, confirmed)?;
+    if let Err(e) = phantom::set_phantom_power(app, card_index, &partner, new_state, confirmed) {
+        // Switch the first side back rather than leave the pair half powered. Its crosspoints are
+        // still muted from the first change, so this goes straight to the switch.
+        return match switches::set_phantom_power(card_index, control_name, !new_state) {
+            Ok(()) => Err(e),
+            Err(rollback) => Err(format!("{}, and {} could not be switched back: {}", e, control_name, rollback)),
+        };
+    }
+    Ok(())
+}
+
+/// Called when a pair becomes linked. The partner takes over the left input's PAD, sensitivity
+/// and gain, and its crosspoints are set to mirror the left input's so the pair pans as one.
+/// 48V is left alone, it only ever changes through the phantom interlocks.
+pub fn link_pair(card_index: &str, left_input: &str) -> Result<(), String> {
+    let Some(right_input) = partner_input(left_input) else {
+        return Ok(());
+    };
+
+    let _guard = APPLY.lock().map_err(|e| e.to_string())?;
+    let mut errors = Vec::new();
+
+    for (name, value) in general::get_control_values(card_index)? {
+        if name.ends_with(" 48V") {
+            continue;
+        }
+
+        let mirrored = match crosspoint::parse_crosspoint(&name) {
+            Some(xp) if xp.source == left_input => partner_control(&name),
+            Some(_) => None,
+            None if phantom::input_name(&name) == left_input => partner_control(&name),
+            None => None,
+        };
+
+        let Some(partner) = mirrored else {
+            continue;
+        };
+
+        let result = match value {
+            ControlValue::Volume(level) => volume::fade_volume(card_index, &partner, level).map(|_| ()),
+            other => general::set_control_value(card_index, &partner, &other),
+        };
+        if let Err(e) = result {
+            errors.push(format!("{}: {}", partner, e));
+        }
+    }
+
+    if errors.is_empty() {
+        println!("Linked {} and {}", left_input, right_input);
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
@@ -1,12 +1,15 @@
+use super::phantom;
 use std::process::Command;
 
 pub fn set_pad_state(card_index: &str, switch_name: &str, new_state: bool) -> Result<(), String> {
     let state = if new_state { "on" } else { "off" };
-    
+
+    phantom::mark_pad_change(switch_name);
     let output = Command::new("amixer")
         .args(&["-c", &card_index, "set", switch_name, state])
         .output()
         .map_err(|e| e.to_string())?;
+    phantom::mark_pad_change(switch_name);
     
     if output.status.success() {
         Ok(())
@@ -0,0 +1,273 @@
+use super::{crosspoint, db_scale, general, hold, ramp, safety, volume};
+use super::general::ControlValue;
+use super::volume::OutputPair;
+use crate::capabilities::detect::Capabilities;
+use crate::storage::config::ConfigStorage;
+use once_cell::sync::Lazy;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::AppHandle;
+
+// Kernels before 6.12 have no output volume controls. Their outputs get a virtual volume
+// instead, a dB offset on every crosspoint feeding the output, inputs and PCM alike. Clients
+// keep using the "Main-Out" control names and see each crosspoint at its own level.
+
+struct VirtualOutput {
+    /// The output's volume control name, e.g. "Main-Out AN1"
+    control_name: &'static str,
+    /// Added to every crosspoint feeding the output, at or below the lowest dB it is silent
+    offset_db: f64,
+    /// Each crosspoint's own level, what the device gets at an offset of 0 dB
+    levels: HashMap<String, i32>,
+}
+
+/// Keyed by route, e.g. "AN1"
+static OUTPUTS: Lazy<Mutex<HashMap<&'static str, VirtualOutput>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Every output volume control with its route, e.g. ("Main-Out AN1", "AN1")
+fn output_controls() -> impl Iterator<Item = (&'static str, &'static str)> {
+    OutputPair::ALL.into_iter().flat_map(|pair| {
+        let (left, right) = pair.control_names();
+        let (left_route, right_route) = pair.routes();
+        [(left, left_route), (right, right_route)]
+    })
+}
+
+fn output_route(control_name: &str) -> Option<&'static str> {
+    output_controls().find(|(control, _)| *control == control_name).map(|(_, route)| route)
+}
+
+fn is_silent(control_name: &str, level: i32) -> bool {
+    level <= 0 || db_scale::get_db_value(control_name, level).muted
+}
+
+/// What the device gets for a crosspoint's own level, within the output's level cap
+fn device_level(output_control: &str, control_name: &str, level: i32, offset_db: f64) -> i32 {
+    if is_silent(control_name, level) {
+        return level;
+    }
+    if offset_db <= volume::MIN_DB {
+        return 0;
+    }
+    let raw = db_scale::db_to_raw(control_name, db_scale::raw_to_db(control_name, level) + offset_db);
+    safety::clamp_crosspoint(output_control, control_name, raw)
+}
+
+/// A crosspoint's own level worked back from the device. Lossy where the offset pushed it
+/// against either end of the scale.
+fn own_level(control_name: &str, device: i32, offset_db: f64) -> i32 {
+    if is_silent(control_name, device) {
+        return device;
+    }
+    db_scale::db_to_raw(control_name, db_scale::raw_to_db(control_name, device) - offset_db)
+}
+
+fn device_volumes(card_index: &str) -> Result<HashMap<String, i32>, String> {
+    Ok(general::get_control_values(card_index)?
+        .into_iter()
+        .filter_map(|(name, value)| match value {
+            ControlValue::Volume(volume) => Some((name, volume)),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Sets up a virtual volume for every output the device has no volume control for. The offsets
+/// come back from the last state, and own levels too where the device still matches them.
+pub fn load(app: &AppHandle, card_index: &str, capabilities: &Capabilities) -> Result<usize, String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let stored = storage.load_config().map_err(|e| e.to_string())?.last_state;
+    let device = device_volumes(card_index)?;
+
+    let mut outputs = HashMap::new();
+    for pair in OutputPair::ALL.iter().filter(|pair| !capabilities.output_volume.contains(pair)) {
+        let (left, right) = pair.control_names();
+        let (left_route, right_route) = pair.routes();
+
+        for (control_name, route) in [(left, left_route), (right, right_route)] {
+            let offset_db = match stored.get(control_name) {
+                Some(ControlValue::Volume(raw)) => volume::raw_to_db(*raw),
+                _ => 0.0,
+            };
+
+            let levels: HashMap<String, i32> = device
+                .iter()
+                .filter(|(name, _)| crosspoint::parse_crosspoint(name).is_some_and(|xp| xp.output == route))
+                .map(|(name, value)| {
+                    let level = match stored.get(name) {
+                        Some(ControlValue::Volume(level)) if device_level(control_name, name, *level, offset_db) == *value => *level,
+                        _ => own_level(name, *value, offset_db),
+                    };
+                    (name.clone(), level)
+                })
+                .collect();
+
+            if !levels.is_empty() {
+                outputs.insert(
+                    route,
+                    VirtualOutput {
+                        control_name,
+                        offset_db,
+                        levels,
+                    },
+                );
+            }
+        }
+    }
+
+    let count = outputs.len();
+    *OUTPUTS.lock().map_err(|e| e.to_string())? = outputs;
+    Ok(count)
+}
+
+/// The level a client sees: the volume of a virtual output, or the own level of a crosspoint
+/// feeding one. None for everything else.
+pub fn level(control_name: &str) -> Option<i32> {
+    let outputs = OUTPUTS.lock().ok()?;
+    if let Some(route) = output_route(control_name) {
+        return outputs.get(route).map(|output| volume::db_to_raw(output.offset_db));
+    }
+
+    let xp = crosspoint::parse_crosspoint(control_name)?;
+    outputs.get(xp.output.as_str())?.levels.get(control_name).copied()
+}
+
+pub fn is_output(control_name: &str) -> bool {
+    output_route(control_name).is_some_and(|route| OUTPUTS.lock().is_ok_and(|outputs| outputs.contains_key(route)))
+}
+
+/// Crosspoints feeding a virtual output
+fn output_crosspoints(control_name: &str) -> Vec<String> {
+    let Some(route) = output_route(control_name) else {
+        return Vec::new();
+    };
+    OUTPUTS
+        .lock()
+        .ok()
+        .and_then(|outputs| outputs.get(route).map(|output| output.levels.keys().cloned().collect()))
+        .unwrap_or_default()
+}
+
+/// Moves a virtual output's volume by scaling its crosspoints. Returns false for controls that
+/// aren't a virtual output, those are written as usual. While the monitor section holds the
+/// output the new volume is what it returns to.
+pub fn set_output(card_index: &str, control_name: &str, volume: i32, duration: Duration) -> Result<bool, String> {
+    if !is_output(control_name) {
+        return Ok(false);
+    }
+    if hold::update_held_value(card_index, control_name, volume)? {
+        return Ok(true);
+    }
+
+    // Asked before OUTPUTS is locked, the section locks it too while it applies
+    let held: HashMap<String, i32> = output_crosspoints(control_name)
+        .into_iter()
+        .filter_map(|name| hold::held_value(&name).map(|value| (name, value)))
+        .collect();
+    scale_output(card_index, control_name, volume, duration, &held)?;
+
+    // Held crosspoints are the section's to write. Handing it one back makes it work all of
+    // them out again at the new offset.
+    if let Some((name, value)) = held.into_iter().next() {
+        hold::update_held_value(card_index, &name, value)?;
+    }
+    Ok(true)
+}
+
+/// `set_output` for the monitor section, which holds the output and keeps its own lock while it
+/// applies. Nothing goes back to the section, and the crosspoints in `held` are left for it.
+pub fn set_held_output(card_index: &str, control_name: &str, volume: i32, duration: Duration, held: &HashMap<String, i32>) -> Result<bool, String> {
+    scale_output(card_index, control_name, volume, duration, held)
+}
+
+fn scale_output(card_index: &str, control_name: &str, volume: i32, duration: Duration, held: &HashMap<String, i32>) -> Result<bool, String> {
+    let Some(route) = output_route(control_name) else {
+        return Ok(false);
+    };
+    let mut outputs = OUTPUTS.lock().map_err(|e| e.to_string())?;
+    let Some(output) = outputs.get_mut(route) else {
+        return Ok(false);
+    };
+
+    let current = device_volumes(card_index)?;
+
+    // A crosspoint changed outside the app (alsamixer, another mixer) keeps its new level
+    for (name, level) in output.levels.iter_mut() {
+        let settled = ramp::pending_target(name).is_none() && !held.contains_key(name);
+        if let Some(&value) = current.get(name) {
+            if settled && device_level(output.control_name, name, *level, output.offset_db) != value {
+                *level = own_level(name, value, output.offset_db);
+            }
+        }
+    }
+
+    let from = volume::db_to_raw(output.offset_db);
+    output.offset_db = volume::raw_to_db(volume);
+    // The output's step limit sets the pace, and the curve, for all of its crosspoints
+    let (duration, curve) = volume::limited_ramp(control_name, from, volume, duration, ramp::settings().curve);
+
+    for (name, level) in output.levels.iter().filter(|(name, _)| !held.contains_key(*name)) {
+        let Some(&from) = current.get(name) else {
+            continue;
+        };
+        let target = device_level(output.control_name, name, *level, output.offset_db);
+        let (duration, curve) = volume::limited_ramp(name, from, target, duration, curve);
+        ramp::start_from(card_index, name, from, target, duration, curve)?;
+    }
+    Ok(true)
+}
+
+/// What the device gets for a client's value: a crosspoint feeding a virtual output at its own
+/// level plus the offset, anything else as it is. Nothing is stored.
+pub fn device_value(control_name: &str, level: i32) -> i32 {
+    let Some(xp) = crosspoint::parse_crosspoint(control_name) else {
+        return level;
+    };
+    let Ok(outputs) = OUTPUTS.lock() else {
+        return level;
+    };
+    match outputs.get(xp.output.as_str()) {
+        Some(output) => device_level(output.control_name, control_name, level, output.offset_db),
+        None => level,
+    }
+}
+
+/// Stores a new own level for a crosspoint feeding a virtual output and returns what the device
+/// should get. Other controls come back unchanged.
+pub fn apply_offset(control_name: &str, level: i32) -> i32 {
+    let Some(xp) = crosspoint::parse_crosspoint(control_name) else {
+        return level;
+    };
+    let Ok(mut outputs) = OUTPUTS.lock() else {
+        return level;
+    };
+
+    match outputs.get_mut(xp.output.as_str()) {
+        Some(output) => {
+            output.levels.insert(control_name.to_string(), level);
+            device_level(output.control_name, control_name, level, output.offset_db)
+        }
+        None => level,
+    }
+}
+
+/// Turns device values into what clients see: own levels for the crosspoints, plus the volume
+/// of every virtual output. Used wherever control values are stored or compared.
+pub fn logical_values(values: &mut BTreeMap<String, ControlValue>) {
+    let Ok(outputs) = OUTPUTS.lock() else {
+        return;
+    };
+
+    for (control_name, route) in output_controls() {
+        let Some(output) = outputs.get(route) else {
+            continue;
+        };
+        values.insert(control_name.to_string(), ControlValue::Volume(volume::db_to_raw(output.offset_db)));
+        for (name, level) in &output.levels {
+            if let Some(value) = values.get_mut(name) {
+                *value = ControlValue::Volume(*level);
+            }
+        }
+    }
+}
@@ -1,7 +1,70 @@
-use super::general;
+use super::{db_scale, general, hold, ramp, safety, stereo_link, virtual_volume};
+use super::ramp::{RampCurve, RampHandle};
+use serde::{Deserialize, Serialize};
 use std::process::Command;
+use std::time::Duration;
 
 pub fn set_volume(card_index: &str, control_name: &str, volume: i32) -> Result<(), String> {
+    set_volume_over(card_index, control_name, volume, ramp::settings().control_duration()).map(|_| ())
+}
+
+/// Like `set_volume`, but with the longer fade used for switched changes such as preset recall.
+pub fn fade_volume(card_index: &str, control_name: &str, volume: i32) -> Result<RampHandle, String> {
+    set_volume_over(card_index, control_name, volume, ramp::settings().fade_duration())
+}
+
+fn set_volume_over(card_index: &str, control_name: &str, volume: i32, duration: Duration) -> Result<RampHandle, String> {
+    let volume = safety::clamp_volume(control_name, volume);
+
+    // Outputs without a volume control scale their crosspoints instead
+    if virtual_volume::set_output(card_index, control_name, volume, duration)? {
+        return Ok(RampHandle::done());
+    }
+    let device_volume = virtual_volume::apply_offset(control_name, volume);
+    // While the monitor section holds a control (dim, mute, mono...) the new value becomes the
+    // level it returns to, as clients see it, and the section works out what the device gets
+    if hold::update_held_value(card_index, control_name, volume)? {
+        return Ok(RampHandle::done());
+    }
+
+    let from = read_volume(card_index, control_name)?;
+    write_over(card_index, control_name, from, device_volume, duration, ramp::settings().curve)
+}
+
+/// Stretches a ramp to keep within the control's step limit. Step limits are worked out in dB,
+/// evenly over the steps, so a limited ramp always runs in dB. A raw curve, however long, would
+/// bunch the dB change into the quiet steps.
+pub fn limited_ramp(control_name: &str, from: i32, to: i32, duration: Duration, curve: RampCurve) -> (Duration, RampCurve) {
+    let min_duration = safety::min_ramp_duration(control_name, from, to);
+    if min_duration > Duration::ZERO {
+        (duration.max(min_duration), RampCurve::Decibel)
+    } else {
+        (duration, curve)
+    }
+}
+
+/// Ramps a control from `from`, within its step limit. Below the virtual volume and the
+/// monitor section.
+fn write_over(card_index: &str, control_name: &str, from: i32, volume: i32, duration: Duration, curve: RampCurve) -> Result<RampHandle, String> {
+    let (duration, curve) = limited_ramp(control_name, from, volume, duration, curve);
+    ramp::start_from(card_index, control_name, from, volume, duration, curve)
+}
+
+/// Sets a volume in dB, rounded to the nearest raw step. Returns what was actually applied,
+/// which may be lower than asked for if the output has a level cap. Linked inputs follow.
+pub fn set_volume_db(card_index: &str, control_name: &str, db: f64) -> Result<db_scale::DbValue, String> {
+    let volume = safety::clamp_volume(control_name, db_scale::db_to_raw(control_name, db));
+    stereo_link::set_volume(card_index, control_name, volume)?;
+    Ok(db_scale::get_db_value(control_name, volume))
+}
+
+pub fn get_volume_db(card_index: &str, control_name: &str) -> Result<db_scale::DbValue, String> {
+    let volume = get_volume(card_index, control_name)?;
+    Ok(db_scale::get_db_value(control_name, volume))
+}
+
+/// Writes a raw value straight to the device, bypassing the monitor section.
+pub fn write_volume(card_index: &str, control_name: &str, volume: i32) -> Result<(), String> {
 
     let output = Command::new("amixer")
         .args(&[
@@ -22,6 +85,19 @@ pub fn set_volume(card_index: &str, control_name: &str, volume: i32) -> Result<(
 }
 
 pub fn get_volume(card_index: &str, control_name: &str) -> Result<i32, String> {
+    // Held values are client values too, a held virtual output's level is the dimmed one
+    if let Some(volume) = hold::held_value(control_name) {
+        return Ok(volume);
+    }
+    if let Some(level) = virtual_volume::level(control_name) {
+        return Ok(level);
+    }
+
+    read_volume(card_index, control_name)
+}
+
+/// Reads the value currently on the device, even if the monitor section is attenuating it.
+pub fn read_volume(card_index: &str, control_name: &str) -> Result<i32, String> {
 
     let output = Command::new("amixer")
         .args(&["-c", &card_index, "get", control_name])
@@ -35,4 +111,84 @@ pub fn get_volume(card_index: &str, control_name: &str) -> Result<i32, String> {
     } else {
         Err(String::from_utf8_lossy(&output.stderr).to_string())
     }
-}
\ No newline at end of file
+}
+
+// Matches the scale used by alsaValConversion.ts in the frontend, where 32768 is unity gain
+pub const MIN_DB: f64 = -65.0;
+pub const MAX_DB: f64 = 6.0;
+pub const MAX_RAW: i32 = 65535;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputPair {
+    Main,
+    Headphones,
+    Spdif,
+    Adat34,
+    Adat56,
+    Adat78,
+}
+
+impl OutputPair {
+    pub const ALL: [OutputPair; 6] = [
+        OutputPair::Main,
+        OutputPair::Headphones,
+        OutputPair::Spdif,
+        OutputPair::Adat34,
+        OutputPair::Adat56,
+        OutputPair::Adat78,
+    ];
+
+    /// Output names as used in the crosspoint controls, e.g. "Mic-AN1-PH3"
+    pub fn routes(&self) -> (&'static str, &'static str) {
+        match self {
+            OutputPair::Main => ("AN1", "AN2"),
+            OutputPair::Headphones => ("PH3", "PH4"),
+            OutputPair::Spdif => ("AS1", "AS2"),
+            OutputPair::Adat34 => ("ADAT3", "ADAT4"),
+            OutputPair::Adat56 => ("ADAT5", "ADAT6"),
+            OutputPair::Adat78 => ("ADAT7", "ADAT8"),
+        }
+    }
+
+    pub fn control_names(&self) -> (&'static str, &'static str) {
+        match self {
+            OutputPair::Main => ("Main-Out AN1", "Main-Out AN2"),
+            OutputPair::Headphones => ("Main-Out PH3", "Main-Out PH4"),
+            OutputPair::Spdif => ("Main-Out AS1", "Main-Out AS2"),
+            OutputPair::Adat34 => ("Main-Out ADAT3", "Main-Out ADAT4"),
+            OutputPair::Adat56 => ("Main-Out ADAT5", "Main-Out ADAT6"),
+            OutputPair::Adat78 => ("Main-Out ADAT7", "Main-Out ADAT8"),
+        }
+    }
+}
+
+pub fn raw_to_db(raw: i32) -> f64 {
+    let raw = raw.clamp(0, MAX_RAW);
+    if raw == 0 {
+        return MIN_DB;
+    }
+
+    let db = 20.0 * (raw as f64 / 32768.0).log10();
+    db.clamp(MIN_DB, MAX_DB)
+}
+
+pub fn db_to_raw(db: f64) -> i32 {
+    let db = db.clamp(MIN_DB, MAX_DB);
+    if db <= MIN_DB {
+        return 0;
+    }
+
+    let raw = (32768.0 * 10f64.powf(db / 20.0)).round() as i32;
+    raw.clamp(0, MAX_RAW)
+}
+
+pub fn step_output_volume(card_index: &str, output: OutputPair, step_db: f64) -> Result<i32, String> {
+    let (left, right) = output.control_names();
+    let current = get_volume(card_index, left)?;
+    let new_volume = db_scale::db_to_raw(left, db_scale::raw_to_db(left, current) + step_db);
+
+    set_volume(card_index, left, new_volume)?;
+    set_volume(card_index, right, new_volume)?;
+    Ok(new_volume)
+}
@@ -0,0 +1,143 @@
+use crate::alsa::{db_scale, general, input_gain, stereo_link};
+use crate::history::journal::{self, Origin};
+use crate::pipewire::audio::{self, BlockInfo};
+use crate::pipewire::loopback;
+use crate::pipewire::tap::{self, TapSource};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_TARGET_PEAK_DB: f64 = -12.0;
+const MIN_SECONDS: f64 = 1.0;
+const MAX_SECONDS: f64 = 30.0;
+// Below this nobody was speaking or playing, a gain computed from it would be far too high
+const SILENCE_DB: f64 = -60.0;
+const CLIP_LEVEL: f32 = 0.999;
+// Closer than this to the target the gain is left alone
+const TOLERANCE_DB: f64 = 0.5;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct AutoGainReport {
+    pub input: String,
+    pub gain_control: String,
+    /// Length of audio that was measured
+    pub seconds: f64,
+    pub peak_dbfs: f64,
+    pub rms_dbfs: f64,
+    /// Samples at full scale, the real peak was higher than measured when this is not 0
+    pub clipped_samples: u64,
+    pub target_peak_dbfs: f64,
+    pub previous_gain_db: f64,
+    pub new_gain_db: f64,
+    /// Peak the measured signal would have reached with the new gain
+    pub expected_peak_dbfs: f64,
+    /// Set when the gain range couldn't reach the target
+    pub limited: bool,
+}
+
+#[derive(Default)]
+struct Levels {
+    frames: u64,
+    rate: u32,
+    peak: f32,
+    sum_squares: f64,
+    clipped: u64,
+    error: Option<String>,
+}
+
+fn to_dbfs(level: f64) -> f64 {
+    if level > 0.0 { 20.0 * level.log10() } else { f64::NEG_INFINITY }
+}
+
+/// Captures `seconds` of the input from the input tap and returns its levels.
+fn measure(input: &str, seconds: f64) -> Result<Levels, String> {
+    let (_, channel) = audio::capture_target(input)?;
+
+    let levels = Arc::new(Mutex::new(Levels::default()));
+    let shared = levels.clone();
+    let on_samples = Box::new(move |samples: &[f32], info: BlockInfo| {
+        let Ok(mut levels) = shared.lock() else {
+            return;
+        };
+        if channel >= info.channels {
+            levels.error = Some(format!("The capture has only {} channels", info.channels));
+            return;
+        }
+
+        levels.rate = info.rate;
+        for frame in samples.chunks_exact(info.channels) {
+            let sample = frame[channel].abs();
+            levels.peak = levels.peak.max(sample);
+            levels.sum_squares += (sample as f64) * (sample as f64);
+            if sample >= CLIP_LEVEL {
+                levels.clipped += 1;
+            }
+            levels.frames += 1;
+        }
+    });
+
+    let tap = tap::subscribe(TapSource::Inputs, on_samples)?;
+    thread::sleep(Duration::from_secs_f64(seconds));
+    drop(tap);
+
+    let mut levels = levels.lock().map_err(|e| e.to_string())?;
+    if let Some(e) = levels.error.take() {
+        return Err(e);
+    }
+    if levels.frames == 0 {
+        return Err(format!("No audio was captured from {}", input));
+    }
+    Ok(std::mem::take(&mut *levels))
+}
+
+/// Listens to the input for `seconds` while the user speaks or plays, then ramps its gain so the
+/// loudest moment lands at `target_peak_db`. A linked partner follows.
+pub fn run(card_index: &str, input: &str, seconds: f64, target_peak_db: f64) -> Result<AutoGainReport, String> {
+    // Shared with the measurements, a noise floor run sets and records the same gains
+    let _claim = loopback::claim()?;
+
+    let gain_control = format!("{} Gain", input);
+    general::get_control_value(card_index, &gain_control).map_err(|_| format!("{} has no gain control", input))?;
+
+    let seconds = seconds.clamp(MIN_SECONDS, MAX_SECONDS);
+    let levels = measure(input, seconds)?;
+
+    let peak_dbfs = to_dbfs(levels.peak as f64);
+    let rms_dbfs = to_dbfs((levels.sum_squares / levels.frames as f64).sqrt());
+    if peak_dbfs < SILENCE_DB {
+        return Err(format!(
+            "{} peaked at {:.1} dBFS, too quiet to set the gain from. Check the cable and speak or play during the measurement",
+            input, peak_dbfs
+        ));
+    }
+
+    let previous = input_gain::get_input_gain_db(card_index, &gain_control)?;
+    let range = db_scale::get_range(&gain_control);
+    let wanted_db = previous.db + target_peak_db - peak_dbfs;
+    let new_raw = db_scale::db_to_raw(&gain_control, wanted_db.clamp(range.min_db, range.max_db));
+    let new_gain_db = db_scale::get_db_value(&gain_control, new_raw).db;
+
+    let change = (new_gain_db - previous.db).abs() >= TOLERANCE_DB;
+    if change {
+        journal::record(Origin::AutoGain, card_index, &stereo_link::affected_controls(&gain_control), || {
+            stereo_link::set_input_gain(card_index, &gain_control, new_raw)
+        })?;
+    }
+
+    let applied_db = if change { new_gain_db } else { previous.db };
+
+    Ok(AutoGainReport {
+        input: input.to_string(),
+        gain_control,
+        seconds: levels.frames as f64 / levels.rate.max(1) as f64,
+        peak_dbfs,
+        rms_dbfs,
+        clipped_samples: levels.clipped,
+        target_peak_dbfs: target_peak_db,
+        previous_gain_db: previous.db,
+        new_gain_db: applied_db,
+        expected_peak_dbfs: peak_dbfs + applied_db - previous.db,
+        limited: wanted_db < range.min_db || wanted_db > range.max_db,
+    })
+}
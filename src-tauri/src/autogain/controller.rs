@@ -0,0 +1,13 @@
+use crate::AppState;
+use tauri::{AppHandle, Emitter, State};
+use super::assistant::{self, AutoGainReport};
+
+/// Runs off the main thread, the measurement takes as long as `seconds`
+#[tauri::command(async)]
+pub fn run_auto_gain(app_handle: AppHandle, state: State<AppState>, input: String, seconds: f64, target_peak_db: Option<f64>) -> Result<AutoGainReport, String> {
+    let card_number = &state.alsa_card_number;
+    let target = target_peak_db.unwrap_or(assistant::DEFAULT_TARGET_PEAK_DB);
+    let report = assistant::run(card_number, &input, seconds, target)?;
+    let _ = app_handle.emit("controls-changed", ());
+    Ok(report)
+}
@@ -0,0 +1,2 @@
+pub mod assistant;
+pub mod controller;
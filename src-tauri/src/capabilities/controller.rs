@@ -0,0 +1,14 @@
+use crate::AppState;
+use super::detect::{self, Capabilities};
+use tauri::State;
+
+#[tauri::command]
+pub fn get_capabilities(state: State<AppState>) -> Result<Capabilities, String> {
+    detect::current(&state.alsa_card_number)
+}
+
+/// Detects again, e.g. after a kernel module reload
+#[tauri::command]
+pub fn refresh_capabilities(state: State<AppState>) -> Result<Capabilities, String> {
+    detect::load(&state.alsa_card_number)
+}
@@ -0,0 +1,219 @@
+use crate::alsa::crosspoint;
+use crate::alsa::general;
+use crate::alsa::stereo_link::STEREO_PAIRS;
+use crate::alsa::volume::OutputPair;
+use crate::pipewire::general::{self as pipewire_general, CARD_NAME};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::process::Command;
+use std::sync::Mutex;
+
+// The first kernel whose driver has the input gain and output volume controls
+const FULL_SUPPORT_KERNEL: (u32, u32) = (6, 12);
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct KernelVersion {
+    /// As in `uname -r`, e.g. "6.11.0-19-generic"
+    pub release: String,
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl KernelVersion {
+    fn parse(release: &str) -> Self {
+        let mut numbers = release
+            .split(|c: char| !c.is_ascii_digit())
+            .take(3)
+            .map(|part| part.parse().unwrap_or(0));
+        Self {
+            release: release.to_string(),
+            major: numbers.next().unwrap_or(0),
+            minor: numbers.next().unwrap_or(0),
+            patch: numbers.next().unwrap_or(0),
+        }
+    }
+
+    pub fn at_least(&self, major: u32, minor: u32) -> bool {
+        (self.major, self.minor) >= (major, minor)
+    }
+}
+
+/// Switch controls by kind, full control names
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Switches {
+    pub phantom: Vec<String>,
+    pub pad: Vec<String>,
+    pub line_sensitivity: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct MixerTopology {
+    /// Every control on the card
+    pub controls: usize,
+    /// Inputs and playback channels that feed the mixer, e.g. "Mic-AN1" or "PCM-AN1"
+    pub sources: usize,
+    pub outputs: usize,
+    pub crosspoints: usize,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct PipeWireSupport {
+    /// A PipeWire server answers on the Pulse protocol
+    pub available: bool,
+    pub version: Option<String>,
+    /// The Babyface shows up as a PipeWire card
+    pub card: bool,
+}
+
+/// What the device supports with the running kernel and audio stack
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Capabilities {
+    pub kernel: KernelVersion,
+    /// Inputs with a gain control, e.g. "Mic-AN1"
+    pub input_gain: Vec<String>,
+    /// Output pairs with a volume control. Main is missing on kernels before 6.12.
+    pub output_volume: Vec<OutputPair>,
+    /// No gain or main volume, crosspoints stand in for the main volume
+    pub compatibility_mode: bool,
+    pub switches: Switches,
+    pub mixer: MixerTopology,
+    pub pipewire: PipeWireSupport,
+}
+
+static CURRENT: Lazy<Mutex<Option<Capabilities>>> = Lazy::new(|| Mutex::new(None));
+
+fn kernel_version() -> KernelVersion {
+    let release = fs::read_to_string("/proc/sys/kernel/osrelease")
+        .ok()
+        .or_else(|| Command::new("uname").arg("-r").output().ok().map(|output| String::from_utf8_lossy(&output.stdout).to_string()))
+        .unwrap_or_default();
+    KernelVersion::parse(release.trim())
+}
+
+fn pipewire_support() -> PipeWireSupport {
+    let output = Command::new("pactl").arg("info").output();
+    let server = output
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+        .unwrap_or_default();
+
+    // e.g. "Server Name: PulseAudio (on PipeWire 1.0.5)"
+    let version_re = Regex::new(r"on PipeWire ([\w.\-]+)").unwrap();
+    let version = version_re.captures(&server).map(|caps| caps[1].to_string());
+
+    PipeWireSupport {
+        available: version.is_some(),
+        version,
+        card: pipewire_general::get_card_id_by_name(CARD_NAME).is_ok(),
+    }
+}
+
+/// Works out the capabilities from the card's controls. Kernels before 6.12 are told apart by
+/// the controls they lack rather than the version, as distributions backport the driver.
+pub fn detect(card_index: &str) -> Result<Capabilities, String> {
+    let controls = general::get_soundcard_controls(card_index)?;
+    let has = |name: &str| controls.contains_key(name);
+
+    let input_gain: Vec<String> = STEREO_PAIRS
+        .iter()
+        .flat_map(|(left, right)| [*left, *right])
+        .filter(|input| has(&format!("{} Gain", input)))
+        .map(|input| input.to_string())
+        .collect();
+
+    let output_volume: Vec<OutputPair> = OutputPair::ALL
+        .into_iter()
+        .filter(|pair| {
+            let (left, right) = pair.control_names();
+            has(left) && has(right)
+        })
+        .collect();
+
+    let mut names: Vec<&String> = controls.keys().collect();
+    names.sort();
+    let ending = |suffix: &str| names.iter().filter(|name| name.ends_with(suffix)).map(|name| name.to_string()).collect();
+    let switches = Switches {
+        phantom: ending(" 48V"),
+        pad: ending(" PAD"),
+        line_sensitivity: ending(" Sens."),
+    };
+
+    let crosspoints: Vec<_> = names.iter().filter_map(|name| crosspoint::parse_crosspoint(name)).collect();
+    let mixer = MixerTopology {
+        controls: controls.len(),
+        sources: crosspoints.iter().map(|xp| xp.source.as_str()).collect::<BTreeSet<_>>().len(),
+        outputs: crosspoints.iter().map(|xp| xp.output.as_str()).collect::<BTreeSet<_>>().len(),
+        crosspoints: crosspoints.len(),
+    };
+
+    let kernel = kernel_version();
+    let compatibility_mode = input_gain.is_empty() || !output_volume.contains(&OutputPair::Main);
+    if compatibility_mode && kernel.at_least(FULL_SUPPORT_KERNEL.0, FULL_SUPPORT_KERNEL.1) {
+        eprintln!("Kernel {} should have gain and volume controls, but they are missing", kernel.release);
+    }
+
+    Ok(Capabilities {
+        kernel,
+        input_gain,
+        output_volume,
+        compatibility_mode,
+        switches,
+        mixer,
+        pipewire: pipewire_support(),
+    })
+}
+
+/// Detects the capabilities once, later calls reuse them.
+pub fn load(card_index: &str) -> Result<Capabilities, String> {
+    let capabilities = detect(card_index)?;
+    *CURRENT.lock().map_err(|e| e.to_string())? = Some(capabilities.clone());
+    Ok(capabilities)
+}
+
+pub fn current(card_index: &str) -> Result<Capabilities, String> {
+    if let Some(capabilities) = CURRENT.lock().map_err(|e| e.to_string())?.clone() {
+        return Ok(capabilities);
+    }
+    load(card_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(release: &str) -> (u32, u32, u32) {
+        let kernel = KernelVersion::parse(release);
+        (kernel.major, kernel.minor, kernel.patch)
+    }
+
+    #[test]
+    fn parses_distribution_releases() {
+        assert_eq!(version("6.11.0-19-generic"), (6, 11, 0));
+        assert_eq!(version("6.12.1-arch1"), (6, 12, 1));
+        assert_eq!(version("6.12.9-200.fc41.x86_64"), (6, 12, 9));
+    }
+
+    #[test]
+    fn ignores_release_candidate_suffixes() {
+        assert_eq!(version("6.11.0-rc3"), (6, 11, 0));
+        assert_eq!(version("6.12-rc1"), (6, 12, 0));
+    }
+
+    #[test]
+    fn unreadable_release_is_zero() {
+        assert_eq!(version(""), (0, 0, 0));
+        assert!(!KernelVersion::parse("").at_least(6, 12));
+    }
+
+    #[test]
+    fn compares_major_and_minor() {
+        assert!(KernelVersion::parse("6.12.1-arch1").at_least(6, 12));
+        assert!(KernelVersion::parse("7.0.0").at_least(6, 12));
+        assert!(!KernelVersion::parse("6.11.0-rc3").at_least(6, 12));
+    }
+}
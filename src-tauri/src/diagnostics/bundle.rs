@@ -0,0 +1,292 @@
+use super::usb;
+use crate::alsa::general;
+use crate::capabilities::detect;
+use crate::pipewire::general::CARD_NAME;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+// Everything in the archive goes under this directory
+const ROOT: &str = "babyface-diagnostics";
+// Only the end of longer logs is kept
+const MAX_LOG_BYTES: usize = 1024 * 1024;
+// Deep enough for e.g. /proc/asound/card1/pcm0p/sub0/hw_params
+const MAX_PROC_DEPTH: usize = 3;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DiagnosticsReport {
+    pub path: String,
+    /// Files in the archive
+    pub files: Vec<String>,
+    /// What could not be collected, and why
+    pub failures: Vec<String>,
+}
+
+/// Takes the user's home directory, user name and host name out of the text that goes into the
+/// archive. Other identifying details, such as USB serial numbers, are left in.
+struct Redactor {
+    home: Option<String>,
+    // Places a user or host name shows up, with their replacement
+    patterns: Vec<(Regex, &'static str)>,
+}
+
+/// A name on its own, not as part of a longer word
+fn whole_word(name: &str) -> Option<Regex> {
+    Regex::new(&format!(r"\b{}\b", regex::escape(name))).ok()
+}
+
+impl Redactor {
+    fn new() -> Self {
+        let mut patterns = vec![
+            (Regex::new(r"/home/[^/\s'\x22]+").unwrap(), "/home/<user>"),
+            (Regex::new(r"/(run/)?media/[^/\s'\x22]+").unwrap(), "/${1}media/<user>"),
+            // pactl info
+            (Regex::new(r"(?m)^(\s*User Name: ).*$").unwrap(), "${1}<user>"),
+            (Regex::new(r"(?m)^(\s*Host Name: ).*$").unwrap(), "${1}<host>"),
+            // pw-dump props
+            (Regex::new(r#"("application\.process\.user"\s*:\s*")[^"]*""#).unwrap(), "${1}<user>\""),
+            (Regex::new(r#"("application\.process\.host"\s*:\s*")[^"]*""#).unwrap(), "${1}<host>\""),
+        ];
+
+        // Anywhere else the names turn up. After the patterns above so their keys still match,
+        // and a name like "user" would only catch the replacements.
+        let user = std::env::var("USER").or_else(|_| std::env::var("LOGNAME")).ok();
+        let host = fs::read_to_string("/proc/sys/kernel/hostname").ok();
+        for (name, replacement) in [(user, "<user>"), (host, "<host>")] {
+            let name = name.map(|name| name.trim().to_string()).filter(|name| !name.is_empty() && !["user", "host"].contains(&name.as_str()));
+            if let Some(pattern) = name.as_deref().and_then(whole_word) {
+                patterns.push((pattern, replacement));
+            }
+        }
+
+        Self {
+            home: std::env::var("HOME").ok().filter(|home| home.len() > 1),
+            patterns,
+        }
+    }
+
+    fn redact(&self, text: &str) -> String {
+        let mut text = match &self.home {
+            Some(home) => text.replace(home.as_str(), "~"),
+            None => text.to_string(),
+        };
+        for (pattern, replacement) in &self.patterns {
+            text = pattern.replace_all(&text, *replacement).to_string();
+        }
+        text
+    }
+}
+
+struct Bundle {
+    redactor: Redactor,
+    files: Vec<(String, Vec<u8>)>,
+    failures: Vec<String>,
+}
+
+impl Bundle {
+    fn add(&mut self, name: &str, content: Result<String, String>) {
+        match content {
+            Ok(text) => self.files.push((name.to_string(), self.redactor.redact(&text).into_bytes())),
+            Err(e) => self.failures.push(format!("{}: {}", name, self.redactor.redact(e.trim()))),
+        }
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to execute {}: {}", program, e))?;
+
+    if !output.status.success() {
+        return Err(format!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Several commands in one file, each under a "$ command" line. Failures are written in place.
+fn run_all(commands: &[(&str, &[&str])]) -> String {
+    commands
+        .iter()
+        .map(|(program, args)| {
+            let output = run(program, args).unwrap_or_else(|e| e + "\n");
+            format!("$ {} {}\n{}\n", program, args.join(" "), output)
+        })
+        .collect()
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))
+}
+
+fn kernel() -> Result<String, String> {
+    Ok(format!("{}\n{}", read("/proc/sys/kernel/osrelease")?.trim(), read("/proc/version")?))
+}
+
+fn read_proc_dir(dir: &Path, prefix: &str, depth: usize, bundle: &mut Bundle) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() && depth > 0 {
+            read_proc_dir(&entry.path(), &name, depth - 1, bundle);
+        } else if file_type.is_file() {
+            bundle.add(&name, fs::read_to_string(entry.path()).map_err(|e| e.to_string()));
+        }
+    }
+}
+
+/// The Babyface's objects from pw-dump: its device, nodes and ports
+fn pw_dump() -> Result<String, String> {
+    let objects: Vec<Value> = serde_json::from_str(&run("pw-dump", &[])?).map_err(|e| format!("Unexpected pw-dump output: {}", e))?;
+    let is_babyface = |object: &Value| {
+        object["info"]["props"]
+            .as_object()
+            .is_some_and(|props| props.values().any(|value| value.as_str().is_some_and(|s| s.contains(CARD_NAME))))
+    };
+
+    let device: Vec<&Value> = objects.iter().filter(|object| is_babyface(object)).collect();
+    if device.is_empty() {
+        return Err(format!("No {} objects in pw-dump", CARD_NAME));
+    }
+    serde_json::to_string_pretty(&device).map_err(|e| e.to_string())
+}
+
+/// The Babyface's section of `pactl list cards`
+fn pactl_card() -> Result<String, String> {
+    let cards = run("pactl", &["list", "cards"])?;
+    cards
+        .split("Card #")
+        .find(|card| card.contains(CARD_NAME))
+        .map(|card| format!("Card #{}", card))
+        .ok_or_else(|| format!("No {} card in pactl", CARD_NAME))
+}
+
+fn control_values(card_index: &str) -> Result<String, String> {
+    serde_json::to_string_pretty(&general::get_control_values(card_index)?).map_err(|e| e.to_string())
+}
+
+fn capabilities(card_index: &str) -> Result<String, String> {
+    serde_json::to_string_pretty(&detect::detect(card_index)?).map_err(|e| e.to_string())
+}
+
+/// The last part of a log, cut at a line start
+fn log_tail(path: &Path) -> Result<String, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    if text.len() <= MAX_LOG_BYTES {
+        return Ok(text);
+    }
+    let mut start = text.len() - MAX_LOG_BYTES;
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    let start = text[start..].find('\n').map(|line| start + line + 1).unwrap_or(start);
+    Ok(text[start..].to_string())
+}
+
+fn app_files(app: &AppHandle, bundle: &mut Bundle) {
+    match app.path().app_config_dir() {
+        Ok(dir) => bundle.add("app/config.json", read(&dir.join("config.json").to_string_lossy())),
+        Err(e) => bundle.add("app/config.json", Err(e.to_string())),
+    }
+
+    let Ok(dir) = app.path().app_log_dir() else {
+        return;
+    };
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return;
+    };
+    for entry in entries.flatten().filter(|entry| entry.path().is_file()) {
+        let name = format!("app/logs/{}", entry.file_name().to_string_lossy());
+        bundle.add(&name, log_tail(&entry.path()));
+    }
+}
+
+fn summary(app: &AppHandle, card_index: &str, bundle: &Bundle) -> String {
+    let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let mut lines = vec![
+        format!("{} {}", app.package_info().name, app.package_info().version),
+        format!("Created: {} (unix time)", created),
+        format!("ALSA card: {}", card_index),
+        String::new(),
+        "Files:".to_string(),
+    ];
+    lines.extend(bundle.files.iter().map(|(name, _)| format!("  {}", name)));
+    if !bundle.failures.is_empty() {
+        lines.push(String::new());
+        lines.push("Not collected:".to_string());
+        lines.extend(bundle.failures.iter().map(|failure| format!("  {}", failure)));
+    }
+    lines.join("\n") + "\n"
+}
+
+fn write_archive(path: &str, files: &[(String, Vec<u8>)]) -> Result<(), String> {
+    let error = |e: std::io::Error| format!("Failed to write {}: {}", path, e);
+    let file = File::create(path).map_err(error)?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let mtime = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+
+    for (name, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        archive.append_data(&mut header, format!("{}/{}", ROOT, name), content.as_slice()).map_err(error)?;
+    }
+    archive.into_inner().and_then(|encoder| encoder.finish()).map_err(error)?;
+    Ok(())
+}
+
+/// Gathers what's needed to look into a problem into one .tar.gz at `path`, with the home
+/// directory, user name and host name taken out. Anything that can't be collected is listed in
+/// the summary.
+pub fn collect(app: &AppHandle, card_index: &str, path: &str) -> Result<DiagnosticsReport, String> {
+    let mut bundle = Bundle {
+        redactor: Redactor::new(),
+        files: Vec::new(),
+        failures: Vec::new(),
+    };
+
+    bundle.add("system/kernel.txt", kernel());
+    bundle.add("alsa/cards", read("/proc/asound/cards"));
+    let card_dir = format!("/proc/asound/card{}", card_index);
+    read_proc_dir(Path::new(&card_dir), &format!("alsa/card{}", card_index), MAX_PROC_DEPTH, &mut bundle);
+    bundle.add("alsa/amixer-contents.txt", run("amixer", &["-c", card_index, "contents"]));
+    bundle.add("alsa/control-values.json", control_values(card_index));
+    bundle.add("capabilities.json", capabilities(card_index));
+
+    bundle.add(
+        "pipewire/versions.txt",
+        Ok(run_all(&[("pipewire", &["--version"]), ("wireplumber", &["--version"]), ("pactl", &["info"])])),
+    );
+    bundle.add("pipewire/pw-dump.json", pw_dump());
+    bundle.add("pipewire/pactl-card.txt", pactl_card());
+    bundle.add("pipewire/settings-metadata.txt", run("pw-metadata", &["-n", "settings"]));
+    bundle.add("usb/descriptors.txt", usb::describe());
+    app_files(app, &mut bundle);
+
+    let summary = summary(app, card_index, &bundle);
+    bundle.files.insert(0, ("summary.txt".to_string(), summary.into_bytes()));
+    write_archive(path, &bundle.files)?;
+
+    Ok(DiagnosticsReport {
+        path: path.to_string(),
+        files: bundle.files.into_iter().map(|(name, _)| name).collect(),
+        failures: bundle.failures,
+    })
+}
@@ -0,0 +1,9 @@
+use crate::AppState;
+use super::bundle::{self, DiagnosticsReport};
+use tauri::{AppHandle, State};
+
+/// Runs off the main thread, pw-dump and the USB scan take a moment
+#[tauri::command(async)]
+pub fn collect_diagnostics(app_handle: AppHandle, state: State<AppState>, path: String) -> Result<DiagnosticsReport, String> {
+    bundle::collect(&app_handle, &state.alsa_card_number, &path)
+}
@@ -0,0 +1,3 @@
+pub mod bundle;
+pub mod controller;
+pub mod usb;
@@ -0,0 +1,109 @@
+use rusb::{Device, DeviceDescriptor, GlobalContext};
+
+// RME Babyface Pro in class compliant mode, as in /proc/asound/cardN/usbid
+const VENDOR_ID: u16 = 0x2a39;
+const PRODUCT_ID: u16 = 0x3fb0;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" ")
+}
+
+fn describe_device(device: &Device<GlobalContext>, descriptor: &DeviceDescriptor, lines: &mut Vec<String>) {
+    lines.push(format!(
+        "Bus {:03} Device {:03}: ID {:04x}:{:04x}, {:?} speed",
+        device.bus_number(),
+        device.address(),
+        descriptor.vendor_id(),
+        descriptor.product_id(),
+        device.speed()
+    ));
+    lines.push(format!("  USB {}, device version {}", descriptor.usb_version(), descriptor.device_version()));
+    lines.push(format!(
+        "  Class {:02x}/{:02x}/{:02x}, max packet size {}, {} configurations",
+        descriptor.class_code(),
+        descriptor.sub_class_code(),
+        descriptor.protocol_code(),
+        descriptor.max_packet_size(),
+        descriptor.num_configurations()
+    ));
+
+    // Reading strings needs access to the device node, which not every setup gives
+    let strings = device.open().and_then(|handle| {
+        Ok((handle.read_manufacturer_string_ascii(descriptor)?, handle.read_product_string_ascii(descriptor)?))
+    });
+    match strings {
+        Ok((manufacturer, product)) => lines.push(format!("  {} {}", manufacturer, product)),
+        Err(e) => lines.push(format!("  Strings unavailable: {}", e)),
+    }
+
+    for index in 0..descriptor.num_configurations() {
+        let config = match device.config_descriptor(index) {
+            Ok(config) => config,
+            Err(e) => {
+                lines.push(format!("  Configuration {}: {}", index, e));
+                continue;
+            }
+        };
+        lines.push(format!(
+            "  Configuration {}: {} interfaces, {} mA, self powered {}, remote wakeup {}",
+            config.number(),
+            config.num_interfaces(),
+            config.max_power(),
+            config.self_powered(),
+            config.remote_wakeup()
+        ));
+        if !config.extra().is_empty() {
+            lines.push(format!("    Extra: {}", hex(config.extra())));
+        }
+
+        for interface in config.interfaces() {
+            for setting in interface.descriptors() {
+                lines.push(format!(
+                    "    Interface {} alt {}: class {:02x}/{:02x}/{:02x}, {} endpoints",
+                    setting.interface_number(),
+                    setting.setting_number(),
+                    setting.class_code(),
+                    setting.sub_class_code(),
+                    setting.protocol_code(),
+                    setting.num_endpoints()
+                ));
+                // Class specific descriptors: the UAC2 terminals, clocks and formats
+                if !setting.extra().is_empty() {
+                    lines.push(format!("      Extra: {}", hex(setting.extra())));
+                }
+                for endpoint in setting.endpoint_descriptors() {
+                    lines.push(format!(
+                        "      Endpoint {:02x}: {:?} {:?}, {:?}/{:?}, max packet {}, interval {}",
+                        endpoint.address(),
+                        endpoint.direction(),
+                        endpoint.transfer_type(),
+                        endpoint.sync_type(),
+                        endpoint.usage_type(),
+                        endpoint.max_packet_size(),
+                        endpoint.interval()
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// The Babyface's device, configuration, interface and endpoint descriptors as text
+pub fn describe() -> Result<String, String> {
+    let devices = rusb::devices().map_err(|e| format!("Failed to list USB devices: {}", e))?;
+
+    let mut lines = Vec::new();
+    for device in devices.iter() {
+        let Ok(descriptor) = device.device_descriptor() else {
+            continue;
+        };
+        if descriptor.vendor_id() == VENDOR_ID && descriptor.product_id() == PRODUCT_ID {
+            describe_device(&device, &descriptor, &mut lines);
+        }
+    }
+
+    if lines.is_empty() {
+        return Err(format!("No {:04x}:{:04x} device on the USB bus", VENDOR_ID, PRODUCT_ID));
+    }
+    Ok(lines.join("\n") + "\n")
+}
@@ -0,0 +1,229 @@
+use std::f64::consts::TAU;
+use std::ops::{Add, Mul, Sub};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    pub fn scale(self, factor: f64) -> Self {
+        Self::new(self.re * factor, self.im * factor)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        Self::new(self.re * other.re - self.im * other.im, self.re * other.im + self.im * other.re)
+    }
+}
+
+fn transform(buffer: &mut [Complex], inverse: bool) {
+    let n = buffer.len();
+    assert!(n.is_power_of_two(), "FFT size has to be a power of two");
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            buffer.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * TAU / len as f64;
+        let step = Complex::new(angle.cos(), angle.sin());
+        for start in (0..n).step_by(len) {
+            let mut twiddle = Complex::new(1.0, 0.0);
+            for k in 0..len / 2 {
+                let even = buffer[start + k];
+                let odd = buffer[start + k + len / 2] * twiddle;
+                buffer[start + k] = even + odd;
+                buffer[start + k + len / 2] = even - odd;
+                twiddle = twiddle * step;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// In-place forward FFT, the length has to be a power of two.
+pub fn fft(buffer: &mut [Complex]) {
+    transform(buffer, false);
+}
+
+/// In-place inverse FFT, scaled so `ifft(fft(x))` gives back `x`.
+pub fn ifft(buffer: &mut [Complex]) {
+    transform(buffer, true);
+    let scale = 1.0 / buffer.len() as f64;
+    for value in buffer.iter_mut() {
+        *value = value.scale(scale);
+    }
+}
+
+/// Spectrum of real samples, zero padded to `size`.
+pub fn real_fft(samples: &[f64], size: usize) -> Vec<Complex> {
+    let mut buffer: Vec<Complex> = samples.iter().take(size).map(|&s| Complex::new(s, 0.0)).collect();
+    buffer.resize(size, Complex::default());
+    fft(&mut buffer);
+    buffer
+}
+
+/// Cross-correlation of `signal` with `reference`, where entry `lag` is the sum of
+/// `signal[i + lag] * reference[i]`, for every lag from 0 to the signal's length.
+pub fn cross_correlate(signal: &[f32], reference: &[f32]) -> Vec<f64> {
+    let size = (signal.len() + reference.len()).next_power_of_two();
+    let to_f64 = |samples: &[f32]| samples.iter().map(|&s| s as f64).collect::<Vec<f64>>();
+
+    let signal_spectrum = real_fft(&to_f64(signal), size);
+    let reference_spectrum = real_fft(&to_f64(reference), size);
+
+    let mut product: Vec<Complex> = signal_spectrum.iter().zip(&reference_spectrum).map(|(s, r)| *s * r.conj()).collect();
+    ifft(&mut product);
+    product.iter().take(signal.len()).map(|value| value.re).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn assert_close(actual: Complex, expected: Complex) {
+        assert!(
+            (actual.re - expected.re).abs() < EPSILON && (actual.im - expected.im).abs() < EPSILON,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    fn naive_dft(samples: &[Complex]) -> Vec<Complex> {
+        let n = samples.len();
+        (0..n)
+            .map(|k| {
+                samples.iter().enumerate().fold(Complex::default(), |sum, (i, sample)| {
+                    let angle = -TAU * (k * i) as f64 / n as f64;
+                    sum + *sample * Complex::new(angle.cos(), angle.sin())
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn impulse_has_a_flat_spectrum() {
+        let spectrum = real_fft(&[1.0], 16);
+        for bin in spectrum {
+            assert_close(bin, Complex::new(1.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn constant_is_all_in_the_first_bin() {
+        let spectrum = real_fft(&[0.5; 32], 32);
+        assert_close(spectrum[0], Complex::new(16.0, 0.0));
+        for bin in &spectrum[1..] {
+            assert_close(*bin, Complex::default());
+        }
+    }
+
+    #[test]
+    fn cosine_lands_in_its_bin_and_the_mirror() {
+        let n = 64;
+        let k = 5;
+        let samples: Vec<f64> = (0..n).map(|i| (TAU * (k * i) as f64 / n as f64).cos()).collect();
+        let spectrum = real_fft(&samples, n);
+
+        for (bin, value) in spectrum.iter().enumerate() {
+            let expected = if bin == k || bin == n - k { n as f64 / 2.0 } else { 0.0 };
+            assert_close(*value, Complex::new(expected, 0.0));
+        }
+    }
+
+    #[test]
+    fn sine_has_an_imaginary_peak() {
+        let n = 128;
+        let k = 9;
+        let samples: Vec<f64> = (0..n).map(|i| (TAU * (k * i) as f64 / n as f64).sin()).collect();
+        let spectrum = real_fft(&samples, n);
+
+        assert_close(spectrum[k], Complex::new(0.0, -(n as f64) / 2.0));
+        assert_close(spectrum[n - k], Complex::new(0.0, n as f64 / 2.0));
+    }
+
+    #[test]
+    fn matches_a_direct_dft() {
+        let samples: Vec<Complex> = (0..16).map(|i| Complex::new((i * 7 % 5) as f64 - 2.0, (i % 3) as f64 * 0.25)).collect();
+        let mut buffer = samples.clone();
+        fft(&mut buffer);
+
+        for (actual, expected) in buffer.iter().zip(naive_dft(&samples)) {
+            assert_close(*actual, expected);
+        }
+    }
+
+    #[test]
+    fn inverse_gives_back_the_input() {
+        let samples: Vec<Complex> = (0..256).map(|i| Complex::new((i as f64 * 0.37).sin(), (i as f64 * 0.11).cos())).collect();
+        let mut buffer = samples.clone();
+        fft(&mut buffer);
+        ifft(&mut buffer);
+
+        for (actual, expected) in buffer.iter().zip(&samples) {
+            assert_close(*actual, *expected);
+        }
+    }
+
+    #[test]
+    fn real_fft_pads_and_truncates_to_size() {
+        assert_eq!(real_fft(&[1.0; 10], 16).len(), 16);
+        let truncated = real_fft(&[1.0; 40], 8);
+        assert_close(truncated[0], Complex::new(8.0, 0.0));
+    }
+
+    #[test]
+    fn cross_correlation_peaks_at_the_delay() {
+        let reference: Vec<f32> = (0..200).map(|i| ((i * 37 % 23) as f32 / 11.0) - 1.0).collect();
+        let delay = 57;
+        let mut signal = vec![0.0; delay];
+        signal.extend_from_slice(&reference);
+        signal.resize(400, 0.0);
+
+        let correlation = cross_correlate(&signal, &reference);
+        assert_eq!(correlation.len(), signal.len());
+        let peak = correlation.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)).map(|(lag, _)| lag);
+        assert_eq!(peak, Some(delay));
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+// Gates and block lengths from ITU-R BS.1770-4 and EBU Tech 3341/3342
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+const RANGE_GATE_LU: f64 = -20.0;
+const SUBBLOCK_SECONDS: f64 = 0.1;
+const MOMENTARY_SUBBLOCKS: usize = 4;
+const SHORT_TERM_SUBBLOCKS: usize = 30;
+const RANGE_LOW_PERCENTILE: f64 = 0.10;
+const RANGE_HIGH_PERCENTILE: f64 = 0.95;
+// Taps per phase of the true-peak interpolator, as in BS.1770 Annex 2
+const TRUE_PEAK_TAPS: usize = 12;
+// Block loudness is kept in 0.1 LU bins from the absolute gate up, as libebur128 does
+const HISTOGRAM_STEP_LU: f64 = 0.1;
+const HISTOGRAM_BINS: usize = 1000;
+
+/// Loudness of a mean square summed over the channels
+fn to_lufs(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.max(1e-20).log10()
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// The two K-weighting stages, a high shelf for the head and the RLB high pass, worked out for
+/// any rate from their analog prototypes
+fn k_weighting(rate: u32) -> [Biquad; 2] {
+    let rate = rate as f64;
+
+    let k = (PI * 1681.974450955533 / rate).tan();
+    let q = 0.7071752369554196;
+    let vh = 10f64.powf(3.999843853973347 / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    let k = (PI * 38.13547087602444 / rate).tan();
+    let q = 0.5003270373238773;
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    [shelf, high_pass]
+}
+
+/// Polyphase windowed-sinc interpolator, finds peaks between the samples
+struct TruePeak {
+    phases: Vec<Vec<f64>>,
+    history: Vec<f64>,
+}
+
+impl TruePeak {
+    fn new(rate: u32) -> Self {
+        // Oversampled to at least 192 kHz
+        let factor = match rate {
+            0..=48000 => 4,
+            48001..=96000 => 2,
+            _ => 1,
+        };
+        let length = TRUE_PEAK_TAPS * factor;
+        let center = (length - 1) as f64 / 2.0;
+        let taps: Vec<f64> = (0..length)
+            .map(|n| {
+                let x = (n as f64 - center) / factor as f64;
+                let sinc = if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+                let window = 0.42 - 0.5 * (2.0 * PI * n as f64 / (length - 1) as f64).cos() + 0.08 * (4.0 * PI * n as f64 / (length - 1) as f64).cos();
+                sinc * window
+            })
+            .collect();
+        let gain = taps.iter().sum::<f64>() / factor as f64;
+
+        Self {
+            phases: (0..factor).map(|phase| taps.iter().skip(phase).step_by(factor).map(|tap| tap / gain).collect()).collect(),
+            history: vec![0.0; TRUE_PEAK_TAPS],
+        }
+    }
+
+    /// Highest absolute value of the interpolated samples up to this one
+    fn process(&mut self, x: f64) -> f64 {
+        self.history.copy_within(1.., 0);
+        self.history[TRUE_PEAK_TAPS - 1] = x;
+        self.phases
+            .iter()
+            .map(|phase| phase.iter().rev().zip(&self.history).map(|(tap, sample)| tap * sample).sum::<f64>().abs())
+            .fold(x.abs(), f64::max)
+    }
+}
+
+/// How many blocks fell in each loudness bin, so gating and percentiles cost the same
+/// however long the measurement runs
+#[derive(Debug, Clone)]
+struct Histogram {
+    counts: Vec<u64>,
+    /// Mean square of the blocks in each bin, summed
+    energies: Vec<f64>,
+    /// Loudest block, including those below the gate
+    max: Option<f64>,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            counts: vec![0; HISTOGRAM_BINS],
+            energies: vec![0.0; HISTOGRAM_BINS],
+            max: None,
+        }
+    }
+
+    /// Blocks under the absolute gate have no bin, the loudest bin takes everything above it
+    fn bin(lufs: f64) -> Option<usize> {
+        (lufs > ABSOLUTE_GATE_LUFS).then(|| (((lufs - ABSOLUTE_GATE_LUFS) / HISTOGRAM_STEP_LU) as usize).min(HISTOGRAM_BINS - 1))
+    }
+
+    fn bin_lufs(bin: usize) -> f64 {
+        ABSOLUTE_GATE_LUFS + (bin as f64 + 0.5) * HISTOGRAM_STEP_LU
+    }
+
+    fn add(&mut self, energy: f64) {
+        self.max = Some(self.max.map_or(energy, |max| max.max(energy)));
+        if let Some(bin) = Self::bin(to_lufs(energy)) {
+            self.counts[bin] += 1;
+            self.energies[bin] += energy;
+        }
+    }
+
+    /// First bin wholly above a gate `offset_lu` from the mean of the blocks above the absolute gate
+    fn relative_gate(&self, offset_lu: f64) -> Option<usize> {
+        let count: u64 = self.counts.iter().sum();
+        if count == 0 {
+            return None;
+        }
+        let gate = to_lufs(self.energies.iter().sum::<f64>() / count as f64) + offset_lu;
+        Some((((gate - ABSOLUTE_GATE_LUFS) / HISTOGRAM_STEP_LU).ceil().max(0.0) as usize).min(HISTOGRAM_BINS))
+    }
+
+    fn integrated(&self) -> Option<f64> {
+        let first = self.relative_gate(RELATIVE_GATE_LU)?;
+        let count: u64 = self.counts[first..].iter().sum();
+        (count > 0).then(|| to_lufs(self.energies[first..].iter().sum::<f64>() / count as f64))
+    }
+
+    fn range(&self) -> Option<f64> {
+        let first = self.relative_gate(RANGE_GATE_LU)?;
+        let count: u64 = self.counts[first..].iter().sum();
+        if count < 2 {
+            return None;
+        }
+        let percentile = |p: f64| {
+            let wanted = ((count - 1) as f64 * p).round() as u64;
+            let mut seen = 0;
+            for (bin, bin_count) in self.counts.iter().enumerate().skip(first) {
+                seen += bin_count;
+                if seen > wanted {
+                    return Self::bin_lufs(bin);
+                }
+            }
+            Self::bin_lufs(HISTOGRAM_BINS - 1)
+        };
+        Some(percentile(RANGE_HIGH_PERCENTILE) - percentile(RANGE_LOW_PERCENTILE))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoudnessValues {
+    /// None until there is a full block
+    pub momentary: Option<f64>,
+    pub short_term: Option<f64>,
+    pub momentary_max: Option<f64>,
+    pub short_term_max: Option<f64>,
+    /// None until a block passes the gates
+    pub integrated: Option<f64>,
+    /// Loudness range in LU
+    pub range: Option<f64>,
+    /// Highest true peak in dBTP
+    pub true_peak: Option<f64>,
+    pub seconds: f64,
+}
+
+/// What a meter has measured so far, copied out so the values can be worked out away from
+/// the audio thread
+#[derive(Debug, Clone)]
+pub struct LoudnessBlocks {
+    rate: u32,
+    /// Mean square of the latest subblocks, enough for a short-term block
+    subblocks: VecDeque<f64>,
+    /// Momentary blocks, the gating blocks for the integrated loudness
+    momentary: Histogram,
+    /// Short-term blocks, for the loudness range
+    short_term: Histogram,
+    peak: f64,
+    frames: u64,
+}
+
+impl LoudnessBlocks {
+    /// Mean square over the latest `count` subblocks
+    fn latest(&self, count: usize) -> Option<f64> {
+        (self.subblocks.len() >= count).then(|| self.subblocks.iter().rev().take(count).sum::<f64>() / count as f64)
+    }
+
+    pub fn values(&self) -> LoudnessValues {
+        LoudnessValues {
+            momentary: self.latest(MOMENTARY_SUBBLOCKS).map(to_lufs),
+            short_term: self.latest(SHORT_TERM_SUBBLOCKS).map(to_lufs),
+            momentary_max: self.momentary.max.map(to_lufs),
+            short_term_max: self.short_term.max.map(to_lufs),
+            integrated: self.momentary.integrated(),
+            range: self.short_term.range(),
+            true_peak: (self.frames > 0).then(|| 20.0 * self.peak.max(1e-10).log10()),
+            seconds: self.frames as f64 / self.rate.max(1) as f64,
+        }
+    }
+}
+
+/// BS.1770 loudness of equally weighted channels, such as a stereo pair
+pub struct LoudnessMeter {
+    filters: Vec<[Biquad; 2]>,
+    true_peaks: Vec<TruePeak>,
+    subblock_frames: usize,
+    frames_in_subblock: usize,
+    subblock_sum: f64,
+    blocks: LoudnessBlocks,
+}
+
+impl LoudnessMeter {
+    pub fn new(rate: u32, channels: usize) -> Self {
+        Self {
+            filters: vec![k_weighting(rate); channels],
+            true_peaks: (0..channels).map(|_| TruePeak::new(rate)).collect(),
+            subblock_frames: ((rate as f64 * SUBBLOCK_SECONDS) as usize).max(1),
+            frames_in_subblock: 0,
+            subblock_sum: 0.0,
+            blocks: LoudnessBlocks {
+                rate,
+                subblocks: VecDeque::with_capacity(SHORT_TERM_SUBBLOCKS),
+                momentary: Histogram::new(),
+                short_term: Histogram::new(),
+                peak: 0.0,
+                frames: 0,
+            },
+        }
+    }
+
+    pub fn rate(&self) -> u32 {
+        self.blocks.rate
+    }
+
+    pub fn blocks(&self) -> LoudnessBlocks {
+        self.blocks.clone()
+    }
+
+    /// One sample per channel
+    pub fn process_frame(&mut self, frame: &[f32]) {
+        for ((sample, filters), true_peak) in frame.iter().zip(self.filters.iter_mut()).zip(self.true_peaks.iter_mut()) {
+            let x = *sample as f64;
+            self.blocks.peak = self.blocks.peak.max(true_peak.process(x));
+            let shelved = filters[0].process(x);
+            let weighted = filters[1].process(shelved);
+            self.subblock_sum += weighted * weighted;
+        }
+        self.blocks.frames += 1;
+        self.frames_in_subblock += 1;
+
+        if self.frames_in_subblock == self.subblock_frames {
+            self.finish_subblock();
+        }
+    }
+
+    fn finish_subblock(&mut self) {
+        let blocks = &mut self.blocks;
+        if blocks.subblocks.len() == SHORT_TERM_SUBBLOCKS {
+            blocks.subblocks.pop_front();
+        }
+        blocks.subblocks.push_back(self.subblock_sum / self.subblock_frames as f64);
+        self.subblock_sum = 0.0;
+        self.frames_in_subblock = 0;
+
+        if let Some(momentary) = blocks.latest(MOMENTARY_SUBBLOCKS) {
+            blocks.momentary.add(momentary);
+        }
+        if let Some(short_term) = blocks.latest(SHORT_TERM_SUBBLOCKS) {
+            blocks.short_term.add(short_term);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f64 = 0.1;
+
+    /// Feeds `seconds` of a 997 Hz sine at `level_db` dBFS to both channels
+    fn feed(meter: &mut LoudnessMeter, rate: u32, level_db: f64, seconds: f64) {
+        let amplitude = 10f64.powf(level_db / 20.0);
+        for i in 0..(seconds * rate as f64) as usize {
+            let sample = (amplitude * (2.0 * PI * 997.0 * i as f64 / rate as f64).sin()) as f32;
+            meter.process_frame(&[sample, sample]);
+        }
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64, tolerance: f64) {
+        let actual = actual.expect("no value");
+        assert!((actual - expected).abs() <= tolerance, "{} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    #[test]
+    fn sine_at_minus_20_reads_minus_20_lufs() {
+        for rate in [44100, 48000] {
+            let mut meter = LoudnessMeter::new(rate, 2);
+            feed(&mut meter, rate, -20.0, 10.0);
+            let values = meter.blocks().values();
+            assert_close(values.momentary, -20.0, TOLERANCE);
+            assert_close(values.short_term, -20.0, TOLERANCE);
+            assert_close(values.integrated, -20.0, TOLERANCE);
+            assert_close(values.true_peak, -20.0, TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn nothing_under_the_absolute_gate_counts() {
+        let mut meter = LoudnessMeter::new(48000, 2);
+        feed(&mut meter, 48000, -80.0, 5.0);
+        assert_eq!(meter.blocks().values().integrated, None);
+    }
+
+    #[test]
+    fn quiet_passages_leave_the_integrated_loudness_alone() {
+        let mut meter = LoudnessMeter::new(48000, 2);
+        feed(&mut meter, 48000, -20.0, 10.0);
+        let before = meter.blocks().values().integrated.expect("no value");
+        // Under the absolute gate, then above it but under the relative one
+        feed(&mut meter, 48000, -80.0, 10.0);
+        feed(&mut meter, 48000, -40.0, 10.0);
+        // Only the blocks straddling the step down come through the gates
+        assert_close(meter.blocks().values().integrated, before, 0.2);
+    }
+
+    #[test]
+    fn two_levels_give_their_difference_as_range() {
+        // EBU Tech 3342 case 1: 20 s at -20 then 20 s at -30 LUFS is 10 LU
+        let mut meter = LoudnessMeter::new(48000, 2);
+        feed(&mut meter, 48000, -20.0, 20.0);
+        feed(&mut meter, 48000, -30.0, 20.0);
+        assert_close(meter.blocks().values().range, 10.0, 0.5);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod fft;
+pub mod loudness;
+pub mod octave;
+pub mod spectrum;
+pub mod weighting;
+pub mod window;
+pub mod yin;
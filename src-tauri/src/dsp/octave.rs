@@ -0,0 +1,41 @@
+/// Centre frequencies of 1/`fraction` octave bands between the limits, on base 2 around 1 kHz
+pub fn band_centers(fraction: u32, min_hz: f64, max_hz: f64) -> Vec<f64> {
+    let fraction = fraction.max(1) as f64;
+    let first = (fraction * (min_hz / 1000.0).log2()).ceil() as i64;
+    let last = (fraction * (max_hz / 1000.0).log2()).floor() as i64;
+    (first..=last).map(|n| 1000.0 * 2f64.powf(n as f64 / fraction)).collect()
+}
+
+pub fn band_edges(center: f64, fraction: u32) -> (f64, f64) {
+    let half = 2f64.powf(1.0 / (2.0 * fraction.max(1) as f64));
+    (center / half, center * half)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BandMode {
+    /// Energy in the band, for levels of noise in fixed-width bands
+    Sum,
+    /// Average bin, for transfer functions
+    Mean,
+    /// Highest bin, so tones keep their level when a spectrum is thinned out for display
+    Max,
+}
+
+/// Reduces a single-sided power spectrum with bins `bin_hz` apart to one band. Bands narrower
+/// than a bin take the nearest bin.
+pub fn band_power(spectrum: &[f64], bin_hz: f64, center: f64, fraction: u32, mode: BandMode) -> f64 {
+    let (low, high) = band_edges(center, fraction);
+    let first = (low / bin_hz).ceil() as usize;
+    let last = ((high / bin_hz).floor() as usize).min(spectrum.len().saturating_sub(1));
+
+    if first > last {
+        return spectrum.get((center / bin_hz).round() as usize).copied().unwrap_or(0.0);
+    }
+
+    let bins = &spectrum[first..=last];
+    match mode {
+        BandMode::Sum => bins.iter().sum(),
+        BandMode::Mean => bins.iter().sum::<f64>() / bins.len() as f64,
+        BandMode::Max => bins.iter().copied().fold(0.0, f64::max),
+    }
+}
@@ -0,0 +1,48 @@
+use super::fft::{self, Complex};
+use super::window::Window;
+
+/// Single-sided power per bin, scaled so the bins add up to the signal's mean square.
+/// Bin `k` is at `k * rate / samples.len()` Hz.
+pub fn power_spectrum(samples: &[f32], window: Window) -> Vec<f64> {
+    power_spectrum_with(samples, &window.coefficients(samples.len()))
+}
+
+/// `power_spectrum` with the window's coefficients worked out beforehand, for a stream of frames
+pub fn power_spectrum_with(samples: &[f32], coefficients: &[f64]) -> Vec<f64> {
+    let size = samples.len();
+    let window_power: f64 = coefficients.iter().map(|w| w * w).sum();
+
+    let mut buffer: Vec<Complex> = samples.iter().zip(coefficients).map(|(s, w)| Complex::new(*s as f64 * w, 0.0)).collect();
+    fft::fft(&mut buffer);
+
+    let scale = 1.0 / (size as f64 * window_power);
+    (0..=size / 2)
+        .map(|k| {
+            let power = (buffer[k].re * buffer[k].re + buffer[k].im * buffer[k].im) * scale;
+            if k == 0 || k == size / 2 { power } else { 2.0 * power }
+        })
+        .collect()
+}
+
+/// Power spectrum averaged over half-overlapping segments of `size` samples, which has to be
+/// a power of two. Returns None when there isn't a full segment.
+pub fn welch(samples: &[f32], size: usize, window: Window) -> Option<Vec<f64>> {
+    let mut sum: Option<Vec<f64>> = None;
+    let mut count = 0;
+
+    for start in (0..samples.len().saturating_sub(size - 1)).step_by(size / 2) {
+        let segment = power_spectrum(&samples[start..start + size], window);
+        match sum.as_mut() {
+            Some(sum) => sum.iter_mut().zip(&segment).for_each(|(total, power)| *total += power),
+            None => sum = Some(segment),
+        }
+        count += 1;
+    }
+
+    sum.map(|sum| sum.into_iter().map(|power| power / count as f64).collect())
+}
+
+/// dBFS of a mean square, where a full-scale sine reads 0 dBFS as in AES17
+pub fn power_to_dbfs(power: f64) -> f64 {
+    10.0 * (2.0 * power).max(1e-20).log10()
+}
@@ -0,0 +1,9 @@
+/// A-weighting gain at a frequency, as a power factor (IEC 61672)
+pub fn a_weighting(frequency: f64) -> f64 {
+    let f2 = frequency * frequency;
+    let numerator = 12194.0f64.powi(2) * f2 * f2;
+    let denominator = (f2 + 20.6f64.powi(2)) * ((f2 + 107.7f64.powi(2)) * (f2 + 737.9f64.powi(2))).sqrt() * (f2 + 12194.0f64.powi(2));
+    // Normalised to 0 dB at 1 kHz, the +2.0 dB of the standard formula
+    let amplitude = numerator / denominator * 10f64.powf(2.0 / 20.0);
+    amplitude * amplitude
+}
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Window {
+    /// No window, only for signals that fit the frame exactly
+    Rectangular,
+    #[default]
+    Hann,
+    /// 4-term Blackman-Harris, sidelobes below -92 dB for distortion measurements
+    BlackmanHarris,
+    /// Reads the level of a tone correctly wherever it falls between bins
+    FlatTop,
+}
+
+impl Window {
+    pub fn coefficients(self, size: usize) -> Vec<f64> {
+        let terms: &[f64] = match self {
+            Window::Rectangular => &[1.0],
+            Window::Hann => &[0.5, 0.5],
+            Window::BlackmanHarris => &[0.35875, 0.48829, 0.14128, 0.01168],
+            Window::FlatTop => &[0.21557895, 0.41663158, 0.277263158, 0.083578947, 0.006947368],
+        };
+
+        (0..size)
+            .map(|n| {
+                let x = TAU * n as f64 / size as f64;
+                terms
+                    .iter()
+                    .enumerate()
+                    .map(|(k, a)| if k % 2 == 0 { a * (k as f64 * x).cos() } else { -a * (k as f64 * x).cos() })
+                    .sum()
+            })
+            .collect()
+    }
+}
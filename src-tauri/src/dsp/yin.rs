@@ -0,0 +1,113 @@
+use super::fft;
+
+// Dips in the normalised difference below this count as a period, 0.10 to 0.15 in the YIN paper
+const THRESHOLD: f64 = 0.12;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Pitch {
+    pub frequency: f64,
+    /// 1 minus the normalised difference at the period, near 1 for a clean tone
+    pub clarity: f64,
+}
+
+/// YIN pitch detection (de Cheveigné and Kawahara) on `samples`, which have to cover two of the
+/// longest periods looked for. Returns None for noise and silence.
+pub fn detect(samples: &[f32], rate: u32, min_hz: f64, max_hz: f64) -> Option<Pitch> {
+    let window = samples.len() / 2;
+    let max_lag = ((rate as f64 / min_hz) as usize).min(window - 1);
+    let min_lag = ((rate as f64 / max_hz) as usize).max(2);
+    if min_lag >= max_lag {
+        return None;
+    }
+
+    // d(lag) = sum (x[j] - x[j + lag])^2 over the window, from the energies and a correlation
+    let correlation = fft::cross_correlate(samples, &samples[..window]);
+    let mut energy = vec![0.0f64; samples.len() + 1];
+    for (i, sample) in samples.iter().enumerate() {
+        energy[i + 1] = energy[i] + (*sample as f64) * (*sample as f64);
+    }
+    let difference = |lag: usize| energy[window] + energy[window + lag] - energy[lag] - 2.0 * correlation[lag];
+
+    // Cumulative mean normalised difference
+    let mut sum = 0.0;
+    let normalised: Vec<f64> = (0..=max_lag)
+        .map(|lag| {
+            if lag == 0 {
+                return 1.0;
+            }
+            let d = difference(lag);
+            sum += d;
+            if sum > 0.0 { d * lag as f64 / sum } else { 1.0 }
+        })
+        .collect();
+
+    let mut lag = (min_lag..max_lag).find(|lag| normalised[*lag] < THRESHOLD)?;
+    while lag < max_lag && normalised[lag + 1] < normalised[lag] {
+        lag += 1;
+    }
+
+    // Parabola through the dip and its neighbours for a period between samples
+    let (before, at, after) = (normalised[lag - 1], normalised[lag], normalised[(lag + 1).min(max_lag)]);
+    let curvature = before + after - 2.0 * at;
+    let offset = if curvature > 0.0 { (before - after) / (2.0 * curvature) } else { 0.0 };
+
+    Some(Pitch {
+        frequency: rate as f64 / (lag as f64 + offset.clamp(-1.0, 1.0)),
+        clarity: (1.0 - at).clamp(0.0, 1.0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::TAU;
+
+    const RATE: u32 = 48000;
+    // Two periods of the lowest pitch looked for
+    const FRAMES: usize = 2 * RATE as usize / 30;
+
+    fn tone(partials: &[(f64, f64)]) -> Vec<f32> {
+        (0..FRAMES)
+            .map(|i| {
+                let t = i as f64 / RATE as f64;
+                partials.iter().map(|(frequency, level)| level * (TAU * frequency * t).sin()).sum::<f64>() as f32
+            })
+            .collect()
+    }
+
+    fn cents(actual: f64, expected: f64) -> f64 {
+        1200.0 * (actual / expected).log2()
+    }
+
+    #[test]
+    fn finds_sine_pitches() {
+        for frequency in [41.2, 82.41, 110.0, 261.63, 440.0, 1318.51] {
+            let pitch = detect(&tone(&[(frequency, 0.5)]), RATE, 30.0, 2000.0).expect("no pitch found");
+            assert!(cents(pitch.frequency, frequency).abs() < 1.0, "{} Hz read as {} Hz", frequency, pitch.frequency);
+            assert!(pitch.clarity > 0.9, "{} Hz had clarity {}", frequency, pitch.clarity);
+        }
+    }
+
+    #[test]
+    fn finds_the_fundamental_under_harmonics() {
+        let pitch = detect(&tone(&[(110.0, 0.5), (220.0, 0.3), (330.0, 0.2)]), RATE, 30.0, 2000.0).expect("no pitch found");
+        assert!(cents(pitch.frequency, 110.0).abs() < 1.0, "read as {} Hz", pitch.frequency);
+    }
+
+    #[test]
+    fn silence_has_no_pitch() {
+        assert!(detect(&vec![0.0; FRAMES], RATE, 30.0, 2000.0).is_none());
+    }
+
+    #[test]
+    fn noise_has_no_pitch() {
+        let mut state = 0x1234_5678u32;
+        let noise: Vec<f32> = (0..FRAMES)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+            })
+            .collect();
+        assert!(detect(&noise, RATE, 30.0, 2000.0).is_none());
+    }
+}
@@ -0,0 +1,21 @@
+use tauri::AppHandle;
+use super::player::{self, GeneratorSettings, GeneratorStatus};
+
+#[tauri::command]
+pub fn start_generator(app_handle: AppHandle, settings: GeneratorSettings) -> Result<GeneratorStatus, String> {
+    let status = player::start(&app_handle, settings)?;
+    player::emit_status(&app_handle);
+    Ok(status)
+}
+
+#[tauri::command]
+pub fn stop_generator(app_handle: AppHandle) -> Result<(), String> {
+    player::stop()?;
+    player::emit_status(&app_handle);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_generator_status() -> GeneratorStatus {
+    player::get_status()
+}
@@ -0,0 +1,3 @@
+pub mod controller;
+pub mod player;
+pub mod signal;
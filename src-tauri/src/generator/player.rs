@@ -0,0 +1,151 @@
+use super::signal::{Oscillator, Signal};
+use crate::alsa::volume::OutputPair;
+use crate::pipewire::audio::{self, AudioSession, StreamKind, StreamSpec};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+const MIN_LEVEL_DB: f64 = -90.0;
+const MAX_TIMEOUT_SECONDS: u32 = 3600;
+
+fn default_level_db() -> f64 {
+    -20.0
+}
+
+fn default_timeout_seconds() -> u32 {
+    60
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GeneratorSettings {
+    pub signal: Signal,
+    pub output: OutputPair,
+    /// Peak level in dBFS, at most 0
+    #[serde(default = "default_level_db")]
+    pub level_db: f64,
+    /// Stops by itself after this long, so a forgotten tone doesn't keep playing
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct GeneratorStatus {
+    pub running: bool,
+    pub settings: Option<GeneratorSettings>,
+    pub remaining_seconds: Option<f64>,
+}
+
+struct Running {
+    id: u64,
+    settings: GeneratorSettings,
+    deadline: Instant,
+    _session: AudioSession,
+}
+
+#[derive(Default)]
+struct Generator {
+    running: Option<Running>,
+    next_id: u64,
+}
+
+static GENERATOR: Lazy<Mutex<Generator>> = Lazy::new(|| Mutex::new(Generator::default()));
+
+pub fn get_status() -> GeneratorStatus {
+    let generator = GENERATOR.lock();
+    match generator.as_ref().ok().and_then(|g| g.running.as_ref()) {
+        Some(running) => GeneratorStatus {
+            running: true,
+            settings: Some(running.settings.clone()),
+            remaining_seconds: Some(running.deadline.saturating_duration_since(Instant::now()).as_secs_f64()),
+        },
+        None => GeneratorStatus {
+            running: false,
+            settings: None,
+            remaining_seconds: None,
+        },
+    }
+}
+
+pub fn emit_status(app: &AppHandle) {
+    let _ = app.emit("generator-changed", get_status());
+}
+
+/// Plays the signal on the output pair, replacing whatever the generator was playing.
+pub fn start(app: &AppHandle, mut settings: GeneratorSettings) -> Result<GeneratorStatus, String> {
+    settings.signal.validate()?;
+    if !settings.level_db.is_finite() {
+        return Err("The level has to be a number".to_string());
+    }
+    settings.level_db = settings.level_db.clamp(MIN_LEVEL_DB, 0.0);
+    settings.timeout_seconds = settings.timeout_seconds.clamp(1, MAX_TIMEOUT_SECONDS);
+
+    // The old session stops before the new one opens the sink
+    stop()?;
+
+    let (left_route, right_route) = settings.output.routes();
+    let (sink, left) = audio::playback_target(left_route)?;
+    let (_, right) = audio::playback_target(right_route)?;
+
+    let gain = 10f64.powf(settings.level_db / 20.0) as f32;
+    let mut oscillator = Oscillator::new(settings.signal);
+    let fill = Box::new(move |samples: &mut [f32], info: audio::BlockInfo| {
+        for frame in samples.chunks_exact_mut(info.channels) {
+            let (l, r) = oscillator.next_frame(info.rate);
+            frame[left] = l * gain;
+            frame[right] = r * gain;
+        }
+    });
+
+    let session = AudioSession::start(
+        "generator",
+        vec![StreamSpec {
+            target: sink.name,
+            kind: StreamKind::Playback { channels: sink.channels, fill },
+        }],
+    )?;
+
+    let timeout = Duration::from_secs(settings.timeout_seconds as u64);
+    let id = {
+        let mut generator = GENERATOR.lock().map_err(|e| e.to_string())?;
+        generator.next_id += 1;
+        let id = generator.next_id;
+        generator.running = Some(Running {
+            id,
+            settings,
+            deadline: Instant::now() + timeout,
+            _session: session,
+        });
+        id
+    };
+
+    let app = app.clone();
+    thread::spawn(move || {
+        thread::sleep(timeout);
+        if stop_if(|running| running.id == id).unwrap_or(false) {
+            println!("Test signal stopped after {} seconds", timeout.as_secs());
+            emit_status(&app);
+        }
+    });
+
+    Ok(get_status())
+}
+
+/// Stops the running signal when `matches` says so. Returns whether it stopped.
+fn stop_if(matches: impl FnOnce(&Running) -> bool) -> Result<bool, String> {
+    let running = {
+        let mut generator = GENERATOR.lock().map_err(|e| e.to_string())?;
+        match generator.running.as_ref() {
+            Some(running) if matches(running) => generator.running.take(),
+            _ => None,
+        }
+    };
+    // Dropped outside the lock, stopping waits for the audio thread
+    Ok(running.is_some())
+}
+
+pub fn stop() -> Result<(), String> {
+    stop_if(|_| true).map(|_| ())
+}
@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+
+const IDENTIFY_PERIOD: f64 = 3.0;
+const IDENTIFY_HZ: f64 = 1000.0;
+// (start, end) in seconds within the period, one beep left, then two right
+const IDENTIFY_LEFT: [(f64, f64); 1] = [(0.0, 0.4)];
+const IDENTIFY_RIGHT: [(f64, f64); 2] = [(1.0, 1.2), (1.4, 1.6)];
+// Beeps fade in and out over this long so they don't click
+const FADE_SECONDS: f64 = 0.005;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Signal {
+    Sine { frequency: f64 },
+    WhiteNoise,
+    PinkNoise,
+    /// Logarithmic sweep, starting over at the end
+    Sweep { start_hz: f64, end_hz: f64, seconds: f64 },
+    /// Beeps once on the left and twice on the right, every three seconds
+    Identify,
+}
+
+impl Signal {
+    pub fn validate(&self) -> Result<(), String> {
+        let audible = |hz: f64| hz.is_finite() && (1.0..=24000.0).contains(&hz);
+        match self {
+            Signal::Sine { frequency } if !audible(*frequency) => Err(format!("{} Hz is outside 1 Hz to 24 kHz", frequency)),
+            Signal::Sweep { start_hz, end_hz, .. } if !audible(*start_hz) || !audible(*end_hz) => {
+                Err(format!("A sweep from {} Hz to {} Hz is outside 1 Hz to 24 kHz", start_hz, end_hz))
+            }
+            Signal::Sweep { seconds, .. } if !(seconds.is_finite() && *seconds >= 0.1) => Err("A sweep has to last at least 0.1 seconds".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// xorshift64*, plenty for test noise and doesn't need a crate
+struct Noise {
+    state: u64,
+    // Paul Kellett's pink filter, one set per channel
+    pink: [[f64; 7]; 2],
+}
+
+impl Noise {
+    fn new() -> Self {
+        Self {
+            state: 0x9E37_79B9_7F4A_7C15,
+            pink: [[0.0; 7]; 2],
+        }
+    }
+
+    /// Uniform in -1..1
+    fn white(&mut self) -> f64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let value = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (value >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+    }
+
+    fn pink(&mut self, channel: usize) -> f64 {
+        let white = self.white();
+        let b = &mut self.pink[channel];
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.1538520;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.0168980;
+        let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+        b[6] = white * 0.115926;
+        // The filter peaks a little over 4, keep it within full scale
+        (pink * 0.2).clamp(-1.0, 1.0)
+    }
+}
+
+/// Produces a signal for a stereo pair, one frame at a time, peaking at full scale
+pub struct Oscillator {
+    signal: Signal,
+    phase: f64,
+    // Seconds since the start, or since the sweep or identify cycle started over
+    time: f64,
+    noise: Noise,
+}
+
+fn envelope(time: f64, beeps: &[(f64, f64)]) -> f64 {
+    beeps
+        .iter()
+        .find(|(start, end)| time >= *start && time < *end)
+        .map(|(start, end)| ((time - start) / FADE_SECONDS).min((end - time) / FADE_SECONDS).min(1.0))
+        .unwrap_or(0.0)
+}
+
+impl Oscillator {
+    pub fn new(signal: Signal) -> Self {
+        Self {
+            signal,
+            phase: 0.0,
+            time: 0.0,
+            noise: Noise::new(),
+        }
+    }
+
+    fn advance_phase(&mut self, frequency: f64, rate: f64) -> f64 {
+        let value = self.phase.sin();
+        self.phase = (self.phase + TAU * frequency / rate) % TAU;
+        value
+    }
+
+    /// Next (left, right) frame
+    pub fn next_frame(&mut self, rate: u32) -> (f32, f32) {
+        let rate = rate.max(1) as f64;
+
+        let frame = match self.signal {
+            Signal::Sine { frequency } => {
+                let value = self.advance_phase(frequency, rate);
+                (value, value)
+            }
+            Signal::WhiteNoise => (self.noise.white(), self.noise.white()),
+            Signal::PinkNoise => (self.noise.pink(0), self.noise.pink(1)),
+            Signal::Sweep { start_hz, end_hz, seconds } => {
+                let frequency = start_hz * (end_hz / start_hz).powf(self.time / seconds);
+                let value = self.advance_phase(frequency, rate);
+                self.time += 1.0 / rate;
+                if self.time >= seconds {
+                    self.time = 0.0;
+                    self.phase = 0.0;
+                }
+                (value, value)
+            }
+            Signal::Identify => {
+                let value = self.advance_phase(IDENTIFY_HZ, rate);
+                let frame = (value * envelope(self.time, &IDENTIFY_LEFT), value * envelope(self.time, &IDENTIFY_RIGHT));
+                self.time = (self.time + 1.0 / rate) % IDENTIFY_PERIOD;
+                frame
+            }
+        };
+
+        (frame.0 as f32, frame.1 as f32)
+    }
+}
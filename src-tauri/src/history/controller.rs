@@ -0,0 +1,31 @@
+use crate::AppState;
+use tauri::{AppHandle, Emitter, State};
+use super::journal::{self, HistoryEntry, HistoryState};
+
+#[tauri::command]
+pub fn get_history() -> HistoryState {
+    journal::get_state()
+}
+
+#[tauri::command]
+pub fn undo(app_handle: AppHandle, state: State<AppState>) -> Result<Option<HistoryEntry>, String> {
+    let card_number = &state.alsa_card_number;
+    let result = journal::undo(&app_handle, card_number);
+    let _ = app_handle.emit("controls-changed", ());
+    crate::tray::menu::refresh(&app_handle);
+    result
+}
+
+#[tauri::command]
+pub fn redo(app_handle: AppHandle, state: State<AppState>) -> Result<Option<HistoryEntry>, String> {
+    let card_number = &state.alsa_card_number;
+    let result = journal::redo(&app_handle, card_number);
+    let _ = app_handle.emit("controls-changed", ());
+    crate::tray::menu::refresh(&app_handle);
+    result
+}
+
+#[tauri::command]
+pub fn clear_history() {
+    journal::clear()
+}
@@ -0,0 +1,425 @@
+use crate::alsa::general::{self, ControlValue};
+use crate::alsa::volume::OutputPair;
+use crate::alsa::{crosspoint, input_gain, phantom, ramp, safety, stereo_link, switches, virtual_volume, volume};
+use crate::monitor::section;
+use crate::storage::config::ConfigStorage;
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+const MAX_ENTRIES: usize = 200;
+// Changes to the same controls closer together than this are one gesture, e.g. a fader drag
+// (which the UI sends as alternating calls for the left and right side)
+const MERGE_WINDOW: Duration = Duration::from_millis(750);
+// Above this many controls one full read is cheaper than reading them one by one
+const BULK_READ_THRESHOLD: usize = 4;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Origin {
+    Ui,
+    Hotkey,
+    Tray,
+    Preset,
+    /// The auto-gain assistant setting an input's gain
+    AutoGain,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ControlChange {
+    pub control_name: String,
+    pub old_value: ControlValue,
+    pub new_value: ControlValue,
+}
+
+/// App state outside the device controls that a change can touch
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", content = "name", rename_all = "snake_case")]
+pub enum Setting {
+    /// Dim, mute, mono, speaker selection and the monitor config
+    Monitor,
+    StereoLink,
+    /// An input's channel config, which also says whether its pair is linked
+    Channel(String),
+    Ramp,
+    OutputLimit(OutputPair),
+    PhantomInterlocks,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SettingChange {
+    pub setting: Setting,
+    pub old_value: Value,
+    pub new_value: Value,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub id: u64,
+    /// Unix time in milliseconds of the latest change merged into the entry
+    pub timestamp: u64,
+    pub origin: Origin,
+    pub changes: Vec<ControlChange>,
+    pub settings: Vec<SettingChange>,
+    /// When a change was last merged in, None once the entry can't take any more
+    #[serde(skip)]
+    updated: Option<Instant>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct HistoryState {
+    /// Oldest first, the last one is what `undo` reverts
+    pub entries: Vec<HistoryEntry>,
+    pub redo_count: usize,
+}
+
+#[derive(Default)]
+struct History {
+    undo: VecDeque<HistoryEntry>,
+    redo: Vec<HistoryEntry>,
+    next_id: u64,
+}
+
+static HISTORY: Lazy<Mutex<History>> = Lazy::new(|| Mutex::new(History::default()));
+
+fn unix_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+/// Where a running ramp is headed rather than whatever step is on the device right now. Levels
+/// the monitor section returns to go on top later, they are client values like the virtual ones.
+fn effective_value(control_name: &str, device_value: ControlValue) -> ControlValue {
+    match ramp::pending_target(control_name) {
+        Some(target) => ControlValue::Volume(target),
+        None => device_value,
+    }
+}
+
+fn read_values(card_index: &str, controls: &[String]) -> BTreeMap<String, ControlValue> {
+    let device_values: BTreeMap<String, ControlValue> = if controls.len() > BULK_READ_THRESHOLD {
+        let all = general::get_control_values(card_index).unwrap_or_default();
+        controls.iter().filter_map(|name| all.get(name).map(|value| (name.clone(), value.clone()))).collect()
+    } else {
+        controls
+            .iter()
+            .filter_map(|name| general::get_control_value(card_index, name).ok().map(|value| (name.clone(), value)))
+            .collect()
+    };
+
+    let mut values: BTreeMap<String, ControlValue> = device_values
+        .into_iter()
+        .map(|(name, value)| {
+            let value = effective_value(&name, value);
+            (name, value)
+        })
+        .collect();
+
+    // Virtual output volumes only exist in the app, and their crosspoints count at their own level
+    virtual_volume::logical_values(&mut values);
+    // Held controls count at the level they return to, which the section keeps as clients see it
+    for (name, value) in values.iter_mut() {
+        if let Some(held) = section::held_value(name) {
+            *value = ControlValue::Volume(held);
+        }
+    }
+    values.retain(|name, _| controls.contains(name));
+    values
+}
+
+fn control_changes(before: BTreeMap<String, ControlValue>, after: BTreeMap<String, ControlValue>) -> Vec<ControlChange> {
+    before
+        .into_iter()
+        .filter_map(|(control_name, old_value)| {
+            let new_value = after.get(&control_name)?.clone();
+            (old_value != new_value).then_some(ControlChange { control_name, old_value, new_value })
+        })
+        .collect()
+}
+
+/// Runs a change and records what it did to `controls`. Controls that end up unchanged are
+/// left out, and nothing is recorded if the change fails.
+pub fn record<T>(origin: Origin, card_index: &str, controls: &[String], apply: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    let before = read_values(card_index, controls);
+    let result = apply()?;
+    let after = read_values(card_index, controls);
+
+    push(origin, control_changes(before, after), Vec::new());
+    Ok(result)
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, String> {
+    serde_json::to_value(value).map_err(|e| e.to_string())
+}
+
+fn from_value<T: DeserializeOwned>(value: &Value) -> Result<T, String> {
+    serde_json::from_value(value.clone()).map_err(|e| e.to_string())
+}
+
+fn read_setting(app: &AppHandle, setting: &Setting) -> Result<Value, String> {
+    match setting {
+        Setting::Monitor => to_value(section::get_state()),
+        Setting::StereoLink => to_value(stereo_link::get_settings()),
+        Setting::Channel(control_name) => {
+            let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+            let config = storage.load_config().map_err(|e| e.to_string())?;
+            to_value(config.channels.get(control_name))
+        }
+        Setting::Ramp => to_value(ramp::settings()),
+        Setting::OutputLimit(output) => to_value(safety::get_limits(app)?.get(output)),
+        Setting::PhantomInterlocks => to_value(phantom::get_interlocks(app)?),
+    }
+}
+
+fn apply_setting(app: &AppHandle, card_index: &str, setting: &Setting, value: &Value) -> Result<(), String> {
+    match setting {
+        Setting::Monitor => {
+            section::set_state(app, card_index, from_value(value)?)?;
+            section::emit_state(app);
+            Ok(())
+        }
+        Setting::StereoLink => stereo_link::set_settings(app, from_value(value)?),
+        Setting::Channel(control_name) => stereo_link::save_channel(app, card_index, control_name, from_value(value)?),
+        Setting::Ramp => ramp::set_settings(app, from_value(value)?),
+        Setting::OutputLimit(output) => {
+            let limit: Option<safety::OutputLimit> = from_value(value)?;
+            safety::set_limit(app, card_index, *output, limit.unwrap_or_default())
+        }
+        Setting::PhantomInterlocks => phantom::set_interlocks(app, from_value(value)?),
+    }
+}
+
+/// Like `record`, for changes to app state outside the device controls. `controls` are the
+/// controls the change may write along the way, e.g. the partner's when a pair gets linked.
+pub fn record_settings<T>(
+    app: &AppHandle,
+    origin: Origin,
+    card_index: &str,
+    settings: &[Setting],
+    controls: &[String],
+    apply: impl FnOnce() -> Result<T, String>,
+) -> Result<T, String> {
+    let before_settings = settings
+        .iter()
+        .map(|setting| read_setting(app, setting))
+        .collect::<Result<Vec<Value>, String>>()?;
+    let before = read_values(card_index, controls);
+    let result = apply()?;
+    let after = read_values(card_index, controls);
+
+    let setting_changes: Vec<SettingChange> = settings
+        .iter()
+        .zip(before_settings)
+        .filter_map(|(setting, old_value)| {
+            let new_value = read_setting(app, setting).ok()?;
+            (old_value != new_value).then(|| SettingChange {
+                setting: setting.clone(),
+                old_value,
+                new_value,
+            })
+        })
+        .collect();
+
+    push(origin, control_changes(before, after), setting_changes);
+    Ok(result)
+}
+
+/// Controls that move together in one gesture share a key: both sides of an output pair, and
+/// a source's two crosspoints into one
+fn gesture_key(control_name: &str) -> String {
+    if let Some(xp) = crosspoint::parse_crosspoint(control_name) {
+        let pair = crosspoint::OUTPUT_ROUTES.iter().position(|route| *route == xp.output).unwrap_or(0) / 2;
+        return format!("{} -> {}", xp.source, pair);
+    }
+
+    let output = OutputPair::ALL.iter().find(|pair| {
+        let (left, right) = pair.control_names();
+        control_name == left || control_name == right
+    });
+    match output {
+        Some(pair) => format!("{:?}", pair),
+        None => control_name.to_string(),
+    }
+}
+
+fn gesture_keys(changes: &[ControlChange]) -> BTreeSet<String> {
+    changes.iter().map(|change| gesture_key(&change.control_name)).collect()
+}
+
+/// A recent entry with the same origin and gesture takes the change instead of a new entry,
+/// even if other changes came in between, as long as none of them touched its controls.
+fn merge(history: &mut History, origin: Origin, changes: &[ControlChange], now: Instant) -> bool {
+    // Only continuous controls are merged, two quick clicks on a switch are two entries
+    let continuous = |changes: &[ControlChange]| changes.iter().all(|change| matches!(change.new_value, ControlValue::Volume(_)));
+    if !continuous(changes) {
+        return false;
+    }
+
+    let keys = gesture_keys(changes);
+    let mut touched_since = BTreeSet::new();
+
+    for entry in history.undo.iter_mut().rev() {
+        if entry.updated.is_none_or(|updated| now.duration_since(updated) >= MERGE_WINDOW) {
+            return false;
+        }
+
+        let entry_keys = gesture_keys(&entry.changes);
+        let same_gesture = entry.origin == origin && entry.settings.is_empty() && continuous(&entry.changes) && entry_keys == keys;
+
+        if same_gesture && touched_since.is_disjoint(&keys) {
+            // Keep the first old value of each control and take the latest new one
+            for change in changes {
+                match entry.changes.iter_mut().find(|merged| merged.control_name == change.control_name) {
+                    Some(merged) => merged.new_value = change.new_value.clone(),
+                    None => entry.changes.push(change.clone()),
+                }
+            }
+            entry.timestamp = unix_millis();
+            entry.updated = Some(now);
+            return true;
+        }
+        touched_since.extend(entry_keys);
+    }
+    false
+}
+
+fn push(origin: Origin, changes: Vec<ControlChange>, settings: Vec<SettingChange>) {
+    if changes.is_empty() && settings.is_empty() {
+        return;
+    }
+    let Ok(mut history) = HISTORY.lock() else {
+        return;
+    };
+
+    let now = Instant::now();
+    history.redo.clear();
+
+    if settings.is_empty() && merge(&mut history, origin, &changes, now) {
+        return;
+    }
+
+    history.next_id += 1;
+    let entry = HistoryEntry {
+        id: history.next_id,
+        timestamp: unix_millis(),
+        origin,
+        changes,
+        settings,
+        updated: Some(now),
+    };
+    history.undo.push_back(entry);
+
+    while history.undo.len() > MAX_ENTRIES {
+        history.undo.pop_front();
+    }
+}
+
+/// Writes a value back through the path a client would have used, so ramps, safety limits
+/// and the phantom interlocks all still apply.
+fn apply_value(app: &AppHandle, card_index: &str, control_name: &str, value: &ControlValue) -> Result<(), String> {
+    match value {
+        ControlValue::Volume(level) if control_name.ends_with(" Gain") => input_gain::set_input_gain(card_index, control_name, *level),
+        ControlValue::Volume(level) => volume::set_volume(card_index, control_name, *level),
+        ControlValue::Switch(on) if control_name.ends_with(" 48V") => phantom::set_phantom_power(app, card_index, control_name, *on, false),
+        ControlValue::Switch(on) if control_name.ends_with(" PAD") => switches::set_pad_state(card_index, control_name, *on),
+        _ => general::set_control_value(card_index, control_name, value),
+    }
+}
+
+/// Settings go first, so e.g. a pair is linked again before its partner's controls are restored.
+fn replay(app: &AppHandle, card_index: &str, entry: &HistoryEntry, undo: bool) -> Result<(), String> {
+    let mut errors = Vec::new();
+
+    for change in &entry.settings {
+        let value = if undo { &change.old_value } else { &change.new_value };
+        if let Err(e) = apply_setting(app, card_index, &change.setting, value) {
+            errors.push(format!("{:?}: {}", change.setting, e.trim()));
+        }
+    }
+
+    for change in &entry.changes {
+        let value = if undo { &change.old_value } else { &change.new_value };
+        if let Err(e) = apply_value(app, card_index, &change.control_name, value) {
+            errors.push(format!("{}: {}", change.control_name, e.trim()));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Failed to restore some controls: {}", errors.join(", ")))
+    }
+}
+
+/// Reverts the latest entry. Returns it, or None when there is nothing to undo. An entry that
+/// can't be fully reverted stays where it is, so it can be tried again.
+pub fn undo(app: &AppHandle, card_index: &str) -> Result<Option<HistoryEntry>, String> {
+    let entry = {
+        let mut history = HISTORY.lock().map_err(|e| e.to_string())?;
+        match history.undo.pop_back() {
+            Some(mut entry) => {
+                entry.updated = None;
+                entry
+            }
+            None => return Ok(None),
+        }
+    };
+
+    let result = replay(app, card_index, &entry, true);
+    let mut history = HISTORY.lock().map_err(|e| e.to_string())?;
+    match result {
+        Ok(()) => {
+            history.redo.push(entry.clone());
+            Ok(Some(entry))
+        }
+        Err(e) => {
+            history.undo.push_back(entry);
+            Err(e)
+        }
+    }
+}
+
+/// Re-applies the latest undone entry. Returns it, or None when there is nothing to redo.
+/// An entry that can't be fully re-applied stays where it is.
+pub fn redo(app: &AppHandle, card_index: &str) -> Result<Option<HistoryEntry>, String> {
+    let entry = {
+        let mut history = HISTORY.lock().map_err(|e| e.to_string())?;
+        match history.redo.pop() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        }
+    };
+
+    let result = replay(app, card_index, &entry, false);
+    let mut history = HISTORY.lock().map_err(|e| e.to_string())?;
+    match result {
+        Ok(()) => {
+            history.undo.push_back(entry.clone());
+            Ok(Some(entry))
+        }
+        Err(e) => {
+            history.redo.push(entry);
+            Err(e)
+        }
+    }
+}
+
+pub fn get_state() -> HistoryState {
+    HISTORY
+        .lock()
+        .map(|history| HistoryState {
+            entries: history.undo.iter().cloned().collect(),
+            redo_count: history.redo.len(),
+        })
+        .unwrap_or(HistoryState { entries: Vec::new(), redo_count: 0 })
+}
+
+pub fn clear() {
+    if let Ok(mut history) = HISTORY.lock() {
+        *history = History::default();
+    }
+}
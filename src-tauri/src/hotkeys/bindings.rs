@@ -0,0 +1,159 @@
+use crate::AppState;
+use crate::alsa::{stereo_link, switches, volume};
+use crate::alsa::volume::OutputPair;
+use crate::history::journal::{self, Origin, Setting};
+use crate::monitor::section::{self, MonitorState};
+use crate::pipewire::buffer_size;
+use crate::preset::snapshot;
+use crate::storage::config::ConfigStorage;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HotkeyAction {
+    StepVolume { output: OutputPair, step_db: f64 },
+    ToggleMute,
+    ToggleDim,
+    ToggleMono,
+    ToggleSpeakers,
+    TogglePad { control_name: String },
+    RecallPreset { index: usize },
+    CycleQuantum,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HotkeyBinding {
+    /// Accelerator string, e.g. "Ctrl+Alt+ArrowUp"
+    pub shortcut: String,
+    pub action: HotkeyAction,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct HotkeyConflict {
+    pub shortcut: String,
+    pub reason: String,
+}
+
+// Conflicts found during the last registration, kept so the UI can ask for them after startup
+static CONFLICTS: Lazy<Mutex<Vec<HotkeyConflict>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+pub fn load_bindings(app: &AppHandle) -> Result<Vec<HotkeyBinding>, String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    Ok(config.settings.hotkeys)
+}
+
+pub fn save_bindings(app: &AppHandle, bindings: Vec<HotkeyBinding>) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    storage.update(|config| config.settings.hotkeys = bindings).map_err(|e| e.to_string())
+}
+
+pub fn get_conflicts() -> Vec<HotkeyConflict> {
+    CONFLICTS.lock().map(|c| c.clone()).unwrap_or_default()
+}
+
+/// Replaces all registered shortcuts with the given bindings. Bindings that can't be
+/// registered are skipped and reported back, the rest stay active.
+pub fn register_all(app: &AppHandle, bindings: &[HotkeyBinding]) -> Vec<HotkeyConflict> {
+    let global_shortcut = app.global_shortcut();
+    let mut conflicts = Vec::new();
+    let mut registered: Vec<Shortcut> = Vec::new();
+
+    if let Err(e) = global_shortcut.unregister_all() {
+        eprintln!("Failed to unregister hotkeys: {}", e);
+    }
+
+    for binding in bindings {
+        let shortcut = match Shortcut::from_str(&binding.shortcut) {
+            Ok(shortcut) => shortcut,
+            Err(e) => {
+                conflicts.push(HotkeyConflict {
+                    shortcut: binding.shortcut.clone(),
+                    reason: format!("Invalid shortcut: {}", e),
+                });
+                continue;
+            }
+        };
+
+        if registered.contains(&shortcut) {
+            conflicts.push(HotkeyConflict {
+                shortcut: binding.shortcut.clone(),
+                reason: "Bound to more than one action".to_string(),
+            });
+            continue;
+        }
+
+        let action = binding.action.clone();
+        let result = global_shortcut.on_shortcut(shortcut, move |app, _shortcut, event| {
+            if event.state == ShortcutState::Pressed {
+                handle_action(app, &action);
+            }
+        });
+
+        match result {
+            Ok(()) => registered.push(shortcut),
+            Err(e) => conflicts.push(HotkeyConflict {
+                shortcut: binding.shortcut.clone(),
+                reason: format!("Could not register, it may be taken by another application: {}", e),
+            }),
+        }
+    }
+
+    if !conflicts.is_empty() {
+        eprintln!("Hotkey conflicts: {:?}", conflicts);
+        let _ = app.emit("hotkey-conflicts", &conflicts);
+    }
+    if let Ok(mut stored) = CONFLICTS.lock() {
+        *stored = conflicts.clone();
+    }
+
+    conflicts
+}
+
+fn handle_action(app: &AppHandle, action: &HotkeyAction) {
+    match run_action(app, action) {
+        Ok(()) => {
+            let _ = app.emit("controls-changed", ());
+        }
+        Err(e) => eprintln!("Hotkey action {:?} failed: {}", action, e),
+    }
+}
+
+fn monitor_action(app: &AppHandle, card_number: &str, apply: impl FnOnce() -> Result<MonitorState, String>) -> Result<(), String> {
+    journal::record_settings(app, Origin::Hotkey, card_number, &[Setting::Monitor], &[], apply)?;
+    section::emit_state(app);
+    Ok(())
+}
+
+fn run_action(app: &AppHandle, action: &HotkeyAction) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let card_number = &state.alsa_card_number;
+
+    match action {
+        HotkeyAction::StepVolume { output, step_db } => {
+            let (left, right) = output.control_names();
+            let controls = [left.to_string(), right.to_string()];
+            journal::record(Origin::Hotkey, card_number, &controls, || volume::step_output_volume(card_number, *output, *step_db)).map(|_| ())
+        }
+        HotkeyAction::ToggleMute => monitor_action(app, card_number, || section::toggle_mute(card_number)),
+        HotkeyAction::ToggleDim => monitor_action(app, card_number, || section::toggle_dim(card_number)),
+        HotkeyAction::ToggleMono => monitor_action(app, card_number, || section::toggle_mono(card_number)),
+        HotkeyAction::ToggleSpeakers => monitor_action(app, card_number, || section::toggle_speaker(card_number)),
+        HotkeyAction::TogglePad { control_name } => {
+            let pad_on = switches::get_pad_state(card_number, control_name)?;
+            let controls = stereo_link::affected_controls(control_name);
+            journal::record(Origin::Hotkey, card_number, &controls, || stereo_link::set_pad_state(card_number, control_name, !pad_on))
+        }
+        HotkeyAction::RecallPreset { index } => snapshot::recall_preset(app, card_number, *index).map(|_| ()),
+        HotkeyAction::CycleQuantum => {
+            buffer_size::cycle_clock_quantum()?;
+            crate::tray::menu::refresh(app);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use tauri::AppHandle;
+use super::bindings::{self, HotkeyBinding, HotkeyConflict};
+
+#[tauri::command]
+pub fn get_hotkeys(app_handle: AppHandle) -> Result<Vec<HotkeyBinding>, String> {
+    bindings::load_bindings(&app_handle)
+}
+
+#[tauri::command]
+pub fn set_hotkeys(app_handle: AppHandle, hotkeys: Vec<HotkeyBinding>) -> Result<Vec<HotkeyConflict>, String> {
+    bindings::save_bindings(&app_handle, hotkeys.clone())?;
+    Ok(bindings::register_all(&app_handle, &hotkeys))
+}
+
+#[tauri::command]
+pub fn get_hotkey_conflicts() -> Vec<HotkeyConflict> {
+    bindings::get_conflicts()
+}
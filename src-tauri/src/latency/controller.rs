@@ -0,0 +1,18 @@
+use tauri::AppHandle;
+use super::measurement::{self, LatencyParams, LatencyResult};
+
+/// Runs off the main thread, the measurement takes a couple of seconds
+#[tauri::command(async)]
+pub fn measure_latency(app_handle: AppHandle, params: LatencyParams) -> Result<LatencyResult, String> {
+    measurement::measure(&app_handle, &params)
+}
+
+#[tauri::command]
+pub fn get_latency_results(app_handle: AppHandle) -> Result<Vec<LatencyResult>, String> {
+    measurement::get_results(&app_handle)
+}
+
+#[tauri::command]
+pub fn clear_latency_results(app_handle: AppHandle) -> Result<(), String> {
+    measurement::clear_results(&app_handle)
+}
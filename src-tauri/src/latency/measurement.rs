@@ -0,0 +1,161 @@
+use crate::dsp::fft;
+use crate::pipewire::{buffer_size, loopback};
+use crate::storage::config::ConfigStorage;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+// Longest round trip looked for, several times the largest quantum
+const MAX_LATENCY_SECONDS: f64 = 1.0;
+// Correlation peak over the rest of the correlation, below this the loopback wasn't found
+const MIN_CONFIDENCE_DB: f64 = 15.0;
+const MLS_ORDER: u32 = 15;
+// The sequence is 32767 frames, under a second at any rate the Babyface runs at
+const SIGNAL_SECONDS: f64 = 1.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LatencySignal {
+    /// A single full-scale sample, quick but easily lost in noise
+    Impulse,
+    /// Maximum length sequence, robust against noise and hum
+    Mls,
+}
+
+fn default_signal() -> LatencySignal {
+    LatencySignal::Mls
+}
+
+fn default_level_db() -> f64 {
+    -12.0
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LatencyParams {
+    /// Output route the signal is played on, e.g. "AN1"
+    pub output: String,
+    /// Input the loopback cable comes back on, e.g. "Line-IN3"
+    pub input: String,
+    #[serde(default = "default_signal")]
+    pub signal: LatencySignal,
+    #[serde(default = "default_level_db")]
+    pub level_db: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LatencyResult {
+    pub output: String,
+    pub input: String,
+    pub signal: LatencySignal,
+    pub rate: u32,
+    /// None when the quantum couldn't be read from the settings metadata
+    pub quantum: Option<u32>,
+    pub latency_frames: u64,
+    pub latency_ms: f64,
+    /// What PipeWire reports for the playback and capture paths together
+    pub reported_frames: i64,
+    pub reported_ms: f64,
+    /// Measured minus reported, the offset to enter in a DAW that already compensates for
+    /// what PipeWire reports
+    pub daw_offset_frames: i64,
+    /// The signal came back inverted somewhere in the loop
+    pub polarity_inverted: bool,
+    /// How far the correlation peak stands out, in dB
+    pub confidence_db: f64,
+    /// Unix time in seconds
+    pub measured_at: u64,
+}
+
+/// One period of a maximum length sequence as +-1, from a 15 bit Fibonacci LFSR (taps 15, 14).
+fn mls() -> Vec<f32> {
+    let mask = (1u32 << MLS_ORDER) - 1;
+    let mut state = mask;
+    (0..mask)
+        .map(|_| {
+            let bit = ((state >> (MLS_ORDER - 1)) ^ (state >> (MLS_ORDER - 2))) & 1;
+            state = ((state << 1) | bit) & mask;
+            if bit == 1 { 1.0 } else { -1.0 }
+        })
+        .collect()
+}
+
+/// Plays the signal on the output and finds it again on the input, which has to be connected
+/// to the output with a cable. The result is stored for the connected device.
+pub fn measure(app: &AppHandle, params: &LatencyParams) -> Result<LatencyResult, String> {
+    let _claim = loopback::claim()?;
+
+    let gain = 10f64.powf(params.level_db.min(0.0) / 20.0) as f32;
+    let signal = params.signal;
+    let build = Box::new(move |_rate: u32| {
+        let sequence = match signal {
+            LatencySignal::Impulse => vec![1.0],
+            LatencySignal::Mls => mls(),
+        };
+        sequence.into_iter().map(|sample| sample * gain).collect()
+    });
+
+    let run = loopback::play_and_capture(&params.output, &params.input, build, SIGNAL_SECONDS, MAX_LATENCY_SECONDS)?;
+
+    let correlation = fft::cross_correlate(&run.captured, &run.signal);
+    let (lag, peak) = correlation
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+        .map(|(lag, value)| (lag, *value))
+        .ok_or("Nothing was captured")?;
+
+    let rest: f64 = correlation.iter().map(|value| value * value).sum::<f64>() - peak * peak;
+    let rest_rms = (rest / (correlation.len().max(2) - 1) as f64).sqrt();
+    let confidence_db = if rest_rms > 0.0 { 20.0 * (peak.abs() / rest_rms).log10() } else { f64::INFINITY };
+    if confidence_db < MIN_CONFIDENCE_DB {
+        return Err(format!(
+            "The signal from {} wasn't found on {} ({:.1} dB above the noise). Check the loopback cable and the input gain",
+            params.output, params.input, confidence_db
+        ));
+    }
+
+    let to_ms = |frames: f64| frames * 1000.0 / run.rate.max(1) as f64;
+    let reported_frames = run.playback_delay + run.capture_delay;
+    let result = LatencyResult {
+        output: params.output.clone(),
+        input: params.input.clone(),
+        signal: params.signal,
+        rate: run.rate,
+        quantum: buffer_size::get_clock_quantum().ok(),
+        latency_frames: lag as u64,
+        latency_ms: to_ms(lag as f64),
+        reported_frames,
+        reported_ms: to_ms(reported_frames as f64),
+        daw_offset_frames: lag as i64 - reported_frames,
+        polarity_inverted: peak < 0.0,
+        confidence_db,
+        measured_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+    };
+
+    store_result(app, result.clone())?;
+    Ok(result)
+}
+
+/// Keeps the newest result per output, input, rate and quantum.
+fn store_result(app: &AppHandle, result: LatencyResult) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    storage
+        .update(|config| {
+            config.latency.retain(|stored| {
+                !(stored.output == result.output && stored.input == result.input && stored.rate == result.rate && stored.quantum == result.quantum)
+            });
+            config.latency.push(result);
+        })
+        .map_err(|e| e.to_string())
+}
+
+pub fn get_results(app: &AppHandle) -> Result<Vec<LatencyResult>, String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    Ok(config.latency)
+}
+
+pub fn clear_results(app: &AppHandle) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    storage.update(|config| config.latency.clear()).map_err(|e| e.to_string())
+}
@@ -0,0 +1,29 @@
+use tauri::AppHandle;
+use super::meter::{self, LoudnessReading};
+
+/// Loudness of what is played to the main outputs, see `meter::start`. Readings arrive as
+/// "playback-loudness-changed" events every 100 ms while the meter runs.
+#[tauri::command]
+pub fn start_playback_loudness(app_handle: AppHandle) -> Result<LoudnessReading, String> {
+    meter::start(&app_handle)?;
+    Ok(meter::get_reading())
+}
+
+#[tauri::command]
+pub fn stop_playback_loudness(app_handle: AppHandle) -> Result<LoudnessReading, String> {
+    meter::stop()?;
+    meter::emit_reading(&app_handle);
+    Ok(meter::get_reading())
+}
+
+#[tauri::command]
+pub fn reset_playback_loudness(app_handle: AppHandle) -> Result<LoudnessReading, String> {
+    meter::reset()?;
+    meter::emit_reading(&app_handle);
+    Ok(meter::get_reading())
+}
+
+#[tauri::command]
+pub fn get_playback_loudness() -> LoudnessReading {
+    meter::get_reading()
+}
@@ -0,0 +1,125 @@
+use crate::alsa::volume::OutputPair;
+use crate::dsp::loudness::LoudnessMeter;
+use crate::pipewire::audio::{self, BlockInfo};
+use crate::pipewire::tap::{self, Tap, TapSource};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+// Momentary loudness moves on every 100 ms
+const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Emitted as "playback-loudness-changed". Values stay None until there is enough audio for them.
+#[derive(Serialize, Debug, Clone)]
+pub struct LoudnessReading {
+    pub running: bool,
+    pub momentary_lufs: Option<f64>,
+    pub short_term_lufs: Option<f64>,
+    pub momentary_max_lufs: Option<f64>,
+    pub short_term_max_lufs: Option<f64>,
+    pub integrated_lufs: Option<f64>,
+    pub loudness_range_lu: Option<f64>,
+    pub true_peak_dbtp: Option<f64>,
+    /// Audio measured since the last reset
+    pub seconds: f64,
+}
+
+struct Running {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+    _tap: Tap,
+}
+
+// Kept across stop and start so the integrated loudness carries on, until a reset
+static METER: Lazy<Mutex<Option<LoudnessMeter>>> = Lazy::new(|| Mutex::new(None));
+static RUNNING: Lazy<Mutex<Option<Running>>> = Lazy::new(|| Mutex::new(None));
+
+pub fn get_reading() -> LoudnessReading {
+    let running = RUNNING.lock().map(|running| running.is_some()).unwrap_or(false);
+    // Copied under the lock the audio thread takes, worked out after it's released
+    let blocks = METER.lock().ok().and_then(|meter| meter.as_ref().map(|meter| meter.blocks()));
+    let values = blocks.map(|blocks| blocks.values()).unwrap_or_default();
+    LoudnessReading {
+        running,
+        momentary_lufs: values.momentary,
+        short_term_lufs: values.short_term,
+        momentary_max_lufs: values.momentary_max,
+        short_term_max_lufs: values.short_term_max,
+        integrated_lufs: values.integrated,
+        loudness_range_lu: values.range,
+        true_peak_dbtp: values.true_peak,
+        seconds: values.seconds,
+    }
+}
+
+pub fn emit_reading(app: &AppHandle) {
+    let _ = app.emit("playback-loudness-changed", get_reading());
+}
+
+/// Measures what applications play to the main outputs, from the sink's monitor. It's a playback
+/// meter: inputs monitored through the Babyface's mixer never pass the sink, so they aren't in it.
+pub fn start(app: &AppHandle) -> Result<(), String> {
+    let mut running = RUNNING.lock().map_err(|e| e.to_string())?;
+    if running.is_some() {
+        return Ok(());
+    }
+
+    let (left_route, right_route) = OutputPair::Main.routes();
+    let (_, left) = audio::playback_target(left_route)?;
+    let (_, right) = audio::playback_target(right_route)?;
+
+    let on_samples = Box::new(move |samples: &[f32], info: BlockInfo| {
+        let Ok(mut meter) = METER.lock() else {
+            return;
+        };
+        if left.max(right) >= info.channels {
+            return;
+        }
+        // A new rate starts a new measurement, the filters are made for one rate
+        if meter.as_ref().map(|meter| meter.rate() != info.rate).unwrap_or(true) {
+            *meter = Some(LoudnessMeter::new(info.rate, 2));
+        }
+        if let Some(meter) = meter.as_mut() {
+            for frame in samples.chunks_exact(info.channels) {
+                meter.process_frame(&[frame[left], frame[right]]);
+            }
+        }
+    });
+    let tap = tap::subscribe(TapSource::Outputs, on_samples)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let (app, stop) = (app.clone(), stop.clone());
+        thread::Builder::new()
+            .name("loudness".to_string())
+            .spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(UPDATE_INTERVAL);
+                    emit_reading(&app);
+                }
+            })
+            .map_err(|e| format!("Failed to start the loudness meter: {}", e))?
+    };
+
+    *running = Some(Running { stop, thread, _tap: tap });
+    Ok(())
+}
+
+/// Stops measuring and keeps the values, a later start carries on from them.
+pub fn stop() -> Result<(), String> {
+    let running = RUNNING.lock().map_err(|e| e.to_string())?.take();
+    if let Some(running) = running {
+        running.stop.store(true, Ordering::Relaxed);
+        let _ = running.thread.join();
+    }
+    Ok(())
+}
+
+pub fn reset() -> Result<(), String> {
+    *METER.lock().map_err(|e| e.to_string())? = None;
+    Ok(())
+}
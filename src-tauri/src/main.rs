@@ -2,8 +2,25 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod alsa;
+mod autogain;
+mod capabilities;
+mod diagnostics;
+mod dsp;
+mod generator;
+mod history;
+mod hotkeys;
+mod latency;
+mod loudness;
+mod measurement;
+mod monitor;
 mod pipewire;
+mod preset;
+mod recorder;
+mod spectrum;
+mod stereo;
 mod storage;
+mod tray;
+mod tuner;
 
 use tauri::Manager;
 
@@ -27,12 +44,76 @@ impl AppState {
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tauri::Builder::default()
     .plugin(tauri_plugin_shell::init())
+    .plugin(tauri_plugin_global_shortcut::Builder::new().build())
     .setup(|app| {
             let app_handle = app.handle(); // Get an immutable AppHandle
-            let app_state = AppState::new(&alsa::general::find_babyface_card()?, "RME_Babyface_Pro", app_handle)?;
+            let app_state = AppState::new(&alsa::general::find_babyface_card()?, pipewire::general::CARD_NAME, app_handle)?;
             // #[cfg(debug_assertions)] // only include this code on debug builds
 
+            // Has to come before anything reads the config, channels and presets are per device
+            if let Err(e) = storage::device::set_current(&app_state.alsa_card_number) {
+                eprintln!("Failed to identify the device, using the shared config: {}", e);
+            }
+
+            match capabilities::detect::load(&app_state.alsa_card_number) {
+                Ok(capabilities) if capabilities.compatibility_mode => {
+                    println!("Kernel {} has no gain or main volume controls, using compatibility mode", capabilities.kernel.release);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Failed to detect device capabilities: {}", e),
+            }
+            if let Err(e) = alsa::db_scale::load_scales(&app_state.alsa_card_number) {
+                eprintln!("Failed to read control dB scales: {}", e);
+            }
+            match capabilities::detect::current(&app_state.alsa_card_number) {
+                Ok(capabilities) => match alsa::virtual_volume::load(app.handle(), &app_state.alsa_card_number, &capabilities) {
+                    Ok(0) => {}
+                    Ok(count) => println!("Using a virtual volume on {} outputs", count),
+                    Err(e) => eprintln!("Failed to set up virtual output volumes: {}", e),
+                },
+                Err(e) => eprintln!("Failed to set up virtual output volumes: {}", e),
+            }
+            if let Err(e) = alsa::ramp::load_settings(app.handle()) {
+                eprintln!("Failed to load ramp settings: {}", e);
+            }
+            if let Err(e) = alsa::safety::load_limits(app.handle()) {
+                eprintln!("Failed to load output limits: {}", e);
+            }
+            // The last state goes on first, the power-on levels still have the final say
+            match storage::device::restore_on_start(app.handle(), &app_state.alsa_card_number) {
+                Ok(true) => println!("Restored the last state of device {}", storage::device::current_key()),
+                Ok(false) => {}
+                Err(e) => eprintln!("Failed to restore the last state: {}", e),
+            }
+            if let Err(e) = alsa::safety::apply_power_on_levels(&app_state.alsa_card_number) {
+                eprintln!("Failed to apply power-on levels: {}", e);
+            }
+
+            if let Err(e) = alsa::stereo_link::load_links(app.handle()) {
+                eprintln!("Failed to load stereo links: {}", e);
+            }
+            match recorder::session::get_settings(app.handle()) {
+                Ok(settings) => {
+                    if let Err(e) = recorder::session::apply_rolling(&settings) {
+                        eprintln!("Failed to start the rolling buffer: {}", e);
+                    }
+                }
+                Err(e) => eprintln!("Failed to load recorder settings: {}", e),
+            }
+
             app.manage(app_state);
+            monitor::section::register_hold();
+            if let Err(e) = monitor::section::load_config(app.handle()) {
+                eprintln!("Failed to load monitor config: {}", e);
+            }
+            tray::menu::create(app.handle())?;
+
+            match hotkeys::bindings::load_bindings(app.handle()) {
+                Ok(bindings) => {
+                    hotkeys::bindings::register_all(app.handle(), &bindings);
+                }
+                Err(e) => eprintln!("Failed to load hotkeys: {}", e),
+            }
 
             {
                 // let window = app.get_webview_window("main").unwrap();
@@ -41,6 +122,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             Ok(())
         })
+        .on_window_event(tray::background::handle_window_event)
         .invoke_handler(tauri::generate_handler![
             alsa::controller::get_alsa_volume,
             alsa::controller::set_alsa_volume,
@@ -52,7 +134,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             alsa::controller::set_pad_state,
             alsa::controller::get_phantom_power_state,
             alsa::controller::set_phantom_power,
+            alsa::controller::get_phantom_interlocks,
+            alsa::controller::set_phantom_interlocks,
+            alsa::controller::get_phantom_log,
             alsa::controller::get_soundcard_controls,
+            alsa::controller::get_input_gain_db,
+            alsa::controller::set_input_gain_db,
+            alsa::controller::get_volume_db,
+            alsa::controller::set_volume_db,
+            alsa::controller::get_control_db_range,
+            alsa::controller::get_stereo_link_settings,
+            alsa::controller::set_stereo_link_settings,
+            alsa::controller::get_output_limits,
+            alsa::controller::set_output_limit,
+            alsa::controller::get_ramp_settings,
+            alsa::controller::set_ramp_settings,
+            capabilities::controller::get_capabilities,
+            capabilities::controller::refresh_capabilities,
+            diagnostics::controller::collect_diagnostics,
             pipewire::controller::get_pipewire_active_profile,
             pipewire::controller::set_pipewire_profile,
             pipewire::controller::get_pipewire_profiles,
@@ -60,9 +159,79 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             pipewire::controller::set_clock_quantum,
             storage::controller::save_channel_config,
             storage::controller::load_channel_config,
-            storage::controller::load_all_channels
+            storage::controller::load_all_channels,
+            storage::controller::get_config_warning,
+            storage::controller::export_preset,
+            storage::controller::export_config,
+            storage::controller::import_file,
+            storage::controller::get_device_identity,
+            storage::controller::restore_last_state,
+            storage::controller::get_restore_last_state_on_start,
+            storage::controller::set_restore_last_state_on_start,
+            history::controller::get_history,
+            history::controller::undo,
+            history::controller::redo,
+            history::controller::clear_history,
+            autogain::controller::run_auto_gain,
+            generator::controller::start_generator,
+            generator::controller::stop_generator,
+            generator::controller::get_generator_status,
+            latency::controller::measure_latency,
+            latency::controller::get_latency_results,
+            latency::controller::clear_latency_results,
+            measurement::controller::measure_frequency_response,
+            measurement::controller::measure_thd_n,
+            measurement::controller::measure_noise_floor,
+            measurement::controller::export_measurement_csv,
+            spectrum::controller::start_spectrum,
+            spectrum::controller::stop_spectrum,
+            spectrum::controller::get_spectrum_status,
+            loudness::controller::start_playback_loudness,
+            loudness::controller::stop_playback_loudness,
+            loudness::controller::reset_playback_loudness,
+            loudness::controller::get_playback_loudness,
+            stereo::controller::start_stereo_analysis,
+            stereo::controller::stop_stereo_analysis,
+            stereo::controller::get_stereo_analysis_status,
+            tuner::controller::start_tuner,
+            tuner::controller::stop_tuner,
+            tuner::controller::get_tuner_status,
+            tuner::controller::set_tuner_settings,
+            recorder::controller::start_recording,
+            recorder::controller::stop_recording,
+            recorder::controller::get_recorder_status,
+            recorder::controller::save_rolling_buffer,
+            recorder::controller::get_recorder_settings,
+            recorder::controller::set_recorder_settings,
+            preset::controller::list_presets,
+            preset::controller::save_preset,
+            preset::controller::recall_preset,
+            preset::controller::delete_preset,
+            tray::controller::get_close_to_tray,
+            tray::controller::set_close_to_tray,
+            hotkeys::controller::get_hotkeys,
+            hotkeys::controller::set_hotkeys,
+            hotkeys::controller::get_hotkey_conflicts,
+            monitor::controller::get_monitor_state,
+            monitor::controller::set_monitor_dim,
+            monitor::controller::set_monitor_mute,
+            monitor::controller::set_monitor_mono,
+            monitor::controller::set_monitor_speaker,
+            monitor::controller::set_monitor_config
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                // Don't leave the speakers dimmed or muted behind when the app goes away
+                let state = app_handle.state::<AppState>();
+                if let Err(e) = storage::device::save_last_state(app_handle, &state.alsa_card_number) {
+                    eprintln!("Failed to save the device state: {}", e);
+                }
+                if let Err(e) = monitor::section::release_all(&state.alsa_card_number) {
+                    eprintln!("Failed to release monitor section: {}", e);
+                }
+            }
+        });
     Ok(())
 }
\ No newline at end of file
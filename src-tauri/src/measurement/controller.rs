@@ -0,0 +1,25 @@
+use crate::AppState;
+use tauri::State;
+use super::report::{self, Measurement};
+use super::routines::{self, FrequencyResponseParams, NoiseFloorParams, ThdNParams};
+
+/// Runs off the main thread, like all the measurements here
+#[tauri::command(async)]
+pub fn measure_frequency_response(params: FrequencyResponseParams) -> Result<Measurement, String> {
+    routines::frequency_response(&params)
+}
+
+#[tauri::command(async)]
+pub fn measure_thd_n(params: ThdNParams) -> Result<Measurement, String> {
+    routines::thd_n(&params)
+}
+
+#[tauri::command(async)]
+pub fn measure_noise_floor(state: State<AppState>, params: NoiseFloorParams) -> Result<Measurement, String> {
+    routines::noise_floor(&state.alsa_card_number, &params)
+}
+
+#[tauri::command]
+pub fn export_measurement_csv(measurement: Measurement, path: String) -> Result<(), String> {
+    report::export_csv(&measurement, &path)
+}
@@ -0,0 +1,3 @@
+pub mod controller;
+pub mod report;
+pub mod routines;
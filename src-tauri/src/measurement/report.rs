@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::fs;
+
+/// One plottable curve, as (frequency in Hz, value) points
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Series {
+    pub name: String,
+    pub unit: String,
+    pub points: Vec<[f64; 2]>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FrequencyResponse {
+    pub output: String,
+    pub input: String,
+    pub rate: u32,
+    pub level_db: f64,
+    /// Loop gain at 1 kHz, what the curve would be normalised to
+    pub gain_1khz_db: f64,
+    /// Largest distance of the curve from the 1 kHz gain
+    pub deviation_db: f64,
+    pub series: Vec<Series>,
+    /// Unix time in seconds
+    pub measured_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Harmonic {
+    pub order: u32,
+    pub frequency: f64,
+    /// Relative to the fundamental
+    pub level_dbc: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThdN {
+    pub output: String,
+    pub input: String,
+    pub rate: u32,
+    pub frequency: f64,
+    pub level_db: f64,
+    /// Level the fundamental came back at
+    pub fundamental_dbfs: f64,
+    /// Everything but the fundamental between 20 Hz and 20 kHz
+    pub thd_n_percent: f64,
+    pub thd_n_db: f64,
+    /// Harmonics only
+    pub thd_percent: f64,
+    pub harmonics: Vec<Harmonic>,
+    pub series: Vec<Series>,
+    pub measured_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InputNoise {
+    pub input: String,
+    /// None for inputs without a gain control
+    pub gain_db: Option<f64>,
+    /// 20 Hz to 20 kHz, unweighted
+    pub rms_dbfs: f64,
+    pub a_weighted_dbfs: f64,
+    pub peak_dbfs: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NoiseFloor {
+    pub rate: u32,
+    pub seconds: f64,
+    pub inputs: Vec<InputNoise>,
+    /// Third-octave spectrum per input
+    pub series: Vec<Series>,
+    pub measured_at: u64,
+}
+
+/// Levels are in dBFS as in AES17, where a full-scale sine reads 0 dBFS
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Measurement {
+    FrequencyResponse(FrequencyResponse),
+    ThdN(ThdN),
+    NoiseFloor(NoiseFloor),
+}
+
+impl Measurement {
+    fn series(&self) -> &[Series] {
+        match self {
+            Measurement::FrequencyResponse(result) => &result.series,
+            Measurement::ThdN(result) => &result.series,
+            Measurement::NoiseFloor(result) => &result.series,
+        }
+    }
+
+    /// The single figures, as they go in the CSV header
+    fn summary(&self) -> Vec<(String, String)> {
+        let mut summary = Vec::new();
+        let mut add = |key: &str, value: String| summary.push((key.to_string(), value));
+
+        match self {
+            Measurement::FrequencyResponse(result) => {
+                add("measurement", "frequency_response".to_string());
+                add("output", result.output.clone());
+                add("input", result.input.clone());
+                add("rate", result.rate.to_string());
+                add("level_db", format!("{:.1}", result.level_db));
+                add("gain_1khz_db", format!("{:.2}", result.gain_1khz_db));
+                add("deviation_db", format!("{:.2}", result.deviation_db));
+                add("measured_at", result.measured_at.to_string());
+            }
+            Measurement::ThdN(result) => {
+                add("measurement", "thd_n".to_string());
+                add("output", result.output.clone());
+                add("input", result.input.clone());
+                add("rate", result.rate.to_string());
+                add("frequency", format!("{:.1}", result.frequency));
+                add("level_db", format!("{:.1}", result.level_db));
+                add("fundamental_dbfs", format!("{:.2}", result.fundamental_dbfs));
+                add("thd_n_percent", format!("{:.5}", result.thd_n_percent));
+                add("thd_n_db", format!("{:.2}", result.thd_n_db));
+                add("thd_percent", format!("{:.5}", result.thd_percent));
+                for harmonic in &result.harmonics {
+                    add(&format!("h{}_dbc", harmonic.order), format!("{:.2}", harmonic.level_dbc));
+                }
+                add("measured_at", result.measured_at.to_string());
+            }
+            Measurement::NoiseFloor(result) => {
+                add("measurement", "noise_floor".to_string());
+                add("rate", result.rate.to_string());
+                add("seconds", format!("{:.1}", result.seconds));
+                for input in &result.inputs {
+                    let gain = input.gain_db.map(|db| format!("{:.1} dB gain, ", db)).unwrap_or_default();
+                    add(
+                        &input.input,
+                        format!("{}{:.2} dBFS, {:.2} dBFS(A), {:.2} dBFS peak", gain, input.rms_dbfs, input.a_weighted_dbfs, input.peak_dbfs),
+                    );
+                }
+                add("measured_at", result.measured_at.to_string());
+            }
+        }
+
+        summary
+    }
+
+    /// Summary as `# key: value` comment lines, then one row per point of every series
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        for (key, value) in self.summary() {
+            let _ = writeln!(csv, "# {}: {}", key, value);
+        }
+        csv.push_str("series,frequency_hz,value,unit\n");
+        for series in self.series() {
+            for [frequency, value] in &series.points {
+                let _ = writeln!(csv, "{},{:.3},{:.3},{}", csv_field(&series.name), frequency, value, csv_field(&series.unit));
+            }
+        }
+        csv
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn export_csv(measurement: &Measurement, path: &str) -> Result<(), String> {
+    fs::write(path, measurement.to_csv()).map_err(|e| format!("Could not write {}: {}", path, e))
+}
@@ -0,0 +1,429 @@
+use super::report::{FrequencyResponse, Harmonic, InputNoise, Measurement, NoiseFloor, Series, ThdN};
+use crate::alsa::{db_scale, input_gain, ramp, stereo_link};
+use crate::dsp::fft;
+use crate::dsp::octave::{self, BandMode};
+use crate::dsp::spectrum::{self, power_to_dbfs};
+use crate::dsp::weighting;
+use crate::dsp::window::Window;
+use crate::generator::signal::{Oscillator, Signal};
+use crate::history::journal::{self, Origin};
+use crate::pipewire::audio::{self, BlockInfo};
+use crate::pipewire::loopback;
+use crate::pipewire::tap::{self, TapSource};
+use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const AUDIO_LOW_HZ: f64 = 20.0;
+const AUDIO_HIGH_HZ: f64 = 20000.0;
+// Long enough for the round trip, see the latency measurement
+const LOOPBACK_TAIL_SECONDS: f64 = 0.5;
+// Below this nothing came back over the cable
+const SILENCE_DB: f64 = -60.0;
+const CLIP_LEVEL: f32 = 0.999;
+const SINE_SECONDS: f64 = 2.0;
+// Bins either side of a tone that belong to it, the Blackman-Harris main lobe is 4 bins wide
+const TONE_BINS: usize = 5;
+const MAX_HARMONIC: u32 = 10;
+// Resolution of the THD+N spectrum in the result
+const SPECTRUM_FRACTION: u32 = 48;
+const NOISE_FFT_SIZE: usize = 8192;
+// Captured before the noise measurement starts, while the gain settles
+const NOISE_SETTLE_SECONDS: f64 = 0.3;
+const MAX_NOISE_SECONDS: f64 = 30.0;
+const MAX_SWEEP_SECONDS: f64 = 30.0;
+
+fn default_response_level_db() -> f64 {
+    -12.0
+}
+
+fn default_start_hz() -> f64 {
+    AUDIO_LOW_HZ
+}
+
+fn default_end_hz() -> f64 {
+    AUDIO_HIGH_HZ
+}
+
+fn default_sweep_seconds() -> f64 {
+    5.0
+}
+
+fn default_resolution() -> u32 {
+    12
+}
+
+fn default_frequency() -> f64 {
+    1000.0
+}
+
+fn default_thd_level_db() -> f64 {
+    -6.0
+}
+
+fn default_noise_seconds() -> f64 {
+    2.0
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FrequencyResponseParams {
+    /// Output route the sweep is played on, e.g. "AN1"
+    pub output: String,
+    /// Input the loopback cable comes back on, e.g. "Line-IN3"
+    pub input: String,
+    #[serde(default = "default_response_level_db")]
+    pub level_db: f64,
+    #[serde(default = "default_start_hz")]
+    pub start_hz: f64,
+    #[serde(default = "default_end_hz")]
+    pub end_hz: f64,
+    #[serde(default = "default_sweep_seconds")]
+    pub seconds: f64,
+    /// Points per octave in the result
+    #[serde(default = "default_resolution")]
+    pub resolution: u32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ThdNParams {
+    pub output: String,
+    pub input: String,
+    #[serde(default = "default_frequency")]
+    pub frequency: f64,
+    #[serde(default = "default_thd_level_db")]
+    pub level_db: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct NoiseFloorParams {
+    pub inputs: Vec<String>,
+    /// Gain the inputs are measured at, they are put back afterwards. None keeps the current gain.
+    pub gain_db: Option<f64>,
+    #[serde(default = "default_noise_seconds")]
+    pub seconds: f64,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn to_db(power: f64) -> f64 {
+    10.0 * power.max(1e-20).log10()
+}
+
+fn peak_dbfs(samples: &[f32]) -> f64 {
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    20.0 * (peak as f64).max(1e-10).log10()
+}
+
+/// Renders `seconds` of the left channel of a generator signal
+fn render(signal: Signal, level_db: f64, seconds: f64, rate: u32) -> Vec<f32> {
+    let gain = 10f64.powf(level_db.min(0.0) / 20.0) as f32;
+    let mut oscillator = Oscillator::new(signal);
+    (0..(seconds * rate as f64) as usize).map(|_| oscillator.next_frame(rate).0 * gain).collect()
+}
+
+/// Errors when the captured signal is too quiet or clipped to measure anything from.
+fn check_level(captured: &[f32], input: &str) -> Result<(), String> {
+    let peak = peak_dbfs(captured);
+    if peak < SILENCE_DB {
+        return Err(format!("Nothing came back on {} ({:.1} dBFS), check the loopback cable and the input gain", input, peak));
+    }
+    if captured.iter().any(|s| s.abs() >= CLIP_LEVEL) {
+        return Err(format!("{} clipped, lower the level or the input gain", input));
+    }
+    Ok(())
+}
+
+fn spectrum_series(name: &str, spectrum: &[f64], bin_hz: f64, fraction: u32, mode: BandMode, high_hz: f64) -> Series {
+    Series {
+        name: name.to_string(),
+        unit: "dBFS".to_string(),
+        points: octave::band_centers(fraction, AUDIO_LOW_HZ, high_hz)
+            .into_iter()
+            .map(|center| [center, power_to_dbfs(octave::band_power(spectrum, bin_hz, center, fraction, mode))])
+            .collect(),
+    }
+}
+
+/// Plays a log sweep over the loopback and divides what came back by what was played.
+pub fn frequency_response(params: &FrequencyResponseParams) -> Result<Measurement, String> {
+    let _claim = loopback::claim()?;
+
+    let seconds = params.seconds.min(MAX_SWEEP_SECONDS);
+    let sweep = Signal::Sweep {
+        start_hz: params.start_hz,
+        end_hz: params.end_hz,
+        seconds,
+    };
+    sweep.validate()?;
+    let level_db = params.level_db;
+    let build = Box::new(move |rate: u32| render(sweep, level_db, seconds, rate));
+
+    let run = loopback::play_and_capture(&params.output, &params.input, build, seconds, LOOPBACK_TAIL_SECONDS)?;
+    check_level(&run.captured, &params.input)?;
+
+    let size = run.captured.len().max(run.signal.len()).next_power_of_two();
+    let to_f64 = |samples: &[f32]| samples.iter().map(|&s| s as f64).collect::<Vec<f64>>();
+    let played = fft::real_fft(&to_f64(&run.signal), size);
+    let captured = fft::real_fft(&to_f64(&run.captured), size);
+
+    // |H|^2 per bin, the latency only turns the phase
+    let transfer: Vec<f64> = played
+        .iter()
+        .zip(&captured)
+        .take(size / 2 + 1)
+        .map(|(p, c)| {
+            let played_power = p.re * p.re + p.im * p.im;
+            if played_power > 0.0 { (c.re * c.re + c.im * c.im) / played_power } else { 0.0 }
+        })
+        .collect();
+
+    let bin_hz = run.rate as f64 / size as f64;
+    let fraction = params.resolution.clamp(1, 48);
+    let high_hz = params.end_hz.min(run.rate as f64 * 0.45);
+    let points: Vec<[f64; 2]> = octave::band_centers(fraction, params.start_hz, high_hz)
+        .into_iter()
+        .map(|center| [center, to_db(octave::band_power(&transfer, bin_hz, center, fraction, BandMode::Mean))])
+        .collect();
+
+    let gain_1khz_db = to_db(octave::band_power(&transfer, bin_hz, 1000.0, fraction, BandMode::Mean));
+    let deviation_db = points.iter().map(|[_, db]| (db - gain_1khz_db).abs()).fold(0.0, f64::max);
+
+    Ok(Measurement::FrequencyResponse(FrequencyResponse {
+        output: params.output.clone(),
+        input: params.input.clone(),
+        rate: run.rate,
+        level_db: params.level_db,
+        gain_1khz_db,
+        deviation_db,
+        series: vec![Series {
+            name: "magnitude".to_string(),
+            unit: "dB".to_string(),
+            points,
+        }],
+        measured_at: now(),
+    }))
+}
+
+/// Sum of the bins around `bin`
+fn tone_power(spectrum: &[f64], bin: usize) -> f64 {
+    let first = bin.saturating_sub(TONE_BINS);
+    let last = (bin + TONE_BINS).min(spectrum.len() - 1);
+    spectrum[first..=last].iter().sum()
+}
+
+/// Plays a sine over the loopback and compares the fundamental with everything else that came
+/// back between 20 Hz and 20 kHz.
+pub fn thd_n(params: &ThdNParams) -> Result<Measurement, String> {
+    let _claim = loopback::claim()?;
+
+    let sine = Signal::Sine { frequency: params.frequency };
+    sine.validate()?;
+    let level_db = params.level_db;
+    let build = Box::new(move |rate: u32| render(sine, level_db, SINE_SECONDS, rate));
+
+    let run = loopback::play_and_capture(&params.output, &params.input, build, SINE_SECONDS, LOOPBACK_TAIL_SECONDS)?;
+    let length = run.signal.len();
+
+    // The middle of the tone, clear of the round trip at the start and the end
+    let size = 1usize << (usize::BITS - 1 - (length / 2).max(1).leading_zeros());
+    let steady = run.captured.get(length / 4..length / 4 + size).ok_or("Too little was captured")?;
+    check_level(steady, &params.input)?;
+
+    let power = spectrum::power_spectrum(steady, Window::BlackmanHarris);
+    let bin_hz = run.rate as f64 / size as f64;
+    let high_hz = AUDIO_HIGH_HZ.min(run.rate as f64 / 2.0);
+    if params.frequency >= high_hz {
+        return Err(format!("{} Hz is above what can be measured at {} Hz", params.frequency, run.rate));
+    }
+
+    let fundamental_bin = (params.frequency / bin_hz).round() as usize;
+    let fundamental = tone_power(&power, fundamental_bin);
+    let first = (AUDIO_LOW_HZ / bin_hz).ceil() as usize;
+    let last = ((high_hz / bin_hz).floor() as usize).min(power.len() - 1);
+    let total: f64 = power[first..=last].iter().sum();
+    let residual = (total - fundamental).max(1e-20);
+
+    let harmonics: Vec<(u32, f64)> = (2..=MAX_HARMONIC)
+        .map(|order| (order, fundamental_bin * order as usize))
+        .filter(|(_, bin)| (*bin as f64) * bin_hz < high_hz)
+        .map(|(order, bin)| (order, tone_power(&power, bin)))
+        .collect();
+    let harmonic_power: f64 = harmonics.iter().map(|(_, power)| power).sum();
+
+    let ratio = (residual / fundamental).sqrt();
+    Ok(Measurement::ThdN(ThdN {
+        output: params.output.clone(),
+        input: params.input.clone(),
+        rate: run.rate,
+        frequency: params.frequency,
+        level_db: params.level_db,
+        fundamental_dbfs: power_to_dbfs(fundamental),
+        thd_n_percent: ratio * 100.0,
+        thd_n_db: 20.0 * ratio.log10(),
+        thd_percent: (harmonic_power / fundamental).sqrt() * 100.0,
+        harmonics: harmonics
+            .iter()
+            .map(|(order, power)| Harmonic {
+                order: *order,
+                frequency: params.frequency * *order as f64,
+                level_dbc: to_db(power / fundamental),
+            })
+            .collect(),
+        series: vec![spectrum_series("spectrum", &power, bin_hz, SPECTRUM_FRACTION, BandMode::Max, high_hz)],
+        measured_at: now(),
+    }))
+}
+
+#[derive(Default)]
+struct NoiseCapture {
+    rate: u32,
+    skip: usize,
+    samples: Vec<Vec<f32>>,
+    error: Option<String>,
+}
+
+/// Records the inputs from the input tap for `seconds` after letting them settle.
+fn capture_inputs(inputs: &[String], seconds: f64) -> Result<(u32, Vec<Vec<f32>>), String> {
+    let mut channels = Vec::new();
+    for input in inputs {
+        channels.push(audio::capture_target(input)?.1);
+    }
+    if channels.is_empty() {
+        return Err("Choose at least one input".to_string());
+    }
+
+    let capture = Arc::new(Mutex::new(NoiseCapture {
+        samples: vec![Vec::new(); channels.len()],
+        ..Default::default()
+    }));
+    let shared = capture.clone();
+    let on_samples = Box::new(move |samples: &[f32], info: BlockInfo| {
+        let Ok(mut capture) = shared.lock() else {
+            return;
+        };
+        if let Some(channel) = channels.iter().find(|c| **c >= info.channels) {
+            capture.error = Some(format!("The capture has only {} channels, not {}", info.channels, channel + 1));
+            return;
+        }
+        if capture.rate == 0 {
+            capture.rate = info.rate;
+            capture.skip = (NOISE_SETTLE_SECONDS * info.rate as f64) as usize;
+        }
+
+        for frame in samples.chunks_exact(info.channels) {
+            if capture.skip > 0 {
+                capture.skip -= 1;
+                continue;
+            }
+            for (samples, channel) in capture.samples.iter_mut().zip(&channels) {
+                samples.push(frame[*channel]);
+            }
+        }
+    });
+
+    let tap = tap::subscribe(TapSource::Inputs, on_samples)?;
+    thread::sleep(Duration::from_secs_f64(seconds + NOISE_SETTLE_SECONDS));
+    drop(tap);
+
+    let mut capture = capture.lock().map_err(|e| e.to_string())?;
+    if let Some(e) = capture.error.take() {
+        return Err(e);
+    }
+    if capture.samples.first().map(|s| s.len()).unwrap_or(0) < NOISE_FFT_SIZE {
+        return Err("Too little audio was captured, check that the Babyface is running in PipeWire".to_string());
+    }
+    Ok((capture.rate, std::mem::take(&mut capture.samples)))
+}
+
+fn analyse_noise(input: &str, gain_db: Option<f64>, samples: &[f32], rate: u32) -> Result<(InputNoise, Series), String> {
+    let power = spectrum::welch(samples, NOISE_FFT_SIZE, Window::Hann).ok_or("Too little audio was captured")?;
+    let bin_hz = rate as f64 / NOISE_FFT_SIZE as f64;
+    let high_hz = AUDIO_HIGH_HZ.min(rate as f64 / 2.0);
+
+    let (mut unweighted, mut a_weighted) = (0.0, 0.0);
+    for (bin, power) in power.iter().enumerate() {
+        let frequency = bin as f64 * bin_hz;
+        if (AUDIO_LOW_HZ..=high_hz).contains(&frequency) {
+            unweighted += power;
+            a_weighted += power * weighting::a_weighting(frequency);
+        }
+    }
+
+    let noise = InputNoise {
+        input: input.to_string(),
+        gain_db,
+        rms_dbfs: power_to_dbfs(unweighted),
+        a_weighted_dbfs: power_to_dbfs(a_weighted),
+        peak_dbfs: peak_dbfs(samples),
+    };
+    Ok((noise, spectrum_series(input, &power, bin_hz, 3, BandMode::Sum, high_hz)))
+}
+
+/// Sets a gain the way the UI does, a linked partner follows, then waits for the ramp
+fn set_gain(card_index: &str, control: &str, raw: i32) -> Result<(), String> {
+    journal::record(Origin::Ui, card_index, &stereo_link::affected_controls(control), || {
+        stereo_link::set_input_gain(card_index, control, raw)
+    })?;
+    thread::sleep(ramp::settings().control_duration());
+    Ok(())
+}
+
+/// Records the inputs with nothing playing into them and reports their noise. With `gain_db`
+/// the inputs are measured at that gain and put back to their own gain afterwards.
+pub fn noise_floor(card_index: &str, params: &NoiseFloorParams) -> Result<Measurement, String> {
+    let _claim = loopback::claim()?;
+    let seconds = params.seconds.clamp(1.0, MAX_NOISE_SECONDS);
+
+    // Gain control and raw value to go back to, per input
+    let mut previous: Vec<Option<(String, i32)>> = Vec::new();
+    for input in &params.inputs {
+        let control = format!("{} Gain", input);
+        let raw = input_gain::get_input_gain(card_index, &control).ok();
+        if raw.is_none() && params.gain_db.is_some() {
+            return Err(format!("{} has no gain control to set", input));
+        }
+        previous.push(raw.map(|raw| (control, raw)));
+    }
+
+    let mut result = Ok(());
+    if let Some(gain_db) = params.gain_db {
+        for (control, _) in previous.iter().flatten() {
+            let range = db_scale::get_range(control);
+            result = result.and_then(|_| set_gain(card_index, control, db_scale::db_to_raw(control, gain_db.clamp(range.min_db, range.max_db))));
+        }
+    }
+
+    let gains: Vec<Option<f64>> = previous
+        .iter()
+        .map(|gain| gain.as_ref().and_then(|(control, _)| input_gain::get_input_gain_db(card_index, control).ok().map(|value| value.db)))
+        .collect();
+    let captured = result.and_then(|_| capture_inputs(&params.inputs, seconds));
+
+    if params.gain_db.is_some() {
+        for (control, raw) in previous.iter().flatten() {
+            if let Err(e) = set_gain(card_index, control, *raw) {
+                eprintln!("Could not put {} back: {}", control, e);
+            }
+        }
+    }
+
+    let (rate, captured) = captured?;
+    let mut inputs = Vec::new();
+    let mut series = Vec::new();
+    for ((input, gain_db), samples) in params.inputs.iter().zip(gains).zip(&captured) {
+        let (noise, spectrum) = analyse_noise(input, gain_db, samples, rate)?;
+        inputs.push(noise);
+        series.push(spectrum);
+    }
+
+    Ok(Measurement::NoiseFloor(NoiseFloor {
+        rate,
+        seconds,
+        inputs,
+        series,
+        measured_at: now(),
+    }))
+}
@@ -0,0 +1,46 @@
+use crate::AppState;
+use crate::history::journal::{self, Origin, Setting};
+use tauri::{AppHandle, State};
+use super::section::{self, MonitorConfig, MonitorState, Speaker};
+
+/// Runs a change to the section through the journal and tells clients about the new state.
+fn change(app_handle: &AppHandle, card_number: &str, apply: impl FnOnce() -> Result<MonitorState, String>) -> Result<MonitorState, String> {
+    let monitor_state = journal::record_settings(app_handle, Origin::Ui, card_number, &[Setting::Monitor], &[], apply)?;
+    section::emit_state(app_handle);
+    Ok(monitor_state)
+}
+
+#[tauri::command]
+pub fn get_monitor_state() -> MonitorState {
+    section::get_state()
+}
+
+#[tauri::command]
+pub fn set_monitor_dim(app_handle: AppHandle, state: State<AppState>, enabled: bool) -> Result<MonitorState, String> {
+    let card_number = &state.alsa_card_number;
+    change(&app_handle, card_number, || section::set_dim(card_number, enabled))
+}
+
+#[tauri::command]
+pub fn set_monitor_mute(app_handle: AppHandle, state: State<AppState>, enabled: bool) -> Result<MonitorState, String> {
+    let card_number = &state.alsa_card_number;
+    change(&app_handle, card_number, || section::set_mute(card_number, enabled))
+}
+
+#[tauri::command]
+pub fn set_monitor_mono(app_handle: AppHandle, state: State<AppState>, enabled: bool) -> Result<MonitorState, String> {
+    let card_number = &state.alsa_card_number;
+    change(&app_handle, card_number, || section::set_mono(card_number, enabled))
+}
+
+#[tauri::command]
+pub fn set_monitor_speaker(app_handle: AppHandle, state: State<AppState>, speaker: Speaker) -> Result<MonitorState, String> {
+    let card_number = &state.alsa_card_number;
+    change(&app_handle, card_number, || section::set_speaker(card_number, speaker))
+}
+
+#[tauri::command]
+pub fn set_monitor_config(app_handle: AppHandle, state: State<AppState>, config: MonitorConfig) -> Result<MonitorState, String> {
+    let card_number = &state.alsa_card_number;
+    change(&app_handle, card_number, || section::set_config(&app_handle, card_number, config))
+}
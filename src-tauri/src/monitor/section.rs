@@ -0,0 +1,287 @@
+use crate::alsa::{crosspoint, db_scale, general, hold, ramp, virtual_volume};
+use crate::alsa::general::ControlValue;
+use crate::alsa::volume::{self, OutputPair};
+use crate::storage::config::ConfigStorage;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Speaker {
+    A,
+    B,
+    Both,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MonitorConfig {
+    pub dim_db: f64,
+    pub speaker_a: OutputPair,
+    pub speaker_b: OutputPair,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            dim_db: 20.0,
+            speaker_a: OutputPair::Main,
+            speaker_b: OutputPair::Headphones,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MonitorState {
+    pub dim: bool,
+    pub mute: bool,
+    pub mono: bool,
+    pub speaker: Speaker,
+    pub config: MonitorConfig,
+}
+
+struct MonitorSection {
+    state: MonitorState,
+    // Original values of every control the section is currently overriding
+    held: HashMap<String, i32>,
+}
+
+static SECTION: Lazy<Mutex<MonitorSection>> = Lazy::new(|| {
+    Mutex::new(MonitorSection {
+        state: MonitorState {
+            dim: false,
+            mute: false,
+            mono: false,
+            speaker: Speaker::Both,
+            config: MonitorConfig::default(),
+        },
+        held: HashMap::new(),
+    })
+});
+
+pub fn get_state() -> MonitorState {
+    SECTION.lock().map(|section| section.state.clone()).unwrap_or_else(|e| e.into_inner().state.clone())
+}
+
+pub fn emit_state(app: &AppHandle) {
+    let _ = app.emit("monitor-changed", get_state());
+}
+
+pub fn load_config(app: &AppHandle) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    let mut section = SECTION.lock().map_err(|e| e.to_string())?;
+    section.state.config = config.settings.monitor;
+    Ok(())
+}
+
+pub fn set_config(app: &AppHandle, card_index: &str, monitor_config: MonitorConfig) -> Result<MonitorState, String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    storage.update(|config| config.settings.monitor = monitor_config.clone()).map_err(|e| e.to_string())?;
+
+    update(card_index, |state| state.config = monitor_config)
+}
+
+/// Puts the whole section back to a recorded state, e.g. on undo.
+pub fn set_state(app: &AppHandle, card_index: &str, monitor_state: MonitorState) -> Result<MonitorState, String> {
+    set_config(app, card_index, monitor_state.config)?;
+    update(card_index, |state| {
+        state.dim = monitor_state.dim;
+        state.mute = monitor_state.mute;
+        state.mono = monitor_state.mono;
+        state.speaker = monitor_state.speaker;
+    })
+}
+
+pub fn set_dim(card_index: &str, enabled: bool) -> Result<MonitorState, String> {
+    update(card_index, |state| state.dim = enabled)
+}
+
+pub fn set_mute(card_index: &str, enabled: bool) -> Result<MonitorState, String> {
+    update(card_index, |state| state.mute = enabled)
+}
+
+pub fn set_mono(card_index: &str, enabled: bool) -> Result<MonitorState, String> {
+    update(card_index, |state| state.mono = enabled)
+}
+
+pub fn set_speaker(card_index: &str, speaker: Speaker) -> Result<MonitorState, String> {
+    update(card_index, |state| state.speaker = speaker)
+}
+
+pub fn toggle_dim(card_index: &str) -> Result<MonitorState, String> {
+    update(card_index, |state| state.dim = !state.dim)
+}
+
+pub fn toggle_mute(card_index: &str) -> Result<MonitorState, String> {
+    update(card_index, |state| state.mute = !state.mute)
+}
+
+pub fn toggle_mono(card_index: &str) -> Result<MonitorState, String> {
+    update(card_index, |state| state.mono = !state.mono)
+}
+
+/// Flips between speaker A and B. From "both" it goes to A.
+pub fn toggle_speaker(card_index: &str) -> Result<MonitorState, String> {
+    update(card_index, |state| {
+        state.speaker = match state.speaker {
+            Speaker::A => Speaker::B,
+            Speaker::B | Speaker::Both => Speaker::A,
+        }
+    })
+}
+
+/// Disengages everything and puts every held control back, e.g. before the app exits.
+pub fn release_all(card_index: &str) -> Result<MonitorState, String> {
+    update(card_index, |state| {
+        state.dim = false;
+        state.mute = false;
+        state.mono = false;
+        state.speaker = Speaker::Both;
+    })
+}
+
+/// Lets volume writes see the controls the section holds.
+pub fn register_hold() {
+    hold::register(hold::HoldHook {
+        held_value,
+        update_held_value,
+    });
+}
+
+/// The value a control returns to once released, if the section is holding it.
+pub fn held_value(control_name: &str) -> Option<i32> {
+    SECTION.lock().ok()?.held.get(control_name).copied()
+}
+
+/// Routes a new value for a held control into the section instead of the device.
+/// Returns false if the control isn't held and should be written as usual.
+pub fn update_held_value(card_index: &str, control_name: &str, value: i32) -> Result<bool, String> {
+    let mut section = SECTION.lock().map_err(|e| e.to_string())?;
+
+    match section.held.get_mut(control_name) {
+        Some(held) => *held = value,
+        None => return Ok(false),
+    }
+
+    apply(card_index, &mut section)?;
+    Ok(true)
+}
+
+fn update<F: FnOnce(&mut MonitorState)>(card_index: &str, change: F) -> Result<MonitorState, String> {
+    let mut section = SECTION.lock().map_err(|e| e.to_string())?;
+    change(&mut section.state);
+    apply(card_index, &mut section)?;
+    Ok(section.state.clone())
+}
+
+fn volumes(values: BTreeMap<String, ControlValue>) -> HashMap<String, i32> {
+    values
+        .into_iter()
+        .filter_map(|(name, value)| match value {
+            ControlValue::Volume(volume) => Some((name, volume)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn apply(card_index: &str, section: &mut MonitorSection) -> Result<(), String> {
+    // The section works with the values clients see, which on kernels without output volume
+    // controls include the virtual Main-Out and the own levels of its crosspoints. Ramps start
+    // from what the device has.
+    let values = general::get_control_values(card_index)?;
+    let device = volumes(values.clone());
+    let mut logical = values;
+    virtual_volume::logical_values(&mut logical);
+    let current = volumes(logical);
+    let crosspoints: Vec<crosspoint::Crosspoint> = current.keys().filter_map(|name| crosspoint::parse_crosspoint(name)).collect();
+
+    let state = &section.state;
+    let original = |name: &str| section.held.get(name).or_else(|| current.get(name)).copied();
+
+    let mut pairs = vec![state.config.speaker_a];
+    if state.config.speaker_b != state.config.speaker_a {
+        pairs.push(state.config.speaker_b);
+    }
+
+    // Control name -> (original, target) for everything the current state overrides
+    let mut targets: BTreeMap<String, (i32, i32)> = BTreeMap::new();
+
+    for pair in pairs {
+        let silenced = match state.speaker {
+            Speaker::A => pair != state.config.speaker_a,
+            Speaker::B => pair != state.config.speaker_b,
+            Speaker::Both => false,
+        };
+
+        if silenced || state.mute || state.dim {
+            let (left, right) = pair.control_names();
+            for name in [left, right] {
+                // Missing when the output has neither a volume control nor a virtual volume
+                let Some(value) = original(name) else {
+                    continue;
+                };
+                let target = if silenced || state.mute {
+                    0
+                } else {
+                    db_scale::db_to_raw(name, db_scale::raw_to_db(name, value) - state.config.dim_db)
+                };
+                targets.insert(name.to_string(), (value, target));
+            }
+        }
+
+        if state.mono {
+            let (left_route, right_route) = pair.routes();
+            for (left, right) in crosspoint::get_stereo_crosspoints(&crosspoints, left_route, right_route) {
+                if let (Some(left_value), Some(right_value)) = (original(&left), original(&right)) {
+                    // Each source feeds both sides at the average level, summing the pair to mono
+                    let summed = (left_value + right_value) / 2;
+                    targets.insert(left, (left_value, summed));
+                    targets.insert(right, (right_value, summed));
+                }
+            }
+        }
+    }
+
+    let released: Vec<(String, i32)> = section
+        .held
+        .iter()
+        .filter(|(name, _)| !targets.contains_key(*name))
+        .map(|(name, value)| (name.clone(), *value))
+        .collect();
+
+    // Virtual outputs go first, the crosspoints the section writes are offset by their new volume
+    let mut writes: Vec<(String, i32)> = released.iter().cloned().chain(targets.iter().map(|(name, (_, target))| (name.clone(), *target))).collect();
+    writes.sort_by_key(|(name, _)| !virtual_volume::is_output(name));
+    let written: HashMap<String, i32> = writes.iter().cloned().collect();
+
+    let ramp_settings = ramp::settings();
+    for (name, target) in &writes {
+        if virtual_volume::is_output(name) {
+            if current.get(name) != Some(target) {
+                virtual_volume::set_held_output(card_index, name, *target, ramp_settings.fade_duration(), &written)?;
+            }
+            continue;
+        }
+        let target = virtual_volume::device_value(name, *target);
+        match device.get(name) {
+            Some(&from) if from != target => {
+                // Unmuting or undimming an output keeps within its step limit like any other change
+                let (duration, curve) = volume::limited_ramp(name, from, target, ramp_settings.fade_duration(), ramp_settings.curve);
+                ramp::start_from(card_index, name, from, target, duration, curve)?;
+            }
+            _ => {}
+        }
+    }
+
+    for (name, _) in released {
+        section.held.remove(&name);
+    }
+    for (name, (value, _)) in targets {
+        section.held.insert(name, value);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,325 @@
+use ::pipewire as pw;
+use pw::properties::properties;
+use pw::spa;
+use spa::param::audio::{AudioFormat, AudioInfoRaw};
+use spa::param::format::{MediaSubtype, MediaType};
+use spa::param::format_utils;
+use spa::pod::Pod;
+use crate::alsa::crosspoint::OUTPUT_ROUTES;
+use super::general::{self, AudioNode, CARD_NAME};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+
+/// Capture channels with the Pro Audio profile, in the order the Babyface sends them
+pub const CAPTURE_CHANNELS: [&str; 12] = [
+    "AN1", "AN2", "IN3", "IN4", "AS1", "AS2", "ADAT3", "ADAT4", "ADAT5", "ADAT6", "ADAT7", "ADAT8",
+];
+
+/// Channel index of an input in a capture of the Babyface source, from "Mic-AN1" or "AN1"
+pub fn capture_channel(input: &str) -> Option<usize> {
+    let channel = input.rsplit('-').next()?;
+    CAPTURE_CHANNELS.iter().position(|c| *c == channel)
+}
+
+/// Channel index of an output in a playback to the Babyface sink, from "AN1" or "PH3"
+pub fn playback_channel(output: &str) -> Option<usize> {
+    OUTPUT_ROUTES.iter().position(|route| *route == output)
+}
+
+/// The Babyface source and the channel the input arrives on
+pub fn capture_target(input: &str) -> Result<(AudioNode, usize), String> {
+    let channel = capture_channel(input).ok_or_else(|| format!("{} is not a Babyface input", input))?;
+    let source = general::find_audio_node("sources", CARD_NAME)?;
+    if channel >= source.channels as usize {
+        return Err(format!(
+            "The Babyface source has {} channels, switch the card to the Pro Audio profile to use {}",
+            source.channels, input
+        ));
+    }
+    Ok((source, channel))
+}
+
+/// The Babyface sink and the channel that plays on the output
+pub fn playback_target(output: &str) -> Result<(AudioNode, usize), String> {
+    let channel = playback_channel(output).ok_or_else(|| format!("{} is not a Babyface output", output))?;
+    let sink = general::find_audio_node("sinks", CARD_NAME)?;
+    if channel >= sink.channels as usize {
+        return Err(format!(
+            "The Babyface sink has {} channels, switch the card to the Pro Audio profile to use {}",
+            sink.channels, output
+        ));
+    }
+    Ok((sink, channel))
+}
+
+/// Where a block sits on the graph clock
+#[derive(Debug, Clone, Copy)]
+pub struct StreamClock {
+    /// Graph position of the block's first frame. Every stream in the graph sees the same
+    /// position in a cycle, so played and captured blocks can be lined up by it.
+    pub position: u64,
+    /// Frames between the stream and the device, as PipeWire reports it
+    pub delay: i64,
+}
+
+/// Rate and channel count a stream settled on, and its clock for the current block
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlockInfo {
+    pub rate: u32,
+    pub channels: usize,
+    pub clock: Option<StreamClock>,
+}
+
+/// Called with interleaved samples, on the session's thread
+pub type CaptureCallback = Box<dyn FnMut(&[f32], BlockInfo) + Send>;
+/// Fills a zeroed buffer of interleaved samples, on the session's thread
+pub type PlaybackCallback = Box<dyn FnMut(&mut [f32], BlockInfo) + Send>;
+
+pub enum StreamKind {
+    /// Records a source, or a sink's monitor ports when `monitor` is set
+    Capture { monitor: bool, on_samples: CaptureCallback },
+    /// Plays to every channel of a sink, `channels` has to match the sink's
+    Playback { channels: u32, fill: PlaybackCallback },
+}
+
+pub struct StreamSpec {
+    /// Node name or serial, see `general::find_audio_node`
+    pub target: String,
+    pub kind: StreamKind,
+}
+
+struct StreamData {
+    format: AudioInfoRaw,
+    kind: StreamKind,
+    samples: Vec<f32>,
+}
+
+/// Streams running on their own PipeWire loop and thread. They stop when this is dropped.
+pub struct AudioSession {
+    quit: pw::channel::Sender<()>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AudioSession {
+    /// Connects the streams and returns once they are set up, or with the first error.
+    pub fn start(name: &str, streams: Vec<StreamSpec>) -> Result<Self, String> {
+        let (quit, quit_receiver) = pw::channel::channel::<()>();
+        let (ready, ready_receiver) = mpsc::channel::<Result<(), String>>();
+        let name = name.to_string();
+
+        let thread = thread::Builder::new()
+            .name(format!("pw-{}", name))
+            .spawn(move || {
+                if let Err(e) = run_loop(&name, streams, quit_receiver, &ready) {
+                    let _ = ready.send(Err(e));
+                }
+            })
+            .map_err(|e| format!("Failed to start the audio thread: {}", e))?;
+
+        match ready_receiver.recv() {
+            Ok(Ok(())) => Ok(Self { quit, thread: Some(thread) }),
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                Err(e)
+            }
+            Err(_) => Err("The audio thread stopped during setup".to_string()),
+        }
+    }
+
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = self.quit.send(());
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for AudioSession {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn run_loop(name: &str, specs: Vec<StreamSpec>, quit: pw::channel::Receiver<()>, ready: &mpsc::Sender<Result<(), String>>) -> Result<(), String> {
+    pw::init();
+
+    let mainloop = pw::main_loop::MainLoop::new(None).map_err(|e| format!("Failed to create PipeWire loop: {}", e))?;
+    let context = pw::context::Context::new(&mainloop).map_err(|e| format!("Failed to create PipeWire context: {}", e))?;
+    let core = context.connect(None).map_err(|e| format!("Failed to connect to PipeWire: {}", e))?;
+
+    let mut streams = Vec::new();
+    for spec in specs {
+        streams.push(connect_stream(&core, name, spec)?);
+    }
+
+    let quit_loop = mainloop.clone();
+    let _quit = quit.attach(mainloop.loop_(), move |_| quit_loop.quit());
+
+    let _ = ready.send(Ok(()));
+    mainloop.run();
+
+    for (stream, _listener) in &streams {
+        let _ = stream.disconnect();
+    }
+    Ok(())
+}
+
+/// The stream's clock in frames at its own rate, None before the stream is running.
+fn stream_clock(stream: &pw::stream::StreamRef, rate: u32) -> Option<StreamClock> {
+    let mut time: pw::sys::pw_time = unsafe { std::mem::zeroed() };
+    // Safe as long as the size passed matches the struct, older libraries fill in less of it
+    let result = unsafe { pw::sys::pw_stream_get_time_n(stream.as_raw_ptr(), &mut time, std::mem::size_of::<pw::sys::pw_time>()) };
+    if result < 0 || time.rate.denom == 0 || rate == 0 {
+        return None;
+    }
+
+    // Ticks and delay count in units of rate.num / rate.denom seconds
+    let to_frames = |ticks: i128| ticks * time.rate.num as i128 * rate as i128 / time.rate.denom as i128;
+    Some(StreamClock {
+        position: to_frames(time.ticks as i128) as u64,
+        delay: to_frames(time.delay as i128) as i64,
+    })
+}
+
+fn format_param(info: AudioInfoRaw) -> Result<Vec<u8>, String> {
+    let object = spa::pod::Object {
+        type_: spa::utils::SpaTypes::ObjectParamFormat.as_raw(),
+        id: spa::param::ParamType::EnumFormat.as_raw(),
+        properties: info.into(),
+    };
+    spa::pod::serialize::PodSerializer::serialize(std::io::Cursor::new(Vec::new()), &spa::pod::Value::Object(object))
+        .map(|(cursor, _)| cursor.into_inner())
+        .map_err(|e| format!("Failed to build the stream format: {:?}", e))
+}
+
+fn connect_stream(core: &pw::core::Core, name: &str, spec: StreamSpec) -> Result<(pw::stream::Stream, pw::stream::StreamListener<StreamData>), String> {
+    let (category, direction) = match &spec.kind {
+        StreamKind::Capture { .. } => ("Capture", spa::utils::Direction::Input),
+        StreamKind::Playback { .. } => ("Playback", spa::utils::Direction::Output),
+    };
+
+    let mut props = properties! {
+        *pw::keys::MEDIA_TYPE => "Audio",
+        *pw::keys::MEDIA_CATEGORY => category,
+        *pw::keys::MEDIA_ROLE => "Production",
+    };
+    props.insert(*pw::keys::TARGET_OBJECT, spec.target.as_str());
+    // Channels are taken in the device's order, not remixed to a speaker layout
+    props.insert(*pw::keys::STREAM_DONT_REMIX, "true");
+
+    if let StreamKind::Capture { monitor: true, .. } = &spec.kind {
+        props.insert(*pw::keys::STREAM_CAPTURE_SINK, "true");
+    }
+
+    // Rate is left open so the stream runs at the graph's rate, and a capture takes the node's channels
+    let mut info = AudioInfoRaw::new();
+    info.set_format(AudioFormat::F32LE);
+    if let StreamKind::Playback { channels, .. } = &spec.kind {
+        info.set_channels(*channels);
+    }
+
+    let stream = pw::stream::Stream::new(core, name, props).map_err(|e| format!("Failed to create stream: {}", e))?;
+
+    let data = StreamData {
+        format: AudioInfoRaw::new(),
+        kind: spec.kind,
+        samples: Vec::new(),
+    };
+
+    let listener = stream
+        .add_local_listener_with_user_data(data)
+        .param_changed(|_, data, id, param| {
+            let Some(param) = param else {
+                return;
+            };
+            if id != spa::param::ParamType::Format.as_raw() {
+                return;
+            }
+            let Ok((media_type, media_subtype)) = format_utils::parse_format(param) else {
+                return;
+            };
+            if media_type != MediaType::Audio || media_subtype != MediaSubtype::Raw {
+                return;
+            }
+            if let Err(e) = data.format.parse(param) {
+                eprintln!("Failed to parse the stream format: {:?}", e);
+            }
+        })
+        .process(|stream, data| {
+            let Some(mut buffer) = stream.dequeue_buffer() else {
+                return;
+            };
+            let info = BlockInfo {
+                rate: data.format.rate(),
+                channels: data.format.channels() as usize,
+                clock: stream_clock(stream, data.format.rate()),
+            };
+            let requested = buffer.requested();
+            let datas = buffer.datas_mut();
+            if datas.is_empty() || info.channels == 0 {
+                return;
+            }
+
+            match &mut data.kind {
+                StreamKind::Capture { on_samples, .. } => {
+                    let offset = datas[0].chunk().offset() as usize;
+                    let size = datas[0].chunk().size() as usize;
+                    let Some(bytes) = datas[0].data() else {
+                        return;
+                    };
+                    let end = (offset + size).min(bytes.len());
+
+                    data.samples.clear();
+                    data.samples.extend(
+                        bytes[offset.min(end)..end]
+                            .chunks_exact(4)
+                            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+                    );
+                    on_samples(&data.samples, info);
+                }
+                StreamKind::Playback { fill, .. } => {
+                    let stride = 4 * info.channels;
+                    let Some(bytes) = datas[0].data() else {
+                        return;
+                    };
+                    // Only what the graph asks for, a full buffer would queue up as extra latency
+                    let frames = match requested {
+                        0 => bytes.len() / stride,
+                        requested => (requested as usize).min(bytes.len() / stride),
+                    };
+
+                    data.samples.clear();
+                    data.samples.resize(frames * info.channels, 0.0);
+                    fill(&mut data.samples, info);
+                    for (out, sample) in bytes.chunks_exact_mut(4).zip(&data.samples) {
+                        out.copy_from_slice(&sample.to_le_bytes());
+                    }
+
+                    let chunk = datas[0].chunk_mut();
+                    *chunk.offset_mut() = 0;
+                    *chunk.stride_mut() = stride as i32;
+                    *chunk.size_mut() = (frames * stride) as u32;
+                }
+            }
+        })
+        .register()
+        .map_err(|e| format!("Failed to register stream callbacks: {}", e))?;
+
+    let values = format_param(info)?;
+    let mut params = [Pod::from_bytes(&values).ok_or("Invalid stream format")?];
+
+    stream
+        .connect(
+            direction,
+            None,
+            pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+            &mut params,
+        )
+        .map_err(|e| format!("Failed to connect stream to {}: {}", spec.target, e))?;
+
+    Ok((stream, listener))
+}
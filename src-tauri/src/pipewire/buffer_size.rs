@@ -1,5 +1,7 @@
 use std::process::Command;
 
+pub const QUANTUMS: [u32; 7] = [32, 64, 128, 256, 512, 1024, 2048];
+
 pub fn get_clock_quantum() -> Result<u32, String> {
     let output = Command::new("pw-metadata")
         .args(&["-n", "settings"])
@@ -71,3 +73,16 @@ pub fn set_clock_quantum(quantum: u32) -> Result<(), String> {
         Err(stderr.to_string())
     }
 }
+
+/// Steps to the next quantum in `QUANTUMS`, wrapping around after the largest.
+pub fn cycle_clock_quantum() -> Result<u32, String> {
+    let current = get_clock_quantum()?;
+    let next = QUANTUMS
+        .iter()
+        .copied()
+        .find(|&quantum| quantum > current)
+        .unwrap_or(QUANTUMS[0]);
+
+    set_clock_quantum(next)?;
+    Ok(next)
+}
@@ -1,5 +1,9 @@
+use regex::Regex;
 use std::process::Command;
 
+/// What the Babyface's PipeWire card and nodes have in their names
+pub const CARD_NAME: &str = "RME_Babyface_Pro";
+
 // fn get_pipewire_info() -> Result<String, String> {
 //     let output = Command::new("pw-cli")
 //         .args(&["info", "all"])
@@ -25,6 +29,41 @@ pub fn get_card_id_by_name(card_name: &str) -> Result<String, String> {
     Err(format!("Could not find card with name: {}", card_name))
 }
 
+/// A PipeWire source or sink and how many channels it has
+#[derive(Debug, Clone)]
+pub struct AudioNode {
+    pub name: String,
+    pub channels: u32,
+}
+
+/// Finds the card's source or sink node, `kind` is "sources" or "sinks". Monitor sources are skipped.
+pub fn find_audio_node(kind: &str, card_name: &str) -> Result<AudioNode, String> {
+    let output = Command::new("pactl")
+        .args(&["list", kind, "short"])
+        .output()
+        .map_err(|e| format!("Failed to execute pactl: {}", e))?;
+
+    // e.g. "58  alsa_input.usb-RME_Babyface_Pro_...pro-input-0  PipeWire  s32le 12ch 48000Hz  SUSPENDED"
+    let spec_re = Regex::new(r"(\d+)ch").unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    for line in stdout.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 4 || !fields[1].contains(card_name) || fields[1].ends_with(".monitor") {
+            continue;
+        }
+        let Some(caps) = spec_re.captures(fields[3]) else {
+            continue;
+        };
+        return Ok(AudioNode {
+            name: fields[1].to_string(),
+            channels: caps[1].parse().unwrap_or(0),
+        });
+    }
+
+    Err(format!("Could not find {} for card: {}", kind, card_name))
+}
+
 // pub fn get_object_id_by_port_name(target_port_name: &str) -> Result<String, String> {
 //     let stdout = get_pipewire_info()?;
 
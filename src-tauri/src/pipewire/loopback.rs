@@ -0,0 +1,177 @@
+use super::audio::{self, AudioSession, BlockInfo, StreamKind, StreamSpec};
+use once_cell::sync::Lazy;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Silence played first so the graph has settled before the signal starts
+const WARMUP_SECONDS: f64 = 0.3;
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// On top of the signal's own length, for setup and the warmup
+const TIMEOUT_MARGIN: Duration = Duration::from_secs(5);
+
+// One measurement at a time, they share the loopback cable and the input gains
+static CLAIM: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Builds the signal to play, given the graph's rate
+pub type SignalBuilder = Box<dyn FnOnce(u32) -> Vec<f32> + Send>;
+
+/// What came back on the input, lined up so `captured[0]` is the frame captured in the
+/// graph cycle the signal started playing in
+pub struct Loopback {
+    pub signal: Vec<f32>,
+    pub captured: Vec<f32>,
+    pub rate: u32,
+    /// Frames PipeWire reports between each stream and the device
+    pub playback_delay: i64,
+    pub capture_delay: i64,
+}
+
+#[derive(Default)]
+struct Run {
+    build: Option<SignalBuilder>,
+    signal: Vec<f32>,
+    output_channel: usize,
+    input_channel: usize,
+    warmup_frames: u64,
+    silent_frames: u64,
+    /// Graph position the signal started playing at
+    start: Option<u64>,
+    played: usize,
+    captured: Vec<f32>,
+    wanted: usize,
+    tail_seconds: f64,
+    rate: u32,
+    playback_delay: i64,
+    capture_delay: i64,
+}
+
+fn play(run: &mut Run, samples: &mut [f32], info: BlockInfo) {
+    let Some(clock) = info.clock else {
+        return;
+    };
+    run.rate = info.rate;
+    run.playback_delay = clock.delay;
+
+    if let Some(build) = run.build.take() {
+        run.signal = build(info.rate);
+        run.warmup_frames = (WARMUP_SECONDS * info.rate as f64) as u64;
+        run.wanted = run.signal.len() + (run.tail_seconds * info.rate as f64) as usize;
+    }
+
+    let frames = samples.len() / info.channels;
+    if run.start.is_none() {
+        run.silent_frames += frames as u64;
+        if run.silent_frames < run.warmup_frames {
+            return;
+        }
+        run.start = Some(clock.position);
+    }
+
+    for frame in samples.chunks_exact_mut(info.channels) {
+        let Some(sample) = run.signal.get(run.played) else {
+            break;
+        };
+        frame[run.output_channel] = *sample;
+        run.played += 1;
+    }
+}
+
+fn capture(run: &mut Run, samples: &[f32], info: BlockInfo) {
+    let Some(clock) = info.clock else {
+        return;
+    };
+    run.capture_delay = clock.delay;
+    let Some(start) = run.start else {
+        return;
+    };
+
+    for (i, frame) in samples.chunks_exact(info.channels).enumerate() {
+        let position = clock.position + i as u64;
+        if position < start {
+            continue;
+        }
+        let index = (position - start) as usize;
+        if index >= run.wanted {
+            break;
+        }
+        if index >= run.captured.len() {
+            run.captured.resize(index + 1, 0.0);
+        }
+        run.captured[index] = frame[run.input_channel];
+    }
+}
+
+/// Held for as long as a measurement uses the loopback or the inputs
+pub fn claim() -> Result<MutexGuard<'static, ()>, String> {
+    CLAIM.try_lock().map_err(|_| "A measurement is already running".to_string())
+}
+
+/// Plays a signal on one Babyface output and records one input, for measurements over a
+/// loopback cable. Recording goes on for `tail_seconds` after the signal ends so its delayed
+/// return is caught too. `expected_seconds` is roughly how long the signal lasts.
+pub fn play_and_capture(output: &str, input: &str, build: SignalBuilder, expected_seconds: f64, tail_seconds: f64) -> Result<Loopback, String> {
+    let (sink, output_channel) = audio::playback_target(output)?;
+    let (source, input_channel) = audio::capture_target(input)?;
+
+    let run = Arc::new(Mutex::new(Run {
+        build: Some(build),
+        output_channel,
+        input_channel,
+        tail_seconds,
+        ..Default::default()
+    }));
+
+    let player = run.clone();
+    let fill = Box::new(move |samples: &mut [f32], info: BlockInfo| {
+        if let Ok(mut run) = player.lock() {
+            play(&mut run, samples, info);
+        }
+    });
+    let recorder = run.clone();
+    let on_samples = Box::new(move |samples: &[f32], info: BlockInfo| {
+        if let Ok(mut run) = recorder.lock() {
+            capture(&mut run, samples, info);
+        }
+    });
+
+    let session = AudioSession::start(
+        "loopback",
+        vec![
+            StreamSpec {
+                target: sink.name,
+                kind: StreamKind::Playback { channels: sink.channels, fill },
+            },
+            StreamSpec {
+                target: source.name,
+                kind: StreamKind::Capture { monitor: false, on_samples },
+            },
+        ],
+    )?;
+
+    let deadline = Instant::now() + Duration::from_secs_f64(expected_seconds + tail_seconds) + TIMEOUT_MARGIN;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let done = run.lock().map(|run| run.wanted > 0 && run.captured.len() >= run.wanted).unwrap_or(true);
+        if done || Instant::now() >= deadline {
+            break;
+        }
+    }
+    session.stop();
+
+    let mut run = run.lock().map_err(|e| e.to_string())?;
+    if run.start.is_none() {
+        return Err("The signal never started, PipeWire didn't report the stream timing or the sink isn't running".to_string());
+    }
+    if run.captured.len() < run.wanted {
+        return Err("The measurement timed out, check that the Babyface is running in PipeWire".to_string());
+    }
+
+    Ok(Loopback {
+        signal: std::mem::take(&mut run.signal),
+        captured: std::mem::take(&mut run.captured),
+        rate: run.rate,
+        playback_delay: run.playback_delay,
+        capture_delay: run.capture_delay,
+    })
+}
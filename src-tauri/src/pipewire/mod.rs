@@ -1,4 +1,7 @@
 pub mod controller;
+pub mod audio;
 pub mod buffer_size;
 pub mod general;
-pub mod profile;
\ No newline at end of file
+pub mod loopback;
+pub mod profile;
+pub mod tap;
\ No newline at end of file
@@ -0,0 +1,115 @@
+use super::audio::{self, AudioSession, BlockInfo, CaptureCallback, StreamKind, StreamSpec};
+use super::general::{self, CARD_NAME};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TapSource {
+    /// The Babyface source
+    Inputs,
+    /// The monitor ports of the Babyface sink, what is played to the outputs
+    Outputs,
+}
+
+/// A Babyface channel to analyse
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TapChannel {
+    pub source: TapSource,
+    /// "Mic-AN1" or "AN1" for an input, "AN1" or "PH3" for an output
+    pub name: String,
+}
+
+impl TapChannel {
+    /// Channel index in the tap's blocks, checking the node has it
+    pub fn resolve(&self) -> Result<usize, String> {
+        let (_, index) = match self.source {
+            TapSource::Inputs => audio::capture_target(&self.name)?,
+            TapSource::Outputs => audio::playback_target(&self.name)?,
+        };
+        Ok(index)
+    }
+}
+
+type Consumers = Arc<Mutex<HashMap<u64, CaptureCallback>>>;
+
+struct Shared {
+    session: AudioSession,
+    consumers: Consumers,
+}
+
+#[derive(Default)]
+struct Taps {
+    running: HashMap<TapSource, Shared>,
+    next_id: u64,
+}
+
+static TAPS: Lazy<Mutex<Taps>> = Lazy::new(|| Mutex::new(Taps::default()));
+
+/// A subscription to a tap, dropping it unsubscribes. Don't drop it from inside the callback.
+pub struct Tap {
+    id: u64,
+    source: TapSource,
+}
+
+/// Calls `on_samples` with every block captured from the source. All subscribers of a source
+/// share one stream, opened by the first and closed when the last one leaves.
+pub fn subscribe(source: TapSource, on_samples: CaptureCallback) -> Result<Tap, String> {
+    let mut taps = TAPS.lock().map_err(|e| e.to_string())?;
+    taps.next_id += 1;
+    let id = taps.next_id;
+
+    if let Some(shared) = taps.running.get(&source) {
+        shared.consumers.lock().map_err(|e| e.to_string())?.insert(id, on_samples);
+        return Ok(Tap { id, source });
+    }
+
+    let (node, monitor) = match source {
+        TapSource::Inputs => (general::find_audio_node("sources", CARD_NAME)?, false),
+        TapSource::Outputs => (general::find_audio_node("sinks", CARD_NAME)?, true),
+    };
+
+    let consumers: Consumers = Arc::new(Mutex::new(HashMap::from([(id, on_samples)])));
+    let shared = consumers.clone();
+    let on_block = Box::new(move |samples: &[f32], info: BlockInfo| {
+        if let Ok(mut consumers) = shared.lock() {
+            for on_samples in consumers.values_mut() {
+                on_samples(samples, info);
+            }
+        }
+    });
+
+    let session = AudioSession::start(
+        "tap",
+        vec![StreamSpec {
+            target: node.name,
+            kind: StreamKind::Capture { monitor, on_samples: on_block },
+        }],
+    )?;
+    taps.running.insert(source, Shared { session, consumers });
+    Ok(Tap { id, source })
+}
+
+impl Drop for Tap {
+    fn drop(&mut self) {
+        let idle = {
+            let Ok(mut taps) = TAPS.lock() else {
+                return;
+            };
+            let empty = match taps.running.get(&self.source).map(|shared| shared.consumers.lock()) {
+                Some(Ok(mut consumers)) => {
+                    consumers.remove(&self.id);
+                    consumers.is_empty()
+                }
+                _ => false,
+            };
+            if empty { taps.running.remove(&self.source) } else { None }
+        };
+        // Stopped outside the lock, stopping waits for the audio thread
+        if let Some(shared) = idle {
+            shared.session.stop();
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::AppState;
+use tauri::{AppHandle, Emitter, State};
+use super::snapshot;
+
+#[tauri::command]
+pub fn list_presets(app_handle: AppHandle) -> Result<Vec<String>, String> {
+    snapshot::list_presets(&app_handle)
+}
+
+#[tauri::command]
+pub fn save_preset(app_handle: AppHandle, state: State<AppState>, name: String) -> Result<usize, String> {
+    let card_number = &state.alsa_card_number;
+    let index = snapshot::save_preset(&app_handle, card_number, &name)?;
+    crate::tray::menu::refresh(&app_handle);
+    Ok(index)
+}
+
+#[tauri::command]
+pub fn recall_preset(app_handle: AppHandle, state: State<AppState>, index: usize) -> Result<String, String> {
+    let card_number = &state.alsa_card_number;
+    let name = snapshot::recall_preset(&app_handle, card_number, index)?;
+    let _ = app_handle.emit("controls-changed", ());
+    Ok(name)
+}
+
+#[tauri::command]
+pub fn delete_preset(app_handle: AppHandle, index: usize) -> Result<(), String> {
+    snapshot::delete_preset(&app_handle, index)?;
+    crate::tray::menu::refresh(&app_handle);
+    Ok(())
+}
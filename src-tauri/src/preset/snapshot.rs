@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+use tauri::AppHandle;
+use crate::alsa::general::{self, ControlValue};
+use crate::alsa::{phantom, switches, virtual_volume, volume};
+use crate::history::journal::{self, Origin};
+use crate::storage::config::{ConfigStorage, Preset};
+
+/// Writes stored values through the paths a client would use. A 48V switch goes through the
+/// phantom interlocks, so one that needs confirming (a ribbon mic) is refused and reported.
+pub fn apply_values(app: &AppHandle, card_index: &str, values: &BTreeMap<String, ControlValue>) -> Result<(), String> {
+    let mut current = general::get_control_values(card_index)?;
+    virtual_volume::logical_values(&mut current);
+    let mut errors = Vec::new();
+
+    for (control_name, value) in values {
+        // Only touch controls that actually differ, every amixer call is audible on some controls
+        if current.get(control_name) == Some(value) {
+            continue;
+        }
+        // Volumes go through the regular volume path so safety limits and the monitor section apply
+        let result = match value {
+            ControlValue::Volume(level) => volume::fade_volume(card_index, control_name, *level).map(|_| ()),
+            ControlValue::Switch(on) if control_name.ends_with(" 48V") => phantom::set_phantom_power(app, card_index, control_name, *on, false),
+            ControlValue::Switch(on) if control_name.ends_with(" PAD") => switches::set_pad_state(card_index, control_name, *on),
+            _ => general::set_control_value(card_index, control_name, value),
+        };
+        if let Err(e) = result {
+            errors.push(format!("{}: {}", control_name, e.trim()));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Failed to apply some controls: {}", errors.join(", ")))
+    }
+}
+
+pub fn save_preset(app_handle: &AppHandle, card_index: &str, name: &str) -> Result<usize, String> {
+    let storage = ConfigStorage::new(app_handle).map_err(|e| e.to_string())?;
+
+    let mut values = general::get_control_values(card_index)?;
+    virtual_volume::logical_values(&mut values);
+    let preset = Preset {
+        name: name.to_string(),
+        values,
+    };
+
+    // Saving under an existing name overwrites that preset and keeps its slot
+    storage
+        .update(|config| match config.presets.iter().position(|p| p.name == name) {
+            Some(index) => {
+                config.presets[index] = preset;
+                index
+            }
+            None => {
+                config.presets.push(preset);
+                config.presets.len() - 1
+            }
+        })
+        .map_err(|e| e.to_string())
+}
+
+pub fn recall_preset(app_handle: &AppHandle, card_index: &str, index: usize) -> Result<String, String> {
+    let storage = ConfigStorage::new(app_handle).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+
+    let preset = config
+        .presets
+        .get(index)
+        .ok_or_else(|| format!("No preset in slot {}", index + 1))?;
+
+    let controls: Vec<String> = preset.values.keys().cloned().collect();
+    journal::record(Origin::Preset, card_index, &controls, || apply_values(app_handle, card_index, &preset.values))?;
+    println!("Recalled preset {} ({})", index + 1, preset.name);
+    Ok(preset.name.clone())
+}
+
+pub fn delete_preset(app_handle: &AppHandle, index: usize) -> Result<(), String> {
+    let storage = ConfigStorage::new(app_handle).map_err(|e| e.to_string())?;
+
+    storage
+        .update(|config| {
+            if index >= config.presets.len() {
+                return Err(format!("No preset in slot {}", index + 1));
+            }
+            config.presets.remove(index);
+            Ok(())
+        })
+        .map_err(|e| e.to_string())?
+}
+
+pub fn list_presets(app_handle: &AppHandle) -> Result<Vec<String>, String> {
+    let storage = ConfigStorage::new(app_handle).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    Ok(config.presets.iter().map(|p| p.name.clone()).collect())
+}
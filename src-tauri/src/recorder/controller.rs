@@ -0,0 +1,40 @@
+use tauri::AppHandle;
+use super::session::{self, RecorderSettings, RecorderStatus, RecordingSummary};
+
+#[tauri::command]
+pub fn start_recording(app_handle: AppHandle, inputs: Vec<String>) -> Result<RecorderStatus, String> {
+    let status = session::start_recording(&app_handle, inputs)?;
+    session::emit_status(&app_handle);
+    Ok(status)
+}
+
+/// Runs off the main thread, the last blocks are written before it returns
+#[tauri::command(async)]
+pub fn stop_recording(app_handle: AppHandle) -> Result<RecordingSummary, String> {
+    let summary = session::stop_recording();
+    session::emit_status(&app_handle);
+    summary
+}
+
+#[tauri::command]
+pub fn get_recorder_status() -> RecorderStatus {
+    session::get_status()
+}
+
+/// Runs off the main thread, a long buffer takes a moment to write
+#[tauri::command(async)]
+pub fn save_rolling_buffer(app_handle: AppHandle, seconds: Option<f64>) -> Result<RecordingSummary, String> {
+    session::save_rolling(&app_handle, seconds)
+}
+
+#[tauri::command]
+pub fn get_recorder_settings(app_handle: AppHandle) -> Result<RecorderSettings, String> {
+    session::get_settings(&app_handle)
+}
+
+#[tauri::command]
+pub fn set_recorder_settings(app_handle: AppHandle, settings: RecorderSettings) -> Result<(), String> {
+    let result = session::set_settings(&app_handle, settings);
+    session::emit_status(&app_handle);
+    result
+}
@@ -0,0 +1,193 @@
+use super::flac::FlacWriter;
+use crate::alsa::stereo_link;
+use crate::pipewire::audio;
+use crate::storage::config::InputChannelConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingFormat {
+    #[default]
+    Wav,
+    Flac,
+}
+
+impl RecordingFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            RecordingFormat::Wav => "wav",
+            RecordingFormat::Flac => "flac",
+        }
+    }
+}
+
+/// One file: a single input, or both sides of a linked pair as a stereo file
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub name: String,
+    /// Channels of the Babyface source, in file order
+    pub channels: Vec<usize>,
+}
+
+fn display_name(channels: &HashMap<String, InputChannelConfig>, input: &str, stereo: bool) -> String {
+    let config = channels.get(input);
+    let name = config.map(|c| if stereo { &c.display_name_stereo } else { &c.display_name }).map(|n| n.trim()).unwrap_or_default();
+    let fallback = config.map(|c| c.display_name.trim()).unwrap_or_default();
+
+    let name = [name, fallback].into_iter().find(|n| !n.is_empty()).unwrap_or(input);
+    // Keep it a valid file name on every filesystem the files might be copied to
+    name.chars().map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c }).collect()
+}
+
+/// Groups the inputs into files, named from the channel configs. Linked pairs with both sides
+/// selected become one stereo file.
+pub fn plan_tracks(inputs: &[String], channels: &HashMap<String, InputChannelConfig>) -> Result<Vec<Track>, String> {
+    if inputs.is_empty() {
+        return Err("Choose at least one input to record".to_string());
+    }
+
+    let mut tracks = Vec::new();
+    let mut paired = Vec::new();
+    for (left, right) in stereo_link::linked_pairs() {
+        if inputs.iter().any(|i| i == left) && inputs.iter().any(|i| i == right) {
+            tracks.push(Track {
+                name: display_name(channels, left, true),
+                channels: vec![audio::capture_target(left)?.1, audio::capture_target(right)?.1],
+            });
+            paired.extend([left, right]);
+        }
+    }
+
+    for input in inputs.iter().filter(|input| !paired.contains(&input.as_str())) {
+        tracks.push(Track {
+            name: display_name(channels, input, false),
+            channels: vec![audio::capture_target(input)?.1],
+        });
+    }
+    Ok(tracks)
+}
+
+/// Local time for file names, e.g. "2024-05-01 14-03-22"
+pub fn timestamp() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H-%M-%S").to_string()
+}
+
+/// A sample scaled to a 16 or 24 bit integer
+pub fn quantize(sample: f32, bit_depth: u16) -> i32 {
+    let scale = ((1i64 << (bit_depth - 1)) - 1) as f32;
+    (sample.clamp(-1.0, 1.0) * scale).round() as i32
+}
+
+/// "<name> <timestamp>.<ext>", with a number added when the file already exists
+fn file_path(directory: &Path, name: &str, stamp: &str, format: RecordingFormat) -> PathBuf {
+    let mut path = directory.join(format!("{} {}.{}", name, stamp, format.extension()));
+    let mut number = 2;
+    while path.exists() {
+        path = directory.join(format!("{} {} ({}).{}", name, stamp, number, format.extension()));
+        number += 1;
+    }
+    path
+}
+
+enum AudioFile {
+    Wav(hound::WavWriter<BufWriter<File>>),
+    Flac(FlacWriter),
+}
+
+impl AudioFile {
+    fn create(path: &Path, format: RecordingFormat, channels: usize, rate: u32, bit_depth: u16) -> Result<Self, String> {
+        let error = |e: String| format!("Could not create {}: {}", path.display(), e);
+        match format {
+            RecordingFormat::Wav => {
+                let spec = hound::WavSpec {
+                    channels: channels as u16,
+                    sample_rate: rate,
+                    bits_per_sample: bit_depth,
+                    sample_format: hound::SampleFormat::Int,
+                };
+                hound::WavWriter::create(path, spec).map(AudioFile::Wav).map_err(|e| error(e.to_string()))
+            }
+            RecordingFormat::Flac => FlacWriter::create(path, channels, rate, bit_depth as u32).map(AudioFile::Flac).map_err(|e| error(e.to_string())),
+        }
+    }
+
+    fn write(&mut self, samples: &[i32]) -> Result<(), String> {
+        match self {
+            AudioFile::Wav(writer) => samples.iter().try_for_each(|s| writer.write_sample(*s)).map_err(|e| e.to_string()),
+            AudioFile::Flac(writer) => writer.write(samples).map_err(|e| e.to_string()),
+        }
+    }
+
+    fn finish(self) -> Result<(), String> {
+        match self {
+            AudioFile::Wav(writer) => writer.finalize().map_err(|e| e.to_string()),
+            AudioFile::Flac(writer) => writer.finish().map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// The open files of a recording
+pub struct TrackFiles {
+    files: Vec<(usize, AudioFile)>,
+    paths: Vec<String>,
+    bit_depth: u16,
+    buffer: Vec<i32>,
+}
+
+impl TrackFiles {
+    pub fn create(directory: &Path, tracks: &[Track], format: RecordingFormat, bit_depth: u16, rate: u32) -> Result<Self, String> {
+        fs::create_dir_all(directory).map_err(|e| format!("Could not create {}: {}", directory.display(), e))?;
+        let stamp = timestamp();
+
+        let mut files = Vec::new();
+        let mut paths = Vec::new();
+        for track in tracks {
+            let path = file_path(directory, &track.name, &stamp, format);
+            files.push((track.channels.len(), AudioFile::create(&path, format, track.channels.len(), rate, bit_depth)?));
+            paths.push(path.to_string_lossy().to_string());
+        }
+        Ok(Self {
+            files,
+            paths,
+            bit_depth,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Frames with every track's channels side by side, in track order
+    pub fn write(&mut self, samples: &[f32]) -> Result<(), String> {
+        let bit_depth = self.bit_depth;
+        self.write_with(samples, |sample| quantize(sample, bit_depth))
+    }
+
+    /// Like `write`, with the samples already scaled to the bit depth
+    pub fn write_scaled(&mut self, samples: &[i32]) -> Result<(), String> {
+        self.write_with(samples, |sample| sample)
+    }
+
+    fn write_with<T: Copy>(&mut self, samples: &[T], scale: impl Fn(T) -> i32) -> Result<(), String> {
+        let width: usize = self.files.iter().map(|(channels, _)| channels).sum();
+
+        let mut offset = 0;
+        for (channels, file) in self.files.iter_mut() {
+            self.buffer.clear();
+            for frame in samples.chunks_exact(width) {
+                self.buffer.extend(frame[offset..offset + *channels].iter().map(|s| scale(*s)));
+            }
+            file.write(&self.buffer)?;
+            offset += *channels;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<Vec<String>, String> {
+        for (_, file) in self.files {
+            file.finish()?;
+        }
+        Ok(self.paths)
+    }
+}
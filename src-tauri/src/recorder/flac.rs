@@ -0,0 +1,386 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// A FLAC encoder with the fixed predictors and Rice-coded residuals, which gets most of what
+// the reference encoder gets on live recordings without LPC analysis
+
+const BLOCK_SIZE: usize = 4096;
+const MAX_FIXED_ORDER: usize = 4;
+const MAX_PARTITION_ORDER: u32 = 8;
+// Five bit Rice parameters (RICE2), 31 is the escape code
+const MAX_RICE_PARAMETER: u32 = 30;
+const STREAMINFO_OFFSET: u64 = 4;
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    accumulator: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    /// The low `bits` bits of `value`, at most 32
+    fn write(&mut self, value: u64, bits: u32) {
+        if bits == 0 {
+            return;
+        }
+        self.accumulator = (self.accumulator << bits) | (value & ((1u64 << bits) - 1));
+        self.bits += bits;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            self.bytes.push((self.accumulator >> self.bits) as u8);
+        }
+        self.accumulator &= (1u64 << self.bits) - 1;
+    }
+
+    fn write_signed(&mut self, value: i64, bits: u32) {
+        self.write(value as u64, bits);
+    }
+
+    fn write_unary(&mut self, mut zeros: u64) {
+        while zeros >= 32 {
+            self.write(0, 32);
+            zeros -= 32;
+        }
+        self.write(1, zeros as u32 + 1);
+    }
+
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.write(0, 8 - self.bits);
+        }
+    }
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 })
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 })
+    })
+}
+
+/// Frame numbers are coded like UTF-8
+fn write_utf8(writer: &mut BitWriter, value: u64) {
+    if value < 0x80 {
+        writer.write(value, 8);
+        return;
+    }
+    let extra = match value {
+        0..=0x7FF => 1,
+        0x800..=0xFFFF => 2,
+        0x1_0000..=0x1F_FFFF => 3,
+        0x20_0000..=0x3FF_FFFF => 4,
+        _ => 5,
+    };
+    let lead_mask = (0xFF00u16 >> (extra + 1)) as u64 & 0xFF;
+    writer.write(lead_mask | (value >> (6 * extra)), 8);
+    for i in (0..extra).rev() {
+        writer.write(0x80 | ((value >> (6 * i)) & 0x3F), 8);
+    }
+}
+
+fn fixed_residual(samples: &[i64], order: usize) -> Vec<i64> {
+    (order..samples.len())
+        .map(|i| {
+            let x = |back: usize| samples[i - back];
+            match order {
+                0 => x(0),
+                1 => x(0) - x(1),
+                2 => x(0) - 2 * x(1) + x(2),
+                3 => x(0) - 3 * x(1) + 3 * x(2) - x(3),
+                _ => x(0) - 4 * x(1) + 6 * x(2) - 4 * x(3) + x(4),
+            }
+        })
+        .collect()
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Best Rice parameter for a partition and the bits it takes
+fn rice_parameter(values: &[u64]) -> (u32, u64) {
+    let sum: u64 = values.iter().sum();
+    let mean = sum / values.len().max(1) as u64;
+    let guess = (64 - mean.leading_zeros()).min(MAX_RICE_PARAMETER);
+    let cost = |k: u32| values.len() as u64 * (k as u64 + 1) + values.iter().map(|v| v >> k).sum::<u64>();
+
+    [guess.saturating_sub(1), guess, (guess + 1).min(MAX_RICE_PARAMETER)]
+        .into_iter()
+        .map(|k| (k, cost(k)))
+        .min_by_key(|(_, bits)| *bits)
+        .unwrap_or((0, u64::MAX))
+}
+
+/// Partition order and parameters with the fewest bits, and that number of bits
+fn plan_residual(residual: &[u64], block: usize, order: usize) -> (u32, Vec<u32>, u64) {
+    let mut best = (0, Vec::new(), u64::MAX);
+    for partition_order in 0..=MAX_PARTITION_ORDER {
+        let partitions = 1usize << partition_order;
+        if !block.is_multiple_of(partitions) || block / partitions <= order {
+            break;
+        }
+        let size = block / partitions;
+        let mut start = 0;
+        let mut parameters = Vec::with_capacity(partitions);
+        let mut bits = 0;
+        for partition in 0..partitions {
+            let length = if partition == 0 { size - order } else { size };
+            let (k, cost) = rice_parameter(&residual[start..start + length]);
+            parameters.push(k);
+            bits += cost + 5;
+            start += length;
+        }
+        if bits < best.2 {
+            best = (partition_order, parameters, bits);
+        }
+    }
+    best
+}
+
+fn write_subframe(writer: &mut BitWriter, samples: &[i64], bits_per_sample: u32) {
+    if samples.iter().all(|s| *s == samples[0]) {
+        writer.write(0b0000_0000, 8);
+        writer.write_signed(samples[0], bits_per_sample);
+        return;
+    }
+
+    let best = (0..=MAX_FIXED_ORDER.min(samples.len() - 1))
+        .map(|order| {
+            let residual: Vec<u64> = fixed_residual(samples, order).into_iter().map(zigzag).collect();
+            let (partition_order, parameters, bits) = plan_residual(&residual, samples.len(), order);
+            (order, residual, partition_order, parameters, bits + (order as u64) * bits_per_sample as u64)
+        })
+        .min_by_key(|candidate| candidate.4);
+
+    match best {
+        Some((order, residual, partition_order, parameters, bits)) if bits < (samples.len() as u64) * bits_per_sample as u64 => {
+            writer.write(0b0001_0000 | (order as u64) << 1, 8);
+            for sample in &samples[..order] {
+                writer.write_signed(*sample, bits_per_sample);
+            }
+            // RICE2 coding
+            writer.write(0b01, 2);
+            writer.write(partition_order as u64, 4);
+            let mut values = residual.iter();
+            for (partition, k) in parameters.iter().enumerate() {
+                let size = samples.len() >> partition_order;
+                let length = if partition == 0 { size - order } else { size };
+                writer.write(*k as u64, 5);
+                for value in values.by_ref().take(length) {
+                    writer.write_unary(value >> k);
+                    writer.write(*value, *k);
+                }
+            }
+        }
+        _ => {
+            writer.write(0b0000_0010, 8);
+            for sample in samples {
+                writer.write_signed(*sample, bits_per_sample);
+            }
+        }
+    }
+}
+
+/// Writes 16 or 24 bit FLAC, one fixed-size block at a time
+pub struct FlacWriter {
+    file: BufWriter<File>,
+    channels: usize,
+    rate: u32,
+    bits_per_sample: u32,
+    /// Interleaved samples waiting for a full block
+    pending: Vec<i64>,
+    frame_number: u64,
+    total_frames: u64,
+    min_frame_bytes: u32,
+    max_frame_bytes: u32,
+}
+
+impl FlacWriter {
+    pub fn create(path: &Path, channels: usize, rate: u32, bits_per_sample: u32) -> std::io::Result<Self> {
+        let mut writer = Self {
+            file: BufWriter::new(File::create(path)?),
+            channels,
+            rate,
+            bits_per_sample,
+            pending: Vec::new(),
+            frame_number: 0,
+            total_frames: 0,
+            min_frame_bytes: 0,
+            max_frame_bytes: 0,
+        };
+        writer.file.write_all(b"fLaC")?;
+        let streaminfo = writer.streaminfo();
+        writer.file.write_all(&streaminfo)?;
+        Ok(writer)
+    }
+
+    /// The STREAMINFO block, with the totals known so far. The MD5 is left out (all zero).
+    fn streaminfo(&self) -> Vec<u8> {
+        let mut writer = BitWriter::default();
+        // Last metadata block, type 0, 34 bytes
+        writer.write(1, 1);
+        writer.write(0, 7);
+        writer.write(34, 24);
+
+        let block = if self.total_frames > 0 && self.total_frames < BLOCK_SIZE as u64 { self.total_frames.max(16) } else { BLOCK_SIZE as u64 };
+        writer.write(block, 16);
+        writer.write(block, 16);
+        writer.write(self.min_frame_bytes as u64, 24);
+        writer.write(self.max_frame_bytes as u64, 24);
+        writer.write(self.rate as u64, 20);
+        writer.write(self.channels as u64 - 1, 3);
+        writer.write(self.bits_per_sample as u64 - 1, 5);
+        writer.write(self.total_frames >> 32, 4);
+        writer.write(self.total_frames & 0xFFFF_FFFF, 32);
+        for _ in 0..4 {
+            writer.write(0, 32);
+        }
+        writer.bytes
+    }
+
+    /// Interleaved samples, scaled to the bit depth
+    pub fn write(&mut self, samples: &[i32]) -> std::io::Result<()> {
+        self.pending.extend(samples.iter().map(|s| *s as i64));
+        let block_samples = BLOCK_SIZE * self.channels;
+        while self.pending.len() >= block_samples {
+            let block: Vec<i64> = self.pending.drain(..block_samples).collect();
+            self.write_frame(&block)?;
+        }
+        Ok(())
+    }
+
+    fn write_frame(&mut self, interleaved: &[i64]) -> std::io::Result<()> {
+        let frames = interleaved.len() / self.channels;
+        let mut writer = BitWriter::default();
+
+        // Sync code, fixed block size, size in 16 bits after the frame number, rate from STREAMINFO
+        writer.write(0b1111_1111_1111_1000, 16);
+        writer.write(0b0111, 4);
+        writer.write(0b0000, 4);
+        writer.write(self.channels as u64 - 1, 4);
+        writer.write(if self.bits_per_sample == 16 { 0b100 } else { 0b110 }, 3);
+        writer.write(0, 1);
+        write_utf8(&mut writer, self.frame_number);
+        writer.write(frames as u64 - 1, 16);
+        let header_crc = crc8(&writer.bytes);
+        writer.write(header_crc as u64, 8);
+
+        for channel in 0..self.channels {
+            let samples: Vec<i64> = interleaved.iter().skip(channel).step_by(self.channels).copied().collect();
+            write_subframe(&mut writer, &samples, self.bits_per_sample);
+        }
+        writer.align();
+        let frame_crc = crc16(&writer.bytes);
+        writer.write(frame_crc as u64, 16);
+
+        let length = writer.bytes.len() as u32;
+        self.min_frame_bytes = if self.frame_number == 0 { length } else { self.min_frame_bytes.min(length) };
+        self.max_frame_bytes = self.max_frame_bytes.max(length);
+        self.frame_number += 1;
+        self.total_frames += frames as u64;
+        self.file.write_all(&writer.bytes)
+    }
+
+    /// Writes what is left and fills in the totals.
+    pub fn finish(mut self) -> std::io::Result<()> {
+        if !self.pending.is_empty() {
+            let block = std::mem::take(&mut self.pending);
+            self.write_frame(&block)?;
+        }
+        let streaminfo = self.streaminfo();
+        self.file.seek(SeekFrom::Start(STREAMINFO_OFFSET))?;
+        self.file.write_all(&streaminfo)?;
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rme-flac-{}-{}.flac", std::process::id(), name))
+    }
+
+    /// Writes `samples` in uneven chunks, decodes the file and checks every sample came back
+    fn round_trip(name: &str, samples: &[i32], channels: usize, bits_per_sample: u32) {
+        let path = temp_path(name);
+        let mut writer = FlacWriter::create(&path, channels, 48000, bits_per_sample).unwrap();
+        for chunk in samples.chunks(1000 * channels + 1) {
+            writer.write(chunk).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = claxon::FlacReader::open(&path).unwrap();
+        let info = reader.streaminfo();
+        let decoded: Vec<i32> = reader.samples().map(|sample| sample.unwrap()).collect();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(info.channels as usize, channels);
+        assert_eq!(info.bits_per_sample, bits_per_sample);
+        assert_eq!(info.sample_rate, 48000);
+        assert_eq!(info.samples, Some((samples.len() / channels) as u64));
+        assert!(decoded == samples, "{}: decoded samples differ", name);
+    }
+
+    /// A tone with a little noise, silence in the middle and full scale peaks at the end
+    fn signal(frames: usize, channels: usize, bits_per_sample: u32) -> Vec<i32> {
+        let max = (1i64 << (bits_per_sample - 1)) - 1;
+        let mut state = 0x2545_F491u32;
+        let mut samples = Vec::with_capacity(frames * channels);
+        for i in 0..frames {
+            for channel in 0..channels {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let noise = ((state >> 8) as f64 / (1u32 << 24) as f64 - 0.5) * 0.01;
+                let value = match i {
+                    _ if i % 5000 >= 4000 && i % 5000 < 4500 => 0.0,
+                    _ if i + 3 >= frames => if channel % 2 == 0 { 1.0 } else { -1.0 },
+                    _ => 0.5 * (i as f64 * 0.05 * (channel + 1) as f64).sin() + noise,
+                };
+                samples.push((value * max as f64).round().clamp(-(max as f64) - 1.0, max as f64) as i32);
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn round_trips_across_block_sizes_and_bit_depths() {
+        for bits_per_sample in [16, 24] {
+            for channels in [1, 2] {
+                for frames in [1, 15, BLOCK_SIZE - 1, BLOCK_SIZE, BLOCK_SIZE + 1, 3 * BLOCK_SIZE + 123] {
+                    let name = format!("{}bit-{}ch-{}", bits_per_sample, channels, frames);
+                    round_trip(&name, &signal(frames, channels, bits_per_sample), channels, bits_per_sample);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_full_scale_noise() {
+        // Too random for the predictors, the frames fall back to verbatim subframes
+        for bits_per_sample in [16, 24] {
+            let mut state = 0x9E37_79B9u32;
+            let samples: Vec<i32> = (0..2 * BLOCK_SIZE * 2)
+                .map(|_| {
+                    state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                    state as i32 >> (32 - bits_per_sample)
+                })
+                .collect();
+            round_trip(&format!("noise-{}bit", bits_per_sample), &samples, 2, bits_per_sample);
+        }
+    }
+
+    #[test]
+    fn round_trips_constant_channels() {
+        let samples: Vec<i32> = (0..BLOCK_SIZE * 2).flat_map(|_| [0, -1234]).collect();
+        round_trip("constant", &samples, 2, 16);
+    }
+}
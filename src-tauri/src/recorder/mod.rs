@@ -0,0 +1,4 @@
+pub mod controller;
+pub mod files;
+pub mod flac;
+pub mod session;
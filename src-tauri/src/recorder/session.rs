@@ -0,0 +1,506 @@
+use super::files::{self, RecordingFormat, Track, TrackFiles};
+use crate::alsa::stereo_link::STEREO_PAIRS;
+use crate::pipewire::audio::{self, BlockInfo};
+use crate::pipewire::tap::{self, Tap, TapSource};
+use crate::storage::config::ConfigStorage;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use tauri::{AppHandle, Emitter, Manager};
+
+const MIN_ROLLING_SECONDS: u32 = 5;
+// Four inputs for five minutes at 48 kHz is about 170 MB at 24 bit
+const MAX_ROLLING_SECONDS: u32 = 300;
+const RECORDINGS_FOLDER: &str = "Babyface";
+
+fn default_bit_depth() -> u16 {
+    24
+}
+
+fn default_rolling_seconds() -> u32 {
+    60
+}
+
+fn default_rolling_inputs() -> Vec<String> {
+    STEREO_PAIRS.iter().flat_map(|(left, right)| [left.to_string(), right.to_string()]).collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecorderSettings {
+    /// None records to a Babyface folder in the music folder
+    #[serde(default)]
+    pub directory: Option<String>,
+    #[serde(default)]
+    pub format: RecordingFormat,
+    /// 16 or 24
+    #[serde(default = "default_bit_depth")]
+    pub bit_depth: u16,
+    /// Keep the last `rolling_seconds` of the rolling inputs in memory, ready to be saved
+    #[serde(default)]
+    pub rolling_enabled: bool,
+    #[serde(default = "default_rolling_seconds")]
+    pub rolling_seconds: u32,
+    #[serde(default = "default_rolling_inputs")]
+    pub rolling_inputs: Vec<String>,
+}
+
+impl Default for RecorderSettings {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            format: RecordingFormat::default(),
+            bit_depth: default_bit_depth(),
+            rolling_enabled: false,
+            rolling_seconds: default_rolling_seconds(),
+            rolling_inputs: default_rolling_inputs(),
+        }
+    }
+}
+
+/// Emitted as "recorder-changed"
+#[derive(Serialize, Debug, Clone)]
+pub struct RecorderStatus {
+    pub recording: bool,
+    pub inputs: Vec<String>,
+    /// Length of the running recording
+    pub seconds: f64,
+    pub rolling_enabled: bool,
+    /// Audio in the rolling buffer, what "save last seconds" can save
+    pub rolling_available_seconds: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RecordingSummary {
+    pub files: Vec<String>,
+    pub seconds: f64,
+    /// Set when part of the recording couldn't be kept
+    pub warning: Option<String>,
+}
+
+struct Recording {
+    inputs: Vec<String>,
+    rate: Arc<AtomicU32>,
+    frames: Arc<AtomicU64>,
+    writer: JoinHandle<Result<RecordingSummary, String>>,
+    tap: Tap,
+}
+
+/// Rolling audio is kept at the bit depth it's saved in, 2 or 3 bytes a sample instead of 4
+enum Samples {
+    Pcm16(VecDeque<i16>),
+    /// Little endian
+    Pcm24(VecDeque<[u8; 3]>),
+}
+
+struct RingBuffer {
+    rate: u32,
+    /// Channels per frame, one per rolling input
+    width: usize,
+    seconds: u32,
+    bit_depth: u16,
+    samples: Samples,
+}
+
+impl RingBuffer {
+    fn new(width: usize, seconds: u32, bit_depth: u16) -> Self {
+        let samples = if bit_depth == 16 { Samples::Pcm16(VecDeque::new()) } else { Samples::Pcm24(VecDeque::new()) };
+        Self {
+            rate: 0,
+            width,
+            seconds,
+            bit_depth,
+            samples,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.seconds as usize * self.rate as usize * self.width
+    }
+
+    fn len(&self) -> usize {
+        match &self.samples {
+            Samples::Pcm16(samples) => samples.len(),
+            Samples::Pcm24(samples) => samples.len(),
+        }
+    }
+
+    /// Starts over at a new rate, with all the room it will need so pushing doesn't allocate
+    fn restart(&mut self, rate: u32) {
+        self.rate = rate;
+        let capacity = self.capacity();
+        match &mut self.samples {
+            Samples::Pcm16(samples) => *samples = VecDeque::with_capacity(capacity),
+            Samples::Pcm24(samples) => *samples = VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// An empty buffer with the same layout, without room yet
+    fn emptied(&self) -> Self {
+        Self {
+            rate: self.rate,
+            ..Self::new(self.width, self.seconds, self.bit_depth)
+        }
+    }
+
+    /// Puts `newer`'s samples after these, dropping the oldest to stay within capacity
+    fn append(&mut self, newer: &RingBuffer) {
+        let excess = (self.len() + newer.len()).saturating_sub(self.capacity());
+        match (&mut self.samples, &newer.samples) {
+            (Samples::Pcm16(samples), Samples::Pcm16(newer)) => {
+                samples.drain(..excess.min(samples.len()));
+                samples.extend(newer.iter().copied());
+            }
+            (Samples::Pcm24(samples), Samples::Pcm24(newer)) => {
+                samples.drain(..excess.min(samples.len()));
+                samples.extend(newer.iter().copied());
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds a sample, dropping the oldest once full
+    fn push(&mut self, sample: f32) {
+        let full = self.len() >= self.capacity();
+        let value = files::quantize(sample, self.bit_depth);
+        match &mut self.samples {
+            Samples::Pcm16(samples) => {
+                if full {
+                    samples.pop_front();
+                }
+                samples.push_back(value as i16);
+            }
+            Samples::Pcm24(samples) => {
+                if full {
+                    samples.pop_front();
+                }
+                let [low, middle, high, _] = value.to_le_bytes();
+                samples.push_back([low, middle, high]);
+            }
+        }
+    }
+
+    /// The last `count` samples, scaled to the bit depth
+    fn tail(&self, count: usize) -> Vec<i32> {
+        let skip = self.len().saturating_sub(count);
+        match &self.samples {
+            Samples::Pcm16(samples) => samples.iter().skip(skip).map(|sample| *sample as i32).collect(),
+            // Shifted down from the top byte so the sign carries
+            Samples::Pcm24(samples) => samples.iter().skip(skip).map(|[low, middle, high]| i32::from_le_bytes([0, *low, *middle, *high]) >> 8).collect(),
+        }
+    }
+}
+
+struct Rolling {
+    inputs: Vec<String>,
+    channels: Vec<usize>,
+    buffer: Arc<Mutex<RingBuffer>>,
+    _tap: Tap,
+}
+
+static RECORDING: Lazy<Mutex<Option<Recording>>> = Lazy::new(|| Mutex::new(None));
+static ROLLING: Lazy<Mutex<Option<Rolling>>> = Lazy::new(|| Mutex::new(None));
+
+pub fn get_settings(app: &AppHandle) -> Result<RecorderSettings, String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    Ok(config.settings.recorder)
+}
+
+/// Stores the settings and starts or stops the rolling buffer to match.
+pub fn set_settings(app: &AppHandle, mut settings: RecorderSettings) -> Result<(), String> {
+    if settings.bit_depth != 16 && settings.bit_depth != 24 {
+        return Err(format!("{} bit isn't supported, use 16 or 24", settings.bit_depth));
+    }
+    settings.rolling_seconds = settings.rolling_seconds.clamp(MIN_ROLLING_SECONDS, MAX_ROLLING_SECONDS);
+    settings.directory = settings.directory.filter(|d| !d.trim().is_empty());
+
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    storage.update(|config| config.settings.recorder = settings.clone()).map_err(|e| e.to_string())?;
+    apply_rolling(&settings)
+}
+
+fn directory(app: &AppHandle, settings: &RecorderSettings) -> Result<PathBuf, String> {
+    match &settings.directory {
+        Some(directory) => Ok(PathBuf::from(directory)),
+        None => app.path().audio_dir().map(|music| music.join(RECORDINGS_FOLDER)).map_err(|e| format!("No music folder to record to, choose a folder: {}", e)),
+    }
+}
+
+fn plan_tracks(app: &AppHandle, inputs: &[String]) -> Result<Vec<Track>, String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    files::plan_tracks(inputs, &config.channels)
+}
+
+pub fn get_status() -> RecorderStatus {
+    let recording = RECORDING.lock();
+    let recording = recording.as_ref().ok().and_then(|r| r.as_ref());
+    let (rolling_enabled, rolling_available_seconds) = ROLLING
+        .lock()
+        .ok()
+        .and_then(|rolling| {
+            let buffer = rolling.as_ref()?.buffer.lock().ok()?;
+            Some((true, buffer.len() as f64 / (buffer.width.max(1) as f64 * buffer.rate.max(1) as f64)))
+        })
+        .unwrap_or((false, 0.0));
+
+    RecorderStatus {
+        recording: recording.is_some(),
+        inputs: recording.map(|r| r.inputs.clone()).unwrap_or_default(),
+        seconds: recording
+            .map(|r| r.frames.load(Ordering::Relaxed) as f64 / r.rate.load(Ordering::Relaxed).max(1) as f64)
+            .unwrap_or(0.0),
+        rolling_enabled,
+        rolling_available_seconds,
+    }
+}
+
+pub fn emit_status(app: &AppHandle) {
+    let _ = app.emit("recorder-changed", get_status());
+}
+
+/// Picks the channels out of a block into `selected`, frame by frame. False when the block
+/// doesn't have them all.
+fn select(samples: &[f32], info: BlockInfo, channels: &[usize], selected: &mut Vec<f32>) -> bool {
+    if channels.iter().any(|channel| *channel >= info.channels) {
+        return false;
+    }
+    selected.clear();
+    selected.extend(samples.chunks_exact(info.channels).flat_map(|frame| channels.iter().map(move |channel| frame[*channel])));
+    true
+}
+
+type Block = (u32, Vec<f32>);
+
+/// Writes blocks as they come until the tap lets go of the sender, and hands the written ones
+/// back to be filled again. The files are created with the first block, once the rate is known.
+fn write_blocks(
+    blocks: mpsc::Receiver<Block>,
+    recycle: mpsc::Sender<Vec<f32>>,
+    directory: PathBuf,
+    tracks: Vec<Track>,
+    settings: RecorderSettings,
+    rate_seen: Arc<AtomicU32>,
+    frames: Arc<AtomicU64>,
+) -> Result<RecordingSummary, String> {
+    let width: usize = tracks.iter().map(|track| track.channels.len()).sum();
+    let mut files: Option<TrackFiles> = None;
+    let mut warning = None;
+
+    for (rate, samples) in blocks {
+        if warning.is_some() {
+            continue;
+        }
+        if files.is_none() {
+            rate_seen.store(rate, Ordering::Relaxed);
+            files = Some(TrackFiles::create(&directory, &tracks, settings.format, settings.bit_depth, rate)?);
+        } else if rate_seen.load(Ordering::Relaxed) != rate {
+            warning = Some(format!("The rate changed to {} Hz, the recording ends there", rate));
+            continue;
+        }
+        let Some(files) = files.as_mut() else {
+            continue;
+        };
+        if let Err(e) = files.write(&samples) {
+            warning = Some(format!("Writing stopped: {}", e));
+            continue;
+        }
+        frames.fetch_add((samples.len() / width) as u64, Ordering::Relaxed);
+        let _ = recycle.send(samples);
+    }
+
+    let files = files.ok_or("Nothing was recorded, check that the Babyface is running in PipeWire")?.finish()?;
+    Ok(RecordingSummary {
+        files,
+        seconds: frames.load(Ordering::Relaxed) as f64 / rate_seen.load(Ordering::Relaxed).max(1) as f64,
+        warning,
+    })
+}
+
+/// Records the inputs to files named after their channel configs until stopped.
+pub fn start_recording(app: &AppHandle, inputs: Vec<String>) -> Result<RecorderStatus, String> {
+    let mut recording = RECORDING.lock().map_err(|e| e.to_string())?;
+    if recording.is_some() {
+        return Err("A recording is already running".to_string());
+    }
+
+    let settings = get_settings(app)?;
+    let directory = directory(app, &settings)?;
+    let tracks = plan_tracks(app, &inputs)?;
+    let channels: Vec<usize> = tracks.iter().flat_map(|track| track.channels.clone()).collect();
+
+    let (sender, receiver) = mpsc::channel::<Block>();
+    // Written blocks come back, so once it's going the audio thread doesn't allocate
+    let (recycle, recycled) = mpsc::channel::<Vec<f32>>();
+    let on_samples = Box::new(move |samples: &[f32], info: BlockInfo| {
+        let mut block = recycled.try_recv().unwrap_or_default();
+        if select(samples, info, &channels, &mut block) {
+            let _ = sender.send((info.rate, block));
+        }
+    });
+
+    let rate = Arc::new(AtomicU32::new(0));
+    let frames = Arc::new(AtomicU64::new(0));
+    let writer = {
+        let (rate, frames) = (rate.clone(), frames.clone());
+        thread::Builder::new()
+            .name("recorder".to_string())
+            .spawn(move || write_blocks(receiver, recycle, directory, tracks, settings, rate, frames))
+            .map_err(|e| format!("Failed to start the recorder: {}", e))?
+    };
+    // Without the tap the sender goes away and the writer ends by itself
+    let tap = tap::subscribe(TapSource::Inputs, on_samples)?;
+
+    *recording = Some(Recording { inputs, rate, frames, writer, tap });
+    drop(recording);
+    Ok(get_status())
+}
+
+pub fn stop_recording() -> Result<RecordingSummary, String> {
+    let recording = RECORDING.lock().map_err(|e| e.to_string())?.take().ok_or("Nothing is being recorded")?;
+    drop(recording.tap);
+    recording.writer.join().map_err(|_| "The recorder stopped unexpectedly".to_string())?
+}
+
+/// Starts or stops the rolling buffer to match the settings. A running buffer starts over.
+pub fn apply_rolling(settings: &RecorderSettings) -> Result<(), String> {
+    let previous = ROLLING.lock().map_err(|e| e.to_string())?.take();
+    drop(previous);
+    if !settings.rolling_enabled {
+        return Ok(());
+    }
+
+    let mut channels = Vec::new();
+    for input in &settings.rolling_inputs {
+        channels.push(audio::capture_target(input)?.1);
+    }
+    if channels.is_empty() {
+        return Err("Choose at least one input for the rolling buffer".to_string());
+    }
+
+    let buffer = Arc::new(Mutex::new(RingBuffer::new(channels.len(), settings.rolling_seconds, settings.bit_depth)));
+    let shared = buffer.clone();
+    let selected = channels.clone();
+    let on_samples = Box::new(move |samples: &[f32], info: BlockInfo| {
+        // Dropped rather than waited for, saving only holds the lock for a swap
+        let Ok(mut buffer) = shared.try_lock() else {
+            return;
+        };
+        if selected.iter().any(|channel| *channel >= info.channels) {
+            return;
+        }
+        if buffer.rate != info.rate {
+            buffer.restart(info.rate);
+        }
+        for frame in samples.chunks_exact(info.channels) {
+            for channel in &selected {
+                buffer.push(frame[*channel]);
+            }
+        }
+    });
+    let tap = tap::subscribe(TapSource::Inputs, on_samples)?;
+
+    *ROLLING.lock().map_err(|e| e.to_string())? = Some(Rolling {
+        inputs: settings.rolling_inputs.clone(),
+        channels,
+        buffer,
+        _tap: tap,
+    });
+    Ok(())
+}
+
+/// Writes the last `seconds` of the rolling buffer to files, all of it when None.
+pub fn save_rolling(app: &AppHandle, seconds: Option<f64>) -> Result<RecordingSummary, String> {
+    let settings = get_settings(app)?;
+    let directory = directory(app, &settings)?;
+
+    let (inputs, channels, shared) = {
+        let rolling = ROLLING.lock().map_err(|e| e.to_string())?;
+        let rolling = rolling.as_ref().ok_or("The rolling buffer is off, turn it on in the recorder settings")?;
+        (rolling.inputs.clone(), rolling.channels.clone(), rolling.buffer.clone())
+    };
+
+    // Swapped for an empty buffer that the tap fills while the samples are copied out, then the
+    // taken samples go back in front of what came in meanwhile
+    let mut empty = shared.lock().map_err(|e| e.to_string())?.emptied();
+    empty.restart(empty.rate);
+    let mut taken = std::mem::replace(&mut *shared.lock().map_err(|e| e.to_string())?, empty);
+    let (rate, width, bit_depth) = (taken.rate, taken.width, taken.bit_depth);
+    let wanted = seconds.map(|s| (s.max(0.0) * rate as f64) as usize * width).unwrap_or(usize::MAX);
+    let samples = taken.tail(wanted);
+    if let Ok(mut buffer) = shared.lock() {
+        // A rate change in between started the buffer over, the taken samples don't fit anymore
+        if buffer.rate == rate {
+            taken.append(&buffer);
+            *buffer = taken;
+        }
+    }
+    if samples.is_empty() {
+        return Err("The rolling buffer is empty".to_string());
+    }
+
+    // The buffer holds the inputs in settings order, the files want them per track
+    let tracks = plan_tracks(app, &inputs)?;
+    let order: Vec<usize> = tracks
+        .iter()
+        .flat_map(|track| track.channels.iter())
+        .map(|channel| channels.iter().position(|c| c == channel).unwrap_or(0))
+        .collect();
+    let reordered: Vec<i32> = samples.chunks_exact(width).flat_map(|frame| order.iter().map(move |i| frame[*i])).collect();
+
+    let mut files = TrackFiles::create(&directory, &tracks, settings.format, bit_depth, rate)?;
+    files.write_scaled(&reordered)?;
+    Ok(RecordingSummary {
+        files: files.finish()?,
+        seconds: (samples.len() / width) as f64 / rate.max(1) as f64,
+        warning: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One second of `rate` mono samples, so the capacity is `rate` samples
+    fn buffer(bit_depth: u16, rate: u32) -> RingBuffer {
+        let mut buffer = RingBuffer::new(1, 1, bit_depth);
+        buffer.restart(rate);
+        buffer
+    }
+
+    #[test]
+    fn keeps_the_newest_samples_at_both_bit_depths() {
+        for bit_depth in [16, 24] {
+            let mut ring = buffer(bit_depth, 4);
+            for sample in [0.1, -0.2, 0.3, -0.4, 0.5, -1.0] {
+                ring.push(sample);
+            }
+            let expected: Vec<i32> = [0.3, -0.4, 0.5, -1.0].iter().map(|sample| files::quantize(*sample, bit_depth)).collect();
+            assert_eq!(ring.tail(usize::MAX), expected);
+            assert_eq!(ring.tail(2), expected[2..]);
+        }
+    }
+
+    #[test]
+    fn samples_from_a_swapped_in_buffer_go_after_the_taken_ones() {
+        for bit_depth in [16, 24] {
+            let mut taken = buffer(bit_depth, 4);
+            for sample in [0.1, -0.2, 0.3] {
+                taken.push(sample);
+            }
+            let mut meanwhile = taken.emptied();
+            meanwhile.restart(meanwhile.rate);
+            for sample in [-0.4, 0.5] {
+                meanwhile.push(sample);
+            }
+            taken.append(&meanwhile);
+            let expected: Vec<i32> = [-0.2, 0.3, -0.4, 0.5].iter().map(|sample| files::quantize(*sample, bit_depth)).collect();
+            assert_eq!(taken.tail(usize::MAX), expected);
+        }
+    }
+}
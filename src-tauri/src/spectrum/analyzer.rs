@@ -0,0 +1,211 @@
+use crate::dsp::octave::{self, BandMode};
+use crate::dsp::spectrum::{self, power_to_dbfs};
+use crate::dsp::window::Window;
+use crate::pipewire::audio::BlockInfo;
+use crate::pipewire::tap::{self, Tap, TapChannel};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+const MIN_FFT_SIZE: u32 = 256;
+const MAX_FFT_SIZE: u32 = 65536;
+const MAX_AVERAGES: u32 = 64;
+const MAX_FRACTION: u32 = 48;
+const MAX_FRAME_RATE: u32 = 60;
+const LOW_HZ: f64 = 20.0;
+const HIGH_HZ: f64 = 20000.0;
+
+fn default_fft_size() -> u32 {
+    8192
+}
+
+fn default_averages() -> u32 {
+    4
+}
+
+fn default_fraction() -> u32 {
+    6
+}
+
+fn default_frame_rate() -> u32 {
+    20
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpectrumSettings {
+    pub channel: TapChannel,
+    /// Rounded up to a power of two
+    #[serde(default = "default_fft_size")]
+    pub fft_size: u32,
+    #[serde(default)]
+    pub window: Window,
+    /// Frames in the exponential average, 1 shows every frame as it is
+    #[serde(default = "default_averages")]
+    pub averages: u32,
+    /// Bands per octave, 3 for third-octave bands
+    #[serde(default = "default_fraction")]
+    pub fraction: u32,
+    /// Frames per second sent to the frontend
+    #[serde(default = "default_frame_rate")]
+    pub frame_rate: u32,
+}
+
+/// Emitted as "spectrum-frame"
+#[derive(Serialize, Debug, Clone)]
+pub struct SpectrumFrame {
+    pub rate: u32,
+    /// Band centre frequencies in Hz
+    pub bands: Vec<f64>,
+    /// Energy per band in dBFS, a full-scale sine reads 0 dBFS. Pink noise reads flat.
+    pub levels_db: Vec<f64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SpectrumStatus {
+    pub running: bool,
+    pub settings: Option<SpectrumSettings>,
+}
+
+#[derive(Default)]
+struct History {
+    rate: u32,
+    samples: VecDeque<f32>,
+}
+
+struct Running {
+    settings: SpectrumSettings,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+    _tap: Tap,
+}
+
+static ANALYZER: Lazy<Mutex<Option<Running>>> = Lazy::new(|| Mutex::new(None));
+
+pub fn get_status() -> SpectrumStatus {
+    let analyzer = ANALYZER.lock();
+    let settings = analyzer.ok().and_then(|a| a.as_ref().map(|running| running.settings.clone()));
+    SpectrumStatus {
+        running: settings.is_some(),
+        settings,
+    }
+}
+
+/// Averages the newest `fft_size` samples into the running spectrum and reduces it to bands.
+struct Frames {
+    settings: SpectrumSettings,
+    window: Vec<f64>,
+    average: Option<Vec<f64>>,
+}
+
+impl Frames {
+    fn next(&mut self, samples: &[f32], rate: u32) -> SpectrumFrame {
+        let power = spectrum::power_spectrum_with(samples, &self.window);
+        let weight = 1.0 / self.settings.averages as f64;
+        match self.average.as_mut() {
+            Some(average) => average.iter_mut().zip(&power).for_each(|(a, p)| *a += (p - *a) * weight),
+            None => self.average = Some(power),
+        }
+        let average = self.average.as_deref().unwrap_or_default();
+
+        let fraction = self.settings.fraction;
+        let bin_hz = rate as f64 / samples.len() as f64;
+        let bands = octave::band_centers(fraction, LOW_HZ, HIGH_HZ.min(rate as f64 / 2.0));
+        let levels_db = bands
+            .iter()
+            .map(|center| power_to_dbfs(octave::band_power(average, bin_hz, *center, fraction, BandMode::Sum)))
+            .collect();
+
+        SpectrumFrame { rate, bands, levels_db }
+    }
+}
+
+fn run_frames(app: AppHandle, history: Arc<Mutex<History>>, mut frames: Frames, stop: Arc<AtomicBool>) {
+    let size = frames.settings.fft_size as usize;
+    let interval = Duration::from_secs_f64(1.0 / frames.settings.frame_rate as f64);
+    let mut next = Instant::now();
+
+    while !stop.load(Ordering::Relaxed) {
+        let snapshot = history
+            .lock()
+            .ok()
+            .filter(|history| history.samples.len() >= size)
+            .map(|history| (history.rate, history.samples.iter().copied().collect::<Vec<f32>>()));
+
+        if let Some((rate, samples)) = snapshot {
+            let _ = app.emit("spectrum-frame", frames.next(&samples, rate));
+        }
+
+        next += interval;
+        let now = Instant::now();
+        if next > now {
+            thread::sleep(next - now);
+        } else {
+            // Fell behind, skip the missed frames instead of sending them in a burst
+            next = now;
+        }
+    }
+}
+
+/// Starts streaming the channel's spectrum, replacing the running analyzer.
+pub fn start(app: &AppHandle, mut settings: SpectrumSettings) -> Result<SpectrumStatus, String> {
+    settings.fft_size = settings.fft_size.clamp(MIN_FFT_SIZE, MAX_FFT_SIZE).next_power_of_two();
+    settings.averages = settings.averages.clamp(1, MAX_AVERAGES);
+    settings.fraction = settings.fraction.clamp(1, MAX_FRACTION);
+    settings.frame_rate = settings.frame_rate.clamp(1, MAX_FRAME_RATE);
+
+    stop()?;
+
+    let index = settings.channel.resolve()?;
+    let size = settings.fft_size as usize;
+    let history = Arc::new(Mutex::new(History::default()));
+    let shared = history.clone();
+    let on_samples = Box::new(move |samples: &[f32], info: BlockInfo| {
+        let Ok(mut history) = shared.lock() else {
+            return;
+        };
+        if index >= info.channels {
+            return;
+        }
+        history.rate = info.rate;
+        history.samples.extend(samples.chunks_exact(info.channels).map(|frame| frame[index]));
+        let excess = history.samples.len().saturating_sub(size);
+        history.samples.drain(..excess);
+    });
+    let tap = tap::subscribe(settings.channel.source, on_samples)?;
+
+    let frames = Frames {
+        window: settings.window.coefficients(size),
+        settings: settings.clone(),
+        average: None,
+    };
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let (app, stop) = (app.clone(), stop.clone());
+        thread::Builder::new()
+            .name("spectrum".to_string())
+            .spawn(move || run_frames(app, history, frames, stop))
+            .map_err(|e| format!("Failed to start the analyzer: {}", e))?
+    };
+
+    *ANALYZER.lock().map_err(|e| e.to_string())? = Some(Running {
+        settings,
+        stop,
+        thread,
+        _tap: tap,
+    });
+    Ok(get_status())
+}
+
+pub fn stop() -> Result<(), String> {
+    let running = ANALYZER.lock().map_err(|e| e.to_string())?.take();
+    if let Some(running) = running {
+        running.stop.store(true, Ordering::Relaxed);
+        let _ = running.thread.join();
+    }
+    Ok(())
+}
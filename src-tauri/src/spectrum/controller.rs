@@ -0,0 +1,18 @@
+use tauri::AppHandle;
+use super::analyzer::{self, SpectrumSettings, SpectrumStatus};
+
+/// Frames arrive as "spectrum-frame" events until the analyzer is stopped
+#[tauri::command]
+pub fn start_spectrum(app_handle: AppHandle, settings: SpectrumSettings) -> Result<SpectrumStatus, String> {
+    analyzer::start(&app_handle, settings)
+}
+
+#[tauri::command]
+pub fn stop_spectrum() -> Result<(), String> {
+    analyzer::stop()
+}
+
+#[tauri::command]
+pub fn get_spectrum_status() -> SpectrumStatus {
+    analyzer::get_status()
+}
@@ -0,0 +1,273 @@
+use crate::alsa::stereo_link::{self, STEREO_PAIRS};
+use crate::pipewire::audio::{self, BlockInfo};
+use crate::pipewire::tap::{self, Tap, TapSource};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::f32::consts::FRAC_1_SQRT_2;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+// Time constant of the correlation, close to what hardware correlation meters show
+const CORRELATION_SECONDS: f64 = 0.3;
+// Points per pair in a frame, the rest of the frames in between are skipped
+const POINTS_PER_FRAME: usize = 256;
+// Below this mean square a pair counts as silent and has no correlation
+const SILENCE_POWER: f64 = 1e-8;
+
+/// One stereo pair in a "stereo-analysis" event
+#[derive(Serialize, Debug, Clone)]
+pub struct PairAnalysis {
+    pub left: String,
+    pub right: String,
+    /// +1 in phase, 0 unrelated, -1 out of phase. None while the pair is silent.
+    pub correlation: Option<f64>,
+    /// Goniometer points as [side, mid], up for mono and sideways for out of phase
+    pub points: Vec<[f32; 2]>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StereoAnalysisStatus {
+    pub running: bool,
+    /// Linked pairs that are analysed, left input first
+    pub pairs: Vec<[String; 2]>,
+}
+
+#[derive(Default, Clone)]
+struct PairState {
+    // Exponentially decaying sums of l*r, l*l and r*r
+    lr: f64,
+    ll: f64,
+    rr: f64,
+    points: Vec<[f32; 2]>,
+    skip: usize,
+}
+
+impl PairState {
+    fn process(&mut self, samples: &[f32], info: BlockInfo, left: usize, right: usize) {
+        let frames = samples.len() / info.channels;
+        let decay = (-(frames as f64) / (CORRELATION_SECONDS * info.rate.max(1) as f64)).exp();
+        self.lr *= decay;
+        self.ll *= decay;
+        self.rr *= decay;
+
+        let step = (info.rate as f64 * FRAME_INTERVAL.as_secs_f64() / POINTS_PER_FRAME as f64).max(1.0) as usize;
+        for frame in samples.chunks_exact(info.channels) {
+            let (l, r) = (frame[left], frame[right]);
+            self.lr += (l * r) as f64;
+            self.ll += (l * l) as f64;
+            self.rr += (r * r) as f64;
+
+            if self.skip == 0 && self.points.len() < POINTS_PER_FRAME {
+                self.points.push([(l - r) * FRAC_1_SQRT_2, (l + r) * FRAC_1_SQRT_2]);
+                self.skip = step;
+            }
+            self.skip = self.skip.saturating_sub(1);
+        }
+    }
+
+    fn correlation(&self, rate: u32) -> Option<f64> {
+        // The sums hold about this many frames' worth of signal
+        let frames = CORRELATION_SECONDS * rate.max(1) as f64;
+        if self.ll / frames < SILENCE_POWER || self.rr / frames < SILENCE_POWER {
+            return None;
+        }
+        Some((self.lr / (self.ll * self.rr).sqrt()).clamp(-1.0, 1.0))
+    }
+}
+
+#[derive(Default)]
+struct Pairs {
+    rate: u32,
+    states: [PairState; STEREO_PAIRS.len()],
+}
+
+struct Running {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+    _tap: Tap,
+}
+
+static RUNNING: Lazy<Mutex<Option<Running>>> = Lazy::new(|| Mutex::new(None));
+
+fn linked_pairs() -> Vec<[String; 2]> {
+    stereo_link::linked_pairs().iter().map(|(left, right)| [left.to_string(), right.to_string()]).collect()
+}
+
+pub fn get_status() -> StereoAnalysisStatus {
+    StereoAnalysisStatus {
+        running: RUNNING.lock().map(|running| running.is_some()).unwrap_or(false),
+        pairs: linked_pairs(),
+    }
+}
+
+/// The linked pairs' analysis since the last frame. Pairs are looked up every frame, so linking
+/// or unlinking channels shows up without a restart.
+fn take_frame(pairs: &Mutex<Pairs>) -> Vec<PairAnalysis> {
+    let Ok(mut pairs) = pairs.lock() else {
+        return Vec::new();
+    };
+    let rate = pairs.rate;
+    let linked = stereo_link::linked_pairs();
+
+    STEREO_PAIRS
+        .iter()
+        .zip(pairs.states.iter_mut())
+        .filter(|(pair, _)| linked.contains(pair))
+        .map(|((left, right), state)| PairAnalysis {
+            left: left.to_string(),
+            right: right.to_string(),
+            correlation: state.correlation(rate),
+            points: std::mem::take(&mut state.points),
+        })
+        .collect()
+}
+
+/// Streams correlation and goniometer points for every linked input pair as "stereo-analysis".
+pub fn start(app: &AppHandle) -> Result<StereoAnalysisStatus, String> {
+    let mut running = RUNNING.lock().map_err(|e| e.to_string())?;
+    if running.is_some() {
+        return Ok(StereoAnalysisStatus {
+            running: true,
+            pairs: linked_pairs(),
+        });
+    }
+
+    let mut channels = Vec::new();
+    for (left, right) in STEREO_PAIRS {
+        channels.push((audio::capture_target(left)?.1, audio::capture_target(right)?.1));
+    }
+
+    let pairs = Arc::new(Mutex::new(Pairs::default()));
+    let shared = pairs.clone();
+    let on_samples = Box::new(move |samples: &[f32], info: BlockInfo| {
+        let Ok(mut pairs) = shared.lock() else {
+            return;
+        };
+        pairs.rate = info.rate;
+        for (state, (left, right)) in pairs.states.iter_mut().zip(&channels) {
+            if (*left).max(*right) < info.channels {
+                state.process(samples, info, *left, *right);
+            }
+        }
+    });
+    let tap = tap::subscribe(TapSource::Inputs, on_samples)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let (app, stop) = (app.clone(), stop.clone());
+        thread::Builder::new()
+            .name("stereo-analysis".to_string())
+            .spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(FRAME_INTERVAL);
+                    let _ = app.emit("stereo-analysis", take_frame(&pairs));
+                }
+            })
+            .map_err(|e| format!("Failed to start the stereo analysis: {}", e))?
+    };
+
+    *running = Some(Running { stop, thread, _tap: tap });
+    Ok(StereoAnalysisStatus {
+        running: true,
+        pairs: linked_pairs(),
+    })
+}
+
+pub fn stop() -> Result<(), String> {
+    let running = RUNNING.lock().map_err(|e| e.to_string())?.take();
+    if let Some(running) = running {
+        running.stop.store(true, Ordering::Relaxed);
+        let _ = running.thread.join();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INFO: BlockInfo = BlockInfo {
+        rate: 48000,
+        channels: 2,
+        clock: None,
+    };
+
+    /// Runs `seconds` of frames from `frame` through a pair in blocks of 1024 frames
+    fn analyse(seconds: f64, mut frame: impl FnMut(usize) -> [f32; 2]) -> PairState {
+        let mut state = PairState::default();
+        let frames: Vec<f32> = (0..(seconds * INFO.rate as f64) as usize).flat_map(&mut frame).collect();
+        for block in frames.chunks(2 * 1024) {
+            state.process(block, INFO, 0, 1);
+        }
+        state
+    }
+
+    fn sine(i: usize) -> f32 {
+        0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / INFO.rate as f32).sin()
+    }
+
+    #[test]
+    fn identical_channels_correlate() {
+        let state = analyse(1.0, |i| [sine(i), sine(i)]);
+        assert!((state.correlation(INFO.rate).unwrap() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn inverted_channels_anticorrelate() {
+        let state = analyse(1.0, |i| [sine(i), -sine(i)]);
+        assert!((state.correlation(INFO.rate).unwrap() + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn unrelated_noise_does_not_correlate() {
+        let mut state = 0x1234_5678u32;
+        let mut noise = move || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 8) as f32 / (1u32 << 24) as f32 - 0.5
+        };
+        let state = analyse(2.0, |_| [noise(), noise()]);
+        assert!(state.correlation(INFO.rate).unwrap().abs() < 0.05);
+    }
+
+    #[test]
+    fn silence_has_no_correlation() {
+        let state = analyse(1.0, |_| [0.0, 0.0]);
+        assert_eq!(state.correlation(INFO.rate), None);
+    }
+
+    #[test]
+    fn frames_hold_at_most_the_decimated_points() {
+        let mut state = analyse(1.0, |i| [sine(i), sine(i)]);
+        assert_eq!(state.points.len(), POINTS_PER_FRAME);
+
+        // One frame interval of signal fills about a frame's worth again
+        std::mem::take(&mut state.points);
+        let frames = (INFO.rate as f64 * FRAME_INTERVAL.as_secs_f64()) as usize;
+        let block: Vec<f32> = (0..frames).flat_map(|i| [sine(i), sine(i)]).collect();
+        state.process(&block, INFO, 0, 1);
+        assert!(state.points.len() > POINTS_PER_FRAME * 9 / 10);
+        assert!(state.points.len() <= POINTS_PER_FRAME);
+    }
+
+    #[test]
+    fn points_are_side_and_mid() {
+        let mono = analyse(0.01, |_| [0.5, 0.5]);
+        let inverted = analyse(0.01, |_| [0.5, -0.5]);
+        let left_only = analyse(0.01, |_| [0.5, 0.0]);
+        let expected = [
+            (mono, [0.0, 0.5 * 2f32.sqrt()]),
+            (inverted, [0.5 * 2f32.sqrt(), 0.0]),
+            (left_only, [0.5 * FRAC_1_SQRT_2, 0.5 * FRAC_1_SQRT_2]),
+        ];
+        for (state, [side, mid]) in expected {
+            assert!(!state.points.is_empty());
+            for point in &state.points {
+                assert!((point[0] - side).abs() < 1e-6 && (point[1] - mid).abs() < 1e-6, "{:?}", point);
+            }
+        }
+    }
+}
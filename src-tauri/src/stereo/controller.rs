@@ -0,0 +1,18 @@
+use tauri::AppHandle;
+use super::analysis::{self, StereoAnalysisStatus};
+
+/// Frames arrive as "stereo-analysis" events, about 30 per second, until stopped
+#[tauri::command]
+pub fn start_stereo_analysis(app_handle: AppHandle) -> Result<StereoAnalysisStatus, String> {
+    analysis::start(&app_handle)
+}
+
+#[tauri::command]
+pub fn stop_stereo_analysis() -> Result<(), String> {
+    analysis::stop()
+}
+
+#[tauri::command]
+pub fn get_stereo_analysis_status() -> StereoAnalysisStatus {
+    analysis::get_status()
+}
@@ -1,9 +1,25 @@
-use std::collections::HashMap;
-use std::fs;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use once_cell::sync::Lazy;
 use serde::{Serialize, Deserialize};
 use tauri::AppHandle;
 use tauri::Manager;
+use crate::alsa::general::ControlValue;
+use crate::alsa::phantom::PhantomInterlocks;
+use crate::alsa::ramp::RampSettings;
+use crate::alsa::safety::OutputLimit;
+use crate::alsa::stereo_link::StereoLinkSettings;
+use crate::alsa::volume::OutputPair;
+use crate::hotkeys::bindings::HotkeyBinding;
+use crate::latency::measurement::LatencyResult;
+use crate::monitor::section::MonitorConfig;
+use crate::recorder::session::RecorderSettings;
+use crate::tuner::detector::TunerSettings;
+use super::{device, migration};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct InputChannelConfig {
@@ -12,15 +28,146 @@ pub struct InputChannelConfig {
     #[serde(default)]
     pub display_name_stereo: String,
     pub stereo_coupled: bool,
+    #[serde(default)]
+    pub ribbon_mic: bool,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Preset {
+    pub name: String,
+    pub values: BTreeMap<String, ControlValue>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AppSettings {
+    #[serde(default)]
+    pub close_to_tray: bool,
+    #[serde(default)]
+    pub hotkeys: Vec<HotkeyBinding>,
+    #[serde(default)]
+    pub monitor: MonitorConfig,
+    #[serde(default)]
+    pub output_limits: HashMap<OutputPair, OutputLimit>,
+    #[serde(default)]
+    pub phantom_interlocks: PhantomInterlocks,
+    #[serde(default)]
+    pub ramp: RampSettings,
+    #[serde(default)]
+    pub recorder: RecorderSettings,
+    /// Put the device back the way it was left once it is identified at startup
+    #[serde(default)]
+    pub restore_last_state: bool,
+    #[serde(default)]
+    pub stereo_link: StereoLinkSettings,
+    #[serde(default)]
+    pub tuner: TunerSettings,
+}
+
+/// The config as the rest of the app sees it: the connected device's channels, presets and
+/// last state together with the app-wide settings
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SoundCardConfig {
+    pub version: u32,
+    pub channels: HashMap<String, InputChannelConfig>,
+    #[serde(default)]
+    pub presets: Vec<Preset>,
+    #[serde(default)]
+    pub last_state: BTreeMap<String, ControlValue>,
+    #[serde(default)]
+    pub latency: Vec<LatencyResult>,
+    #[serde(default)]
+    pub settings: AppSettings,
+}
+
+/// Everything that belongs to one physical unit
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DeviceConfig {
+    #[serde(default)]
     pub channels: HashMap<String, InputChannelConfig>,
+    #[serde(default)]
+    pub presets: Vec<Preset>,
+    /// Control values when the app last closed
+    #[serde(default)]
+    pub last_state: BTreeMap<String, ControlValue>,
+    /// Round-trip latency measurements
+    #[serde(default)]
+    pub latency: Vec<LatencyResult>,
+}
+
+/// The file on disk, with per-device configs keyed by USB serial
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConfigDocument {
+    pub version: u32,
+    #[serde(default)]
+    pub settings: AppSettings,
+    #[serde(default)]
+    pub devices: BTreeMap<String, DeviceConfig>,
+}
+
+impl Default for ConfigDocument {
+    fn default() -> Self {
+        Self {
+            version: migration::CURRENT_VERSION,
+            settings: AppSettings::default(),
+            devices: BTreeMap::new(),
+        }
+    }
+}
+
+impl ConfigDocument {
+    /// A device seen for the first time starts from the config stored before devices were told apart.
+    fn device_config(&self, key: &str) -> SoundCardConfig {
+        let device = self
+            .devices
+            .get(key)
+            .or_else(|| self.devices.get(device::UNKNOWN_DEVICE))
+            .cloned()
+            .unwrap_or_default();
+
+        SoundCardConfig {
+            version: self.version,
+            channels: device.channels,
+            presets: device.presets,
+            last_state: device.last_state,
+            latency: device.latency,
+            settings: self.settings.clone(),
+        }
+    }
+
+    fn store(&mut self, key: &str, config: SoundCardConfig) {
+        // The first device to save takes the pre-device config over instead of copying it
+        if key != device::UNKNOWN_DEVICE && !self.devices.contains_key(key) {
+            self.devices.remove(device::UNKNOWN_DEVICE);
+        }
+
+        self.devices.insert(key.to_string(), DeviceConfig {
+            channels: config.channels,
+            presets: config.presets,
+            last_state: config.last_state,
+            latency: config.latency,
+        });
+        self.settings = config.settings;
+        self.version = migration::CURRENT_VERSION;
+    }
+}
+
+// How many last-good copies are kept, config.json.1 being the newest
+const BACKUP_COUNT: usize = 5;
+
+// Set when the config had to be restored from a backup, until the UI has shown it
+static RECOVERY_WARNING: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+/// The warning from the last recovery from a backup, cleared once read.
+pub fn take_recovery_warning() -> Option<String> {
+    RECOVERY_WARNING.lock().ok()?.take()
 }
 
 pub struct ConfigStorage {
     config_path: PathBuf,
+    // The unversioned file used before the config was versioned, migrated on first load
+    legacy_path: PathBuf,
+    // Held with an exclusive lock for every read-modify-write, also across processes
+    lock_path: PathBuf,
 }
 
 impl ConfigStorage {
@@ -31,25 +178,163 @@ impl ConfigStorage {
             .map_err(|e| format!("Failed to get app config directory: {}", e))?;
 
         fs::create_dir_all(&config_dir)?;
-        let config_path = config_dir.join("input-channels-conf-v1.json");
-        Ok(Self { config_path })
+        let config_path = config_dir.join("config.json");
+        let legacy_path = config_dir.join("input-channels-conf-v1.json");
+        let lock_path = config_dir.join("config.lock");
+        Ok(Self { config_path, legacy_path, lock_path })
     }
 
-    pub fn save_config(&self, config: &SoundCardConfig) -> Result<(), Box<dyn std::error::Error>> {
-        let json = serde_json::to_string_pretty(config)?;
-        fs::write(&self.config_path, json)?;
-        Ok(())
+    fn lock(&self) -> Result<File, Box<dyn std::error::Error>> {
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(&self.lock_path)?;
+        file.lock()?;
+        Ok(file)
     }
 
+    /// The config of the connected device, see `device::set_current`.
     pub fn load_config(&self) -> Result<SoundCardConfig, Box<dyn std::error::Error>> {
-        if self.config_path.exists() {
-            let json = fs::read_to_string(&self.config_path)?;
-            let config: SoundCardConfig = serde_json::from_str(&json)?;
-            Ok(config)
+        let _lock = self.lock()?;
+        Ok(self.read_document()?.device_config(&device::current_key()))
+    }
+
+    /// Loads, changes and saves the config under one lock, so concurrent changes can't overwrite each other.
+    pub fn update<T>(&self, change: impl FnOnce(&mut SoundCardConfig) -> T) -> Result<T, Box<dyn std::error::Error>> {
+        let _lock = self.lock()?;
+        let key = device::current_key();
+        let mut document = self.read_document()?;
+        let mut config = document.device_config(&key);
+
+        let result = change(&mut config);
+        document.store(&key, config);
+        self.write_document(&document)?;
+        Ok(result)
+    }
+
+    /// Writes to a temp file, syncs it and renames it over the config, so a crash leaves either
+    /// the old file or the new one. The previous file is rotated into the backups first.
+    fn write_document(&self, document: &ConfigDocument) -> Result<(), Box<dyn std::error::Error>> {
+        let json = serde_json::to_string_pretty(document)?;
+        let temp_path = self.config_path.with_extension("json.tmp");
+
+        let mut file = File::create(&temp_path)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+
+        if self.config_path.exists() && parse_document(&self.config_path).is_ok() {
+            self.rotate_backups()?;
+        }
+
+        fs::rename(&temp_path, &self.config_path)?;
+        if let Some(dir) = self.config_path.parent() {
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn backup_path(&self, index: usize) -> PathBuf {
+        self.config_path.with_extension(format!("json.{}", index))
+    }
+
+    fn rotate_backups(&self) -> Result<(), Box<dyn std::error::Error>> {
+        for index in (1..BACKUP_COUNT).rev() {
+            let from = self.backup_path(index);
+            if from.exists() {
+                fs::rename(&from, self.backup_path(index + 1))?;
+            }
+        }
+        fs::copy(&self.config_path, self.backup_path(1))?;
+        Ok(())
+    }
+
+    fn read_document(&self) -> Result<ConfigDocument, Box<dyn std::error::Error>> {
+        let source = if self.config_path.exists() {
+            &self.config_path
+        } else if self.legacy_path.exists() {
+            &self.legacy_path
         } else {
-            Ok(SoundCardConfig {
-                channels: HashMap::new(),
-            })
+            return Ok(ConfigDocument::default());
+        };
+
+        let (document, version) = match parse_document(source) {
+            Ok(parsed) => parsed,
+            // A file from a newer app isn't corrupt, falling back would throw its changes away
+            Err(e) if e.newer_version => return Err(e.message.into()),
+            Err(e) if source == &self.config_path => return self.recover(&e.message),
+            Err(e) => return Err(e.message.into()),
+        };
+
+        if version < migration::CURRENT_VERSION || source == &self.legacy_path {
+            self.upgrade_file(source, version, &document)?;
+        }
+        Ok(document)
+    }
+
+    /// Restores the newest backup that still parses, keeping the broken file for inspection.
+    fn recover(&self, reason: &str) -> Result<ConfigDocument, Box<dyn std::error::Error>> {
+        for index in 1..=BACKUP_COUNT {
+            let backup = self.backup_path(index);
+            let Ok((document, _)) = parse_document(&backup) else {
+                continue;
+            };
+
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            let corrupt_path = self.config_path.with_extension(format!("json.corrupt-{}", timestamp));
+            fs::rename(&self.config_path, &corrupt_path)?;
+            self.write_document(&document)?;
+
+            let warning = format!(
+                "The config file could not be read ({}). Settings were restored from backup {}, the broken file was kept as {}.",
+                reason,
+                backup.display(),
+                corrupt_path.display()
+            );
+            eprintln!("{}", warning);
+            if let Ok(mut pending) = RECOVERY_WARNING.lock() {
+                *pending = Some(warning);
+            }
+            return Ok(document);
         }
+
+        Err(format!("The config file could not be read and there is no usable backup: {}", reason).into())
     }
-}
\ No newline at end of file
+
+    /// Keeps the old file as a backup next to the new one and writes the migrated config.
+    fn upgrade_file(&self, source: &Path, version: u32, document: &ConfigDocument) -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = source.file_name().and_then(|name| name.to_str()).unwrap_or("config.json");
+        let backup_path = source.with_file_name(format!("{}.v{}.bak", file_name, version));
+
+        fs::copy(source, &backup_path)?;
+        self.write_document(document)?;
+        if source != self.config_path {
+            fs::remove_file(source)?;
+        }
+
+        println!("Upgraded config from version {}, the old file is kept as {}", version, backup_path.display());
+        Ok(())
+    }
+}
+
+struct ParseError {
+    message: String,
+    newer_version: bool,
+}
+
+/// Reads and migrates a config file, returning it with the version it was stored as.
+fn parse_document(path: &Path) -> Result<(ConfigDocument, u32), ParseError> {
+    let invalid = |message: String| ParseError { message, newer_version: false };
+
+    let json = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+    let document: serde_json::Value = serde_json::from_str(&json).map_err(|e| invalid(e.to_string()))?;
+    let version = migration::document_version(&document).map_err(invalid)?;
+
+    if version > migration::CURRENT_VERSION {
+        return Err(ParseError {
+            message: migration::migrate(document).err().unwrap_or_default(),
+            newer_version: true,
+        });
+    }
+
+    let migrated = migration::migrate(document).map_err(invalid)?;
+    let document = serde_json::from_value(migrated).map_err(|e| invalid(e.to_string()))?;
+    Ok((document, version))
+}
@@ -1,25 +1,44 @@
-use tauri::AppHandle;
-use super::config::{ConfigStorage, InputChannelConfig};
+use crate::AppState;
+use crate::alsa::{general, stereo_link};
+use crate::history::journal::{self, Origin, Setting};
+use tauri::{AppHandle, Emitter, State};
+use super::config::{self, ConfigStorage, InputChannelConfig};
+use super::device::{self, DeviceIdentity};
+use super::exchange::{self, ImportReport};
 
 #[tauri::command]
 pub fn save_channel_config(
     app_handle: AppHandle,
+    state: State<AppState>,
     control_name: String,
     display_name: String,
     display_name_stereo: String,
     stereo_coupled: bool,
+    ribbon_mic: Option<bool>,
 ) -> Result<(), String> {
+    let card_number = &state.alsa_card_number;
     let storage = ConfigStorage::new(&app_handle).map_err(|e| e.to_string())?;
-    let mut config = storage.load_config().map_err(|e| e.to_string())?;
+    let previous = storage.load_config().map_err(|e| e.to_string())?.channels.remove(&control_name);
 
-    config.channels.insert(control_name.clone(), InputChannelConfig {
-        control_name,
+    let channel = InputChannelConfig {
+        control_name: control_name.clone(),
         display_name,
         display_name_stereo,
         stereo_coupled,
-    });
+        // Older clients don't send the ribbon flag, keep whatever was stored
+        ribbon_mic: ribbon_mic.unwrap_or_else(|| previous.is_some_and(|channel| channel.ribbon_mic)),
+    };
 
-    storage.save_config(&config).map_err(|e| e.to_string())
+    // Linking mirrors the left input onto its partner, so any control can change along with it
+    let controls: Vec<String> = if stereo_coupled {
+        general::get_control_values(card_number)?.into_keys().collect()
+    } else {
+        Vec::new()
+    };
+    let settings = [Setting::Channel(control_name.clone())];
+    journal::record_settings(&app_handle, Origin::Ui, card_number, &settings, &controls, || {
+        stereo_link::save_channel(&app_handle, card_number, &control_name, Some(channel))
+    })
 }
 
 #[tauri::command]
@@ -35,3 +54,49 @@ pub fn load_all_channels(app_handle: AppHandle) -> Result<Vec<InputChannelConfig
     let config = storage.load_config().map_err(|e| e.to_string())?;
     Ok(config.channels.values().cloned().collect())
 }
+
+/// Set once if the config had to be restored from a backup, so the UI can tell the user.
+#[tauri::command]
+pub fn get_config_warning() -> Option<String> {
+    config::take_recovery_warning()
+}
+
+#[tauri::command]
+pub fn export_preset(app_handle: AppHandle, state: State<AppState>, index: usize, path: String) -> Result<(), String> {
+    exchange::export_preset(&app_handle, &state.alsa_card_number, index, &path)
+}
+
+#[tauri::command]
+pub fn export_config(app_handle: AppHandle, state: State<AppState>, path: String) -> Result<(), String> {
+    exchange::export_config(&app_handle, &state.alsa_card_number, &path)
+}
+
+/// Imports a .json or .toml export, see `exchange::import_file`.
+#[tauri::command]
+pub fn import_file(app_handle: AppHandle, state: State<AppState>, path: String, include_settings: Option<bool>) -> Result<ImportReport, String> {
+    let report = exchange::import_file(&app_handle, &state.alsa_card_number, &path, include_settings.unwrap_or(false))?;
+    crate::tray::menu::refresh(&app_handle);
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn get_device_identity() -> Option<DeviceIdentity> {
+    device::current()
+}
+
+#[tauri::command]
+pub fn restore_last_state(app_handle: AppHandle, state: State<AppState>) -> Result<(), String> {
+    device::restore_last_state(&app_handle, &state.alsa_card_number)?;
+    let _ = app_handle.emit("controls-changed", ());
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_restore_last_state_on_start(app_handle: AppHandle) -> Result<bool, String> {
+    device::restore_on_start_enabled(&app_handle)
+}
+
+#[tauri::command]
+pub fn set_restore_last_state_on_start(app_handle: AppHandle, enabled: bool) -> Result<(), String> {
+    device::set_restore_on_start(&app_handle, enabled)
+}
@@ -0,0 +1,141 @@
+use crate::alsa::general::{self, ControlValue};
+use crate::alsa::virtual_volume;
+use crate::monitor::section;
+use crate::preset::snapshot;
+use crate::storage::config::ConfigStorage;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+/// Key for configs stored before devices were told apart, taken over by the first device that loads
+pub const UNKNOWN_DEVICE: &str = "unknown";
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DeviceIdentity {
+    /// What the device's config is stored under: its serial, or the USB port when it has none
+    pub key: String,
+    pub serial: Option<String>,
+    /// USB port path in sysfs, e.g. "1-2"
+    pub port: Option<String>,
+}
+
+static CURRENT: Lazy<Mutex<Option<DeviceIdentity>>> = Lazy::new(|| Mutex::new(None));
+
+fn read_trimmed(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+/// Finds the card's USB device in sysfs from the "bus/device" pair ALSA reports in usbbus.
+pub fn identify(card_index: &str) -> Result<DeviceIdentity, String> {
+    let usbbus = read_trimmed(&format!("/proc/asound/card{}/usbbus", card_index))
+        .ok_or_else(|| format!("Card {} has no USB bus info", card_index))?;
+
+    let (bus, device) = usbbus
+        .split_once('/')
+        .and_then(|(bus, device)| Some((bus.parse::<u32>().ok()?, device.parse::<u32>().ok()?)))
+        .ok_or_else(|| format!("Unexpected usbbus contents: {}", usbbus))?;
+
+    let entries = fs::read_dir("/sys/bus/usb/devices").map_err(|e| format!("Failed to read USB devices: {}", e))?;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(dir) = path.to_str() else {
+            continue;
+        };
+
+        let matches = |file: &str, wanted: u32| {
+            read_trimmed(&format!("{}/{}", dir, file)).and_then(|v| v.parse::<u32>().ok()) == Some(wanted)
+        };
+        if !matches("busnum", bus) || !matches("devnum", device) {
+            continue;
+        }
+
+        let serial = read_trimmed(&format!("{}/serial", dir));
+        let port = entry.file_name().to_str().map(|name| name.to_string());
+        let key = match (&serial, &port) {
+            (Some(serial), _) => serial.clone(),
+            (None, Some(port)) => format!("port-{}", port),
+            (None, None) => UNKNOWN_DEVICE.to_string(),
+        };
+
+        return Ok(DeviceIdentity { key, serial, port });
+    }
+
+    Err(format!("USB device {} not found in sysfs", usbbus))
+}
+
+/// Identifies the connected card, every config load and save afterwards uses its settings.
+pub fn set_current(card_index: &str) -> Result<DeviceIdentity, String> {
+    let identity = identify(card_index)?;
+    println!("Using config for device {}", identity.key);
+    *CURRENT.lock().map_err(|e| e.to_string())? = Some(identity.clone());
+    Ok(identity)
+}
+
+pub fn current() -> Option<DeviceIdentity> {
+    CURRENT.lock().ok()?.clone()
+}
+
+pub fn current_key() -> String {
+    current().map(|identity| identity.key).unwrap_or_else(|| UNKNOWN_DEVICE.to_string())
+}
+
+/// Stores the device's control values so they can be brought back next time. Controls held by
+/// the monitor section are stored at the level they return to.
+pub fn save_last_state(app: &AppHandle, card_index: &str) -> Result<(), String> {
+    let mut values = general::get_control_values(card_index)?;
+    // Held levels are client values, so they go over the virtual ones
+    virtual_volume::logical_values(&mut values);
+    for (name, value) in values.iter_mut() {
+        if let Some(held) = section::held_value(name) {
+            *value = ControlValue::Volume(held);
+        }
+    }
+
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    storage.update(|config| config.last_state = values).map_err(|e| e.to_string())
+}
+
+/// The stored values to bring back. 48V is left out, it only ever changes through its own
+/// switch and the phantom interlocks.
+fn restorable(mut values: BTreeMap<String, ControlValue>) -> BTreeMap<String, ControlValue> {
+    values.retain(|name, _| !name.ends_with(" 48V"));
+    values
+}
+
+pub fn restore_last_state(app: &AppHandle, card_index: &str) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+
+    if config.last_state.is_empty() {
+        return Err(format!("No state stored for device {}", current_key()));
+    }
+    snapshot::apply_values(app, card_index, &restorable(config.last_state))
+}
+
+pub fn restore_on_start_enabled(app: &AppHandle) -> Result<bool, String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    Ok(config.settings.restore_last_state)
+}
+
+pub fn set_restore_on_start(app: &AppHandle, enabled: bool) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    storage.update(|config| config.settings.restore_last_state = enabled).map_err(|e| e.to_string())
+}
+
+/// Restores the identified device's last state at startup, if that is turned on. Returns
+/// whether anything was restored.
+pub fn restore_on_start(app: &AppHandle, card_index: &str) -> Result<bool, String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    if !config.settings.restore_last_state || config.last_state.is_empty() {
+        return Ok(false);
+    }
+
+    snapshot::apply_values(app, card_index, &restorable(config.last_state))?;
+    Ok(true)
+}
@@ -0,0 +1,346 @@
+use crate::alsa::general::{self, ControlValue};
+use crate::alsa::{db_scale, ramp, safety, stereo_link, virtual_volume};
+use crate::hotkeys::bindings;
+use crate::monitor::section;
+use crate::storage::config::{ConfigStorage, InputChannelConfig, Preset, SoundCardConfig};
+use crate::storage::migration;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+/// Marks a file as one of ours, so importing some unrelated JSON fails with a clear message
+const DOCUMENT_KIND: &str = "rme-babyface-control";
+const DEVICE_MODEL: &str = "RME Babyface Pro";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeviceInfo {
+    pub model: String,
+    pub usb_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExportContent {
+    Preset { preset: Preset },
+    Config { config: Box<SoundCardConfig> },
+}
+
+/// A shareable file holding one preset or the whole config
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExportDocument {
+    pub kind: String,
+    /// Version of the config schema the content was written with
+    pub schema_version: u32,
+    pub app_version: String,
+    pub device: DeviceInfo,
+    /// Unix time in seconds
+    pub exported_at: u64,
+    pub content: ExportContent,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ImportReport {
+    /// Names of the imported presets
+    pub presets: Vec<String>,
+    pub channels_imported: usize,
+    pub settings_imported: bool,
+    /// Controls in the file that the connected device doesn't have, these were left out
+    pub missing_controls: Vec<String>,
+    /// Values the device can't take, left out as well
+    pub invalid_values: Vec<String>,
+    /// Set when the file was exported from a different device model
+    pub device_mismatch: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileFormat {
+    Json,
+    Toml,
+}
+
+fn format_for(path: &Path) -> FileFormat {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("toml") => FileFormat::Toml,
+        _ => FileFormat::Json,
+    }
+}
+
+fn device_info(card_index: &str) -> DeviceInfo {
+    let usb_id = fs::read_to_string(format!("/proc/asound/card{}/usbid", card_index))
+        .map(|id| id.trim().to_string())
+        .unwrap_or_default();
+
+    DeviceInfo {
+        model: DEVICE_MODEL.to_string(),
+        usb_id,
+    }
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn write_document(path: &Path, document: &ExportDocument) -> Result<(), String> {
+    let text = match format_for(path) {
+        FileFormat::Json => serde_json::to_string_pretty(document).map_err(|e| e.to_string())?,
+        FileFormat::Toml => toml::to_string_pretty(document).map_err(|e| e.to_string())?,
+    };
+    fs::write(path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn export(card_index: &str, path: &str, content: ExportContent) -> Result<(), String> {
+    let document = ExportDocument {
+        kind: DOCUMENT_KIND.to_string(),
+        schema_version: migration::CURRENT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        device: device_info(card_index),
+        exported_at: unix_secs(),
+        content,
+    };
+    write_document(Path::new(path), &document)
+}
+
+pub fn export_preset(app: &AppHandle, card_index: &str, index: usize, path: &str) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    let preset = config
+        .presets
+        .get(index)
+        .cloned()
+        .ok_or_else(|| format!("No preset in slot {}", index + 1))?;
+
+    export(card_index, path, ExportContent::Preset { preset })
+}
+
+pub fn export_config(app: &AppHandle, card_index: &str, path: &str) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    export(card_index, path, ExportContent::Config { config: Box::new(config) })
+}
+
+/// Reads an export in either format, bringing config content up to the current schema first.
+fn read_document(path: &Path) -> Result<ExportDocument, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut document: Value = match format_for(path) {
+        FileFormat::Json => serde_json::from_str(&text).map_err(|e| e.to_string())?,
+        FileFormat::Toml => toml::from_str(&text).map_err(|e| e.to_string())?,
+    };
+
+    if document.get("kind").and_then(Value::as_str) != Some(DOCUMENT_KIND) {
+        return Err(format!("{} is not an RME Babyface control export", path.display()));
+    }
+
+    let schema_version = document
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .ok_or("The export has no schema version")? as u32;
+    if schema_version > migration::CURRENT_VERSION {
+        return Err(format!(
+            "The export uses schema version {}, which is newer than this app understands (version {})",
+            schema_version, migration::CURRENT_VERSION
+        ));
+    }
+
+    // Exports hold one device's view of the config, which has only ever gained defaulted
+    // fields, so unlike the file on disk it needs no migration beyond the version bump
+    if let Some(config) = document.pointer_mut("/content/config") {
+        config["version"] = Value::from(migration::CURRENT_VERSION);
+    }
+    document["schema_version"] = Value::from(migration::CURRENT_VERSION);
+
+    serde_json::from_value(document).map_err(|e| format!("The export is not valid: {}", e))
+}
+
+/// Picks up imported settings in every module that caches them.
+fn reload_settings(app: &AppHandle) {
+    let results = [
+        ("ramp settings", ramp::load_settings(app)),
+        ("output limits", safety::load_limits(app)),
+        ("stereo links", stereo_link::load_links(app)),
+        ("monitor config", section::load_config(app)),
+    ];
+    for (what, result) in results {
+        if let Err(e) = result {
+            eprintln!("Failed to reload {}: {}", what, e);
+        }
+    }
+
+    match bindings::load_bindings(app) {
+        Ok(hotkeys) => {
+            bindings::register_all(app, &hotkeys);
+        }
+        Err(e) => eprintln!("Failed to reload hotkeys: {}", e),
+    }
+}
+
+/// What an import is checked against
+struct DeviceControls {
+    values: BTreeMap<String, ControlValue>,
+    items: HashMap<String, Vec<String>>,
+}
+
+/// Allowed items of every enumerated control, from amixer's "Items: 'Low' 'High'" line
+fn control_items(card_index: &str) -> Result<HashMap<String, Vec<String>>, String> {
+    let item_re = Regex::new(r"'([^']*)'").unwrap();
+
+    Ok(general::get_soundcard_controls(card_index)?
+        .into_iter()
+        .filter_map(|(name, info)| {
+            let line = info.iter().find_map(|line| line.trim().strip_prefix("Items:"))?;
+            let items = item_re.captures_iter(line).map(|caps| caps[1].to_string()).collect();
+            Some((name, items))
+        })
+        .collect())
+}
+
+/// Why a value can't be written to the device, if it can't: it has to be the same kind of value
+/// the control holds, within its range, or one of its items.
+fn invalid_reason(name: &str, value: &ControlValue, existing: &ControlValue, items: &HashMap<String, Vec<String>>) -> Option<String> {
+    match (value, existing) {
+        (ControlValue::Volume(level), ControlValue::Volume(_)) => {
+            let range = db_scale::get_range(name);
+            (*level < range.raw_min || *level > range.raw_max)
+                .then(|| format!("{}: {} is outside {} to {}", name, level, range.raw_min, range.raw_max))
+        }
+        (ControlValue::Switch(_), ControlValue::Switch(_)) => None,
+        (ControlValue::Item(item), ControlValue::Item(_)) => match items.get(name) {
+            Some(allowed) if !allowed.contains(item) => Some(format!("{}: '{}' is not one of {}", name, item, allowed.join(", "))),
+            _ => None,
+        },
+        _ => Some(format!("{}: {:?} doesn't fit a control holding {:?}", name, value, existing)),
+    }
+}
+
+/// Drops values for controls the device doesn't have, and values it can't take.
+fn retain_valid(values: &mut BTreeMap<String, ControlValue>, device: &DeviceControls, missing: &mut Vec<String>, invalid: &mut Vec<String>) {
+    values.retain(|name, value| {
+        let Some(existing) = device.values.get(name) else {
+            missing.push(name.clone());
+            return false;
+        };
+        match invalid_reason(name, value, existing, &device.items) {
+            Some(reason) => {
+                invalid.push(reason);
+                false
+            }
+            None => true,
+        }
+    });
+}
+
+/// Adds a preset, replacing any with the same name.
+fn merge_preset(config: &mut SoundCardConfig, preset: Preset) {
+    match config.presets.iter().position(|p| p.name == preset.name) {
+        Some(index) => config.presets[index] = preset,
+        None => config.presets.push(preset),
+    }
+}
+
+/// Imports an exported preset or config. Presets are added, replacing any with the same name.
+/// From a config, channels are merged in the same way, and the app settings (hotkeys, limits,
+/// interlocks...) are only taken over if `include_settings` is set. Controls the connected
+/// device doesn't have, and values it can't take, are left out and listed in the report.
+pub fn import_file(app: &AppHandle, card_index: &str, path: &str, include_settings: bool) -> Result<ImportReport, String> {
+    let document = read_document(Path::new(path))?;
+    let mut values = general::get_control_values(card_index)?;
+    virtual_volume::logical_values(&mut values);
+    let device = DeviceControls {
+        values,
+        items: control_items(card_index)?,
+    };
+
+    let local_device = device_info(card_index);
+    let exported = &document.device;
+    let different_usb_id = !exported.usb_id.is_empty() && !local_device.usb_id.is_empty() && exported.usb_id != local_device.usb_id;
+    let device_mismatch = (exported.model != local_device.model || different_usb_id).then(|| {
+        format!(
+            "Exported from a {} ({}), this is a {} ({})",
+            exported.model, exported.usb_id, local_device.model, local_device.usb_id
+        )
+    });
+
+    let mut missing_controls = Vec::new();
+    let mut invalid_values = Vec::new();
+    let mut report = ImportReport {
+        presets: Vec::new(),
+        channels_imported: 0,
+        settings_imported: false,
+        missing_controls: Vec::new(),
+        invalid_values: Vec::new(),
+        device_mismatch,
+    };
+
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+
+    match document.content {
+        ExportContent::Preset { mut preset } => {
+            retain_valid(&mut preset.values, &device, &mut missing_controls, &mut invalid_values);
+            report.presets.push(preset.name.clone());
+
+            storage.update(|config| merge_preset(config, preset)).map_err(|e| e.to_string())?;
+        }
+        ExportContent::Config { config: imported } => {
+            let SoundCardConfig {
+                channels,
+                mut presets,
+                settings,
+                ..
+            } = *imported;
+
+            for preset in &mut presets {
+                retain_valid(&mut preset.values, &device, &mut missing_controls, &mut invalid_values);
+                report.presets.push(preset.name.clone());
+            }
+
+            // Channel configs are keyed by input, e.g. "Mic-AN1"
+            let channels: Vec<_> = channels
+                .into_iter()
+                .filter(|(input, _)| {
+                    let exists = device.values.keys().any(|name| name.starts_with(input.as_str()));
+                    if !exists {
+                        missing_controls.push(input.clone());
+                    }
+                    exists
+                })
+                .map(|(input, channel)| {
+                    let channel = InputChannelConfig {
+                        control_name: input.clone(),
+                        ..channel
+                    };
+                    (input, channel)
+                })
+                .collect();
+
+            report.channels_imported = channels.len();
+            report.settings_imported = include_settings;
+            storage
+                .update(|config| {
+                    config.channels.extend(channels);
+                    for preset in presets {
+                        merge_preset(config, preset);
+                    }
+                    if include_settings {
+                        config.settings = settings;
+                    }
+                })
+                .map_err(|e| e.to_string())?;
+
+            if include_settings {
+                reload_settings(app);
+            } else if let Err(e) = stereo_link::load_links(app) {
+                eprintln!("Failed to reload stereo links: {}", e);
+            }
+        }
+    }
+
+    missing_controls.sort();
+    missing_controls.dedup();
+    report.missing_controls = missing_controls;
+    report.invalid_values = invalid_values;
+    Ok(report)
+}
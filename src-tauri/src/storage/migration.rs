@@ -0,0 +1,209 @@
+use super::device::UNKNOWN_DEVICE;
+use serde_json::{json, Map, Value};
+
+/// Version of the config document this build reads and writes
+pub const CURRENT_VERSION: u32 = 3;
+
+type Migration = fn(Value) -> Result<Value, String>;
+
+// MIGRATIONS[n] upgrades a version n + 1 document to version n + 2
+const MIGRATIONS: [Migration; 2] = [v1_to_v2, v2_to_v3];
+
+/// v1 is the original input-channels-conf-v1.json, which had no version field and at first only
+/// held the channel map. v2 is the single config document with channels, presets and settings.
+fn v1_to_v2(document: Value) -> Result<Value, String> {
+    let Value::Object(mut fields) = document else {
+        return Err("Version 1 config is not a JSON object".to_string());
+    };
+
+    let channels = fields.remove("channels").unwrap_or_else(|| Value::Object(Map::new()));
+    let presets = fields.remove("presets").unwrap_or_else(|| json!([]));
+    let settings = fields.remove("settings").unwrap_or_else(|| json!({}));
+
+    Ok(json!({
+        "version": 2,
+        "channels": channels,
+        "presets": presets,
+        "settings": settings,
+    }))
+}
+
+/// v3 keeps channels and presets per device, keyed by USB serial. The serial of the device a v2
+/// file belonged to isn't known, so they go under "unknown" until the first device takes them over.
+fn v2_to_v3(document: Value) -> Result<Value, String> {
+    let Value::Object(mut fields) = document else {
+        return Err("Version 2 config is not a JSON object".to_string());
+    };
+
+    let channels = fields.remove("channels").unwrap_or_else(|| Value::Object(Map::new()));
+    let presets = fields.remove("presets").unwrap_or_else(|| json!([]));
+    let settings = fields.remove("settings").unwrap_or_else(|| json!({}));
+
+    Ok(json!({
+        "version": 3,
+        "settings": settings,
+        "devices": {
+            UNKNOWN_DEVICE: {
+                "channels": channels,
+                "presets": presets,
+            },
+        },
+    }))
+}
+
+/// Documents without a version field predate versioning and are version 1.
+pub fn document_version(document: &Value) -> Result<u32, String> {
+    match document.get("version") {
+        None => Ok(1),
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|v| *v >= 1)
+            .ok_or_else(|| format!("Config has an invalid version field: {}", version)),
+    }
+}
+
+/// Upgrades a document to the current version, one step at a time.
+pub fn migrate(mut document: Value) -> Result<Value, String> {
+    let mut version = document_version(&document)?;
+
+    if version > CURRENT_VERSION {
+        return Err(format!(
+            "The config file is version {}, which is newer than this app understands (version {}). Update the app, or move the file aside to start over.",
+            version, CURRENT_VERSION
+        ));
+    }
+
+    while version < CURRENT_VERSION {
+        let step = MIGRATIONS[(version - 1) as usize];
+        document = step(document).map_err(|e| format!("Migrating config from version {} failed: {}", version, e))?;
+        version += 1;
+        println!("Migrated config to version {}", version);
+    }
+
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::config::ConfigDocument;
+
+    // An input-channels-conf-v1.json as the first releases wrote it
+    const V1_FILE: &str = include_str!("../../tests/fixtures/config-v1.json");
+    // A single-device config with channels, presets and settings
+    const V2_FILE: &str = include_str!("../../tests/fixtures/config-v2.json");
+
+    fn v1_document() -> Value {
+        serde_json::from_str(V1_FILE).unwrap()
+    }
+
+    fn v2_document() -> Value {
+        serde_json::from_str(V2_FILE).unwrap()
+    }
+
+    #[test]
+    fn unversioned_file_is_version_1() {
+        assert_eq!(document_version(&v1_document()).unwrap(), 1);
+    }
+
+    #[test]
+    fn v1_file_keeps_its_channels() {
+        let migrated = v1_to_v2(v1_document()).unwrap();
+
+        assert_eq!(migrated["version"], 2);
+        assert_eq!(migrated["channels"], v1_document()["channels"]);
+        assert_eq!(migrated["presets"], json!([]));
+        assert_eq!(migrated["settings"], json!({}));
+    }
+
+    #[test]
+    fn v1_file_with_presets_and_settings_keeps_them() {
+        let mut document = v1_document();
+        document["presets"] = json!([{ "name": "Tracking", "values": {} }]);
+        document["settings"] = json!({ "close_to_tray": true });
+
+        let migrated = v1_to_v2(document).unwrap();
+        assert_eq!(migrated["presets"][0]["name"], "Tracking");
+        assert_eq!(migrated["settings"]["close_to_tray"], true);
+    }
+
+    #[test]
+    fn v1_file_migrates_to_current_version() {
+        let migrated = migrate(v1_document()).unwrap();
+        assert_eq!(document_version(&migrated).unwrap(), CURRENT_VERSION);
+    }
+
+    #[test]
+    fn v2_file_moves_channels_and_presets_to_the_unknown_device() {
+        let v2 = v2_document();
+        let migrated = v2_to_v3(v2.clone()).unwrap();
+
+        assert_eq!(migrated["version"], 3);
+        let device = &migrated["devices"][UNKNOWN_DEVICE];
+        assert_eq!(device["channels"], v2["channels"]);
+        assert_eq!(device["presets"], v2["presets"]);
+        assert!(migrated.get("channels").is_none());
+        assert!(migrated.get("presets").is_none());
+    }
+
+    #[test]
+    fn v2_file_keeps_its_settings_app_wide() {
+        let migrated = v2_to_v3(v2_document()).unwrap();
+        assert_eq!(migrated["settings"], v2_document()["settings"]);
+        assert_eq!(migrated["devices"].as_object().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn v2_file_without_presets_or_settings_gets_empty_ones() {
+        let migrated = v2_to_v3(json!({ "version": 2, "channels": {} })).unwrap();
+        assert_eq!(migrated["settings"], json!({}));
+        assert_eq!(migrated["devices"][UNKNOWN_DEVICE]["presets"], json!([]));
+    }
+
+    #[test]
+    fn v2_file_loads_as_the_current_document() {
+        let document: ConfigDocument = serde_json::from_value(migrate(v2_document()).unwrap()).unwrap();
+
+        assert_eq!(document.version, CURRENT_VERSION);
+        assert!(document.settings.close_to_tray);
+        assert_eq!(document.settings.monitor.dim_db, 15.0);
+        let device = &document.devices[UNKNOWN_DEVICE];
+        assert!(device.channels["Mic-AN1"].ribbon_mic);
+        assert_eq!(device.presets[0].name, "Tracking");
+        assert!(device.last_state.is_empty());
+    }
+
+    #[test]
+    fn v1_file_loads_as_the_current_document() {
+        let document: ConfigDocument = serde_json::from_value(migrate(v1_document()).unwrap()).unwrap();
+        let device = &document.devices[UNKNOWN_DEVICE];
+        assert_eq!(device.channels.len(), 2);
+        assert!(device.channels["Line-IN3"].stereo_coupled);
+    }
+
+    #[test]
+    fn current_version_is_left_alone() {
+        let document = json!({ "version": CURRENT_VERSION, "settings": {}, "devices": {} });
+        assert_eq!(migrate(document.clone()).unwrap(), document);
+    }
+
+    #[test]
+    fn newer_version_is_refused() {
+        let error = migrate(json!({ "version": CURRENT_VERSION + 1 })).unwrap_err();
+        assert!(error.contains("newer than this app understands"), "{}", error);
+    }
+
+    #[test]
+    fn invalid_versions_are_refused() {
+        for version in [json!(0), json!(-1), json!("2"), json!(1.5), json!(null)] {
+            assert!(document_version(&json!({ "version": version })).is_err(), "version {}", version);
+        }
+    }
+
+    #[test]
+    fn non_object_v1_file_is_refused() {
+        let error = migrate(json!(["Mic-AN1"])).unwrap_err();
+        assert!(error.contains("version 1"), "{}", error);
+    }
+}
@@ -1,4 +1,7 @@
 pub mod config;
 pub mod controller;
+pub mod device;
+pub mod exchange;
+pub mod migration;
 
 // pub use config::ConfigStorage;
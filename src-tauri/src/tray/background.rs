@@ -0,0 +1,43 @@
+use crate::storage::config::ConfigStorage;
+use tauri::{AppHandle, Manager, Window, WindowEvent};
+
+pub fn close_to_tray_enabled(app: &AppHandle) -> bool {
+    ConfigStorage::new(app)
+        .and_then(|storage| storage.load_config())
+        .map(|config| config.settings.close_to_tray)
+        .unwrap_or(false)
+}
+
+pub fn set_close_to_tray(app: &AppHandle, enabled: bool) -> Result<(), String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    storage.update(|config| config.settings.close_to_tray = enabled).map_err(|e| e.to_string())
+}
+
+pub fn toggle_close_to_tray(app: &AppHandle) -> Result<bool, String> {
+    let enabled = !close_to_tray_enabled(app);
+    set_close_to_tray(app, enabled)?;
+    Ok(enabled)
+}
+
+pub fn show_main_window(app: &AppHandle) -> Result<(), String> {
+    let window = app
+        .get_webview_window("main")
+        .ok_or_else(|| "Main window not found".to_string())?;
+
+    window.show().map_err(|e| e.to_string())?;
+    window.unminimize().map_err(|e| e.to_string())?;
+    window.set_focus().map_err(|e| e.to_string())
+}
+
+/// Hides the main window instead of closing it when close to tray is enabled,
+/// so the backend keeps serving the tray and other background features.
+pub fn handle_window_event(window: &Window, event: &WindowEvent) {
+    if let WindowEvent::CloseRequested { api, .. } = event {
+        if window.label() == "main" && close_to_tray_enabled(window.app_handle()) {
+            api.prevent_close();
+            if let Err(e) = window.hide() {
+                eprintln!("Failed to hide window: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,14 @@
+use tauri::AppHandle;
+use super::{background, menu};
+
+#[tauri::command]
+pub fn get_close_to_tray(app_handle: AppHandle) -> Result<bool, String> {
+    Ok(background::close_to_tray_enabled(&app_handle))
+}
+
+#[tauri::command]
+pub fn set_close_to_tray(app_handle: AppHandle, enabled: bool) -> Result<(), String> {
+    background::set_close_to_tray(&app_handle, enabled)?;
+    menu::refresh(&app_handle);
+    Ok(())
+}
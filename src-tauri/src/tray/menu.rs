@@ -0,0 +1,220 @@
+use crate::AppState;
+use crate::alsa::volume::{self, OutputPair};
+use crate::history::journal::{self, Origin, Setting};
+use crate::monitor::section::{self, Speaker};
+use crate::pipewire::{buffer_size, profile};
+use crate::preset::snapshot;
+use super::background;
+use tauri::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Emitter, Manager, Wry};
+
+const TRAY_ID: &str = "main-tray";
+const VOLUME_STEP_DB: f64 = 1.0;
+
+pub fn create(app: &AppHandle) -> tauri::Result<()> {
+    let menu = build_menu(app)?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("RME Babyface Control")
+        .menu(&menu)
+        .on_menu_event(handle_menu_event);
+
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+
+    builder.build(app)?;
+    Ok(())
+}
+
+/// Rebuilds the tray menu, e.g. after presets or profiles changed.
+pub fn refresh(app: &AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+
+    match build_menu(app) {
+        Ok(menu) => {
+            if let Err(e) = tray.set_menu(Some(menu)) {
+                eprintln!("Failed to update tray menu: {}", e);
+            }
+        }
+        Err(e) => eprintln!("Failed to build tray menu: {}", e),
+    }
+}
+
+fn build_output_menu(app: &AppHandle, output: &str, title: &str) -> tauri::Result<Submenu<Wry>> {
+    let up = MenuItem::with_id(app, format!("volume_up:{}", output), "Volume up", true, None::<&str>)?;
+    let down = MenuItem::with_id(app, format!("volume_down:{}", output), "Volume down", true, None::<&str>)?;
+
+    Submenu::with_items(app, title, true, &[&up, &down])
+}
+
+fn build_monitor_menu(app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+    let state = section::get_state();
+
+    let dim = CheckMenuItem::with_id(app, "monitor:dim", "Dim", true, state.dim, None::<&str>)?;
+    let mute = CheckMenuItem::with_id(app, "monitor:mute", "Mute", true, state.mute, None::<&str>)?;
+    let mono = CheckMenuItem::with_id(app, "monitor:mono", "Mono", true, state.mono, None::<&str>)?;
+    let speaker_a = CheckMenuItem::with_id(app, "speaker:a", "Speaker A", true, state.speaker == Speaker::A, None::<&str>)?;
+    let speaker_b = CheckMenuItem::with_id(app, "speaker:b", "Speaker B", true, state.speaker == Speaker::B, None::<&str>)?;
+    let speaker_both = CheckMenuItem::with_id(app, "speaker:both", "Both speakers", true, state.speaker == Speaker::Both, None::<&str>)?;
+
+    Submenu::with_items(
+        app,
+        "Monitor",
+        true,
+        &[&dim, &mute, &mono, &PredefinedMenuItem::separator(app)?, &speaker_a, &speaker_b, &speaker_both],
+    )
+}
+
+fn build_menu(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
+    let state = app.state::<AppState>();
+
+    let show = MenuItem::with_id(app, "show", "Show window", true, None::<&str>)?;
+    let main_out = build_output_menu(app, "main", "Main Out")?;
+    let headphones = build_output_menu(app, "headphones", "Headphones")?;
+    let monitor = build_monitor_menu(app)?;
+
+    let profiles = Submenu::new(app, "Profile", true)?;
+    let active_profile = profile::get_active_profile(&state.pipewire_card_id).unwrap_or_default();
+    for name in profile::get_profiles(&state.pipewire_card_id).unwrap_or_default() {
+        let is_active = name == active_profile;
+        let item = CheckMenuItem::with_id(app, format!("profile:{}", name), &name, true, is_active, None::<&str>)?;
+        profiles.append(&item)?;
+    }
+
+    let buffer_sizes = Submenu::new(app, "Buffer size", true)?;
+    let active_quantum = buffer_size::get_clock_quantum().ok();
+    for quantum in buffer_size::QUANTUMS {
+        let is_active = active_quantum == Some(quantum);
+        let item = CheckMenuItem::with_id(app, format!("quantum:{}", quantum), quantum.to_string(), true, is_active, None::<&str>)?;
+        buffer_sizes.append(&item)?;
+    }
+
+    let presets = Submenu::new(app, "Presets", true)?;
+    let preset_names = snapshot::list_presets(app).unwrap_or_default();
+    if preset_names.is_empty() {
+        presets.append(&MenuItem::new(app, "No presets saved", false, None::<&str>)?)?;
+    }
+    for (index, name) in preset_names.iter().enumerate() {
+        let item = MenuItem::with_id(app, format!("preset:{}", index), format!("{}. {}", index + 1, name), true, None::<&str>)?;
+        presets.append(&item)?;
+    }
+
+    let close_to_tray = CheckMenuItem::with_id(
+        app,
+        "close_to_tray",
+        "Keep running when window is closed",
+        true,
+        background::close_to_tray_enabled(app),
+        None::<&str>,
+    )?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+
+    Menu::with_items(
+        app,
+        &[
+            &show,
+            &PredefinedMenuItem::separator(app)?,
+            &main_out,
+            &headphones,
+            &monitor,
+            &PredefinedMenuItem::separator(app)?,
+            &profiles,
+            &buffer_sizes,
+            &presets,
+            &PredefinedMenuItem::separator(app)?,
+            &close_to_tray,
+            &quit,
+        ],
+    )
+}
+
+fn parse_output(name: &str) -> Result<OutputPair, String> {
+    match name {
+        "main" => Ok(OutputPair::Main),
+        "headphones" => Ok(OutputPair::Headphones),
+        _ => Err(format!("Unknown output: {}", name)),
+    }
+}
+
+fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    let id = event.id().as_ref().to_string();
+
+    match run_action(app, &id) {
+        Ok(()) => {
+            let _ = app.emit("controls-changed", ());
+        }
+        Err(e) => eprintln!("Tray action {} failed: {}", id, e),
+    }
+}
+
+fn run_action(app: &AppHandle, id: &str) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    let card_number = &state.alsa_card_number;
+
+    match id {
+        "show" => return background::show_main_window(app),
+        "close_to_tray" => return background::toggle_close_to_tray(app).map(|_| ()),
+        "quit" => {
+            app.exit(0);
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let (action, arg) = id.split_once(':').ok_or_else(|| format!("Unknown tray item: {}", id))?;
+
+    match action {
+        "volume_up" | "volume_down" => {
+            let output = parse_output(arg)?;
+            let step_db = if action == "volume_up" { VOLUME_STEP_DB } else { -VOLUME_STEP_DB };
+            let (left, right) = output.control_names();
+            let controls = [left.to_string(), right.to_string()];
+            journal::record(Origin::Tray, card_number, &controls, || volume::step_output_volume(card_number, output, step_db)).map(|_| ())
+        }
+        "monitor" => {
+            let toggle = match arg {
+                "dim" => section::toggle_dim,
+                "mute" => section::toggle_mute,
+                "mono" => section::toggle_mono,
+                _ => return Err(format!("Unknown tray item: {}", id)),
+            };
+            journal::record_settings(app, Origin::Tray, card_number, &[Setting::Monitor], &[], || toggle(card_number))?;
+            section::emit_state(app);
+            // Check items flip themselves when clicked, rebuild so they match the real state
+            refresh(app);
+            Ok(())
+        }
+        "speaker" => {
+            let speaker = match arg {
+                "a" => Speaker::A,
+                "b" => Speaker::B,
+                "both" => Speaker::Both,
+                _ => return Err(format!("Unknown tray item: {}", id)),
+            };
+            journal::record_settings(app, Origin::Tray, card_number, &[Setting::Monitor], &[], || section::set_speaker(card_number, speaker))?;
+            section::emit_state(app);
+            refresh(app);
+            Ok(())
+        }
+        "profile" => {
+            profile::set_profile(&state.pipewire_card_id, arg)?;
+            refresh(app);
+            Ok(())
+        }
+        "quantum" => {
+            let quantum = arg.parse::<u32>().map_err(|e| e.to_string())?;
+            buffer_size::set_clock_quantum(quantum)?;
+            refresh(app);
+            Ok(())
+        }
+        "preset" => {
+            let index = arg.parse::<usize>().map_err(|e| e.to_string())?;
+            snapshot::recall_preset(app, card_number, index).map(|_| ())
+        }
+        _ => Err(format!("Unknown tray item: {}", id)),
+    }
+}
@@ -0,0 +1,3 @@
+pub mod background;
+pub mod controller;
+pub mod menu;
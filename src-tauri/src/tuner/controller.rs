@@ -0,0 +1,23 @@
+use tauri::AppHandle;
+use super::detector::{self, TunerSettings, TunerStatus};
+
+/// Readings arrive as "tuner-reading" events until the tuner is stopped
+#[tauri::command]
+pub fn start_tuner(app_handle: AppHandle, input: String) -> Result<TunerStatus, String> {
+    detector::start(&app_handle, &input)
+}
+
+#[tauri::command]
+pub fn stop_tuner() -> Result<(), String> {
+    detector::stop()
+}
+
+#[tauri::command]
+pub fn get_tuner_status(app_handle: AppHandle) -> Result<TunerStatus, String> {
+    detector::get_status(&app_handle)
+}
+
+#[tauri::command]
+pub fn set_tuner_settings(app_handle: AppHandle, settings: TunerSettings) -> Result<(), String> {
+    detector::set_settings(&app_handle, settings)
+}
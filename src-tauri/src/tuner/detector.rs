@@ -0,0 +1,206 @@
+use crate::dsp::yin;
+use crate::pipewire::audio::{self, BlockInfo};
+use crate::pipewire::tap::{self, Tap, TapSource};
+use crate::storage::config::ConfigStorage;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+// Low B of a five-string bass up to the top of a guitar's range
+const MIN_HZ: f64 = 30.0;
+const MAX_HZ: f64 = 1500.0;
+const UPDATE_INTERVAL: Duration = Duration::from_millis(66);
+// Quieter than this nothing is being played
+const SILENCE_DB: f64 = -60.0;
+const MIN_REFERENCE_HZ: f64 = 400.0;
+const MAX_REFERENCE_HZ: f64 = 480.0;
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+fn default_reference_hz() -> f64 {
+    440.0
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TunerSettings {
+    /// Frequency of A4
+    #[serde(default = "default_reference_hz")]
+    pub reference_hz: f64,
+}
+
+impl Default for TunerSettings {
+    fn default() -> Self {
+        Self {
+            reference_hz: default_reference_hz(),
+        }
+    }
+}
+
+/// Emitted as "tuner-reading". The pitch fields are None while nothing clear is played.
+#[derive(Serialize, Debug, Clone)]
+pub struct TunerReading {
+    pub input: String,
+    pub frequency: Option<f64>,
+    /// Nearest note with its octave, e.g. "E2"
+    pub note: Option<String>,
+    /// How far the pitch is from the note, -50 to +50
+    pub cents: Option<f64>,
+    pub clarity: Option<f64>,
+    pub reference_hz: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TunerStatus {
+    pub running: bool,
+    pub input: Option<String>,
+    pub settings: TunerSettings,
+}
+
+struct Running {
+    input: String,
+    settings: Arc<Mutex<TunerSettings>>,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+    _tap: Tap,
+}
+
+#[derive(Default)]
+struct History {
+    rate: u32,
+    samples: VecDeque<f32>,
+}
+
+static RUNNING: Lazy<Mutex<Option<Running>>> = Lazy::new(|| Mutex::new(None));
+
+pub fn get_settings(app: &AppHandle) -> Result<TunerSettings, String> {
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    let config = storage.load_config().map_err(|e| e.to_string())?;
+    Ok(config.settings.tuner)
+}
+
+/// Stores the settings, a running tuner picks them up straight away.
+pub fn set_settings(app: &AppHandle, mut settings: TunerSettings) -> Result<(), String> {
+    if !settings.reference_hz.is_finite() {
+        return Err("The reference pitch has to be a number".to_string());
+    }
+    settings.reference_hz = settings.reference_hz.clamp(MIN_REFERENCE_HZ, MAX_REFERENCE_HZ);
+
+    let storage = ConfigStorage::new(app).map_err(|e| e.to_string())?;
+    storage.update(|config| config.settings.tuner = settings.clone()).map_err(|e| e.to_string())?;
+
+    if let Some(running) = RUNNING.lock().map_err(|e| e.to_string())?.as_ref() {
+        *running.settings.lock().map_err(|e| e.to_string())? = settings;
+    }
+    Ok(())
+}
+
+pub fn get_status(app: &AppHandle) -> Result<TunerStatus, String> {
+    let input = RUNNING.lock().map_err(|e| e.to_string())?.as_ref().map(|running| running.input.clone());
+    Ok(TunerStatus {
+        running: input.is_some(),
+        input,
+        settings: get_settings(app)?,
+    })
+}
+
+/// Note name, octave and cents from a frequency, with A4 at `reference_hz`
+fn nearest_note(frequency: f64, reference_hz: f64) -> (String, f64) {
+    let midi = 69.0 + 12.0 * (frequency / reference_hz).log2();
+    let note = midi.round();
+    let name = NOTE_NAMES[(note as i64).rem_euclid(12) as usize];
+    (format!("{}{}", name, (note as i64).div_euclid(12) - 1), (midi - note) * 100.0)
+}
+
+fn read(input: &str, history: &Mutex<History>, reference_hz: f64) -> TunerReading {
+    let mut reading = TunerReading {
+        input: input.to_string(),
+        frequency: None,
+        note: None,
+        cents: None,
+        clarity: None,
+        reference_hz,
+    };
+
+    let Some((rate, samples)) = history.lock().ok().filter(|h| h.rate > 0).map(|h| (h.rate, h.samples.iter().copied().collect::<Vec<f32>>())) else {
+        return reading;
+    };
+    if samples.len() < 2 * (rate as f64 / MIN_HZ) as usize {
+        return reading;
+    }
+    let rms = (samples.iter().map(|s| (*s as f64) * (*s as f64)).sum::<f64>() / samples.len() as f64).sqrt();
+    if 20.0 * rms.max(1e-10).log10() < SILENCE_DB {
+        return reading;
+    }
+
+    if let Some(pitch) = yin::detect(&samples, rate, MIN_HZ, MAX_HZ) {
+        let (note, cents) = nearest_note(pitch.frequency, reference_hz);
+        reading.frequency = Some(pitch.frequency);
+        reading.note = Some(note);
+        reading.cents = Some(cents);
+        reading.clarity = Some(pitch.clarity);
+    }
+    reading
+}
+
+/// Listens to the input and sends readings until stopped, replacing a running tuner.
+pub fn start(app: &AppHandle, input: &str) -> Result<TunerStatus, String> {
+    stop()?;
+
+    let (_, channel) = audio::capture_target(input)?;
+    let history = Arc::new(Mutex::new(History::default()));
+    let shared = history.clone();
+    let on_samples = Box::new(move |samples: &[f32], info: BlockInfo| {
+        let Ok(mut history) = shared.lock() else {
+            return;
+        };
+        if channel >= info.channels {
+            return;
+        }
+        history.rate = info.rate;
+        history.samples.extend(samples.chunks_exact(info.channels).map(|frame| frame[channel]));
+        // Two of the longest periods
+        let keep = 2 * (info.rate as f64 / MIN_HZ) as usize;
+        let excess = history.samples.len().saturating_sub(keep);
+        history.samples.drain(..excess);
+    });
+    let tap = tap::subscribe(TapSource::Inputs, on_samples)?;
+
+    let settings = Arc::new(Mutex::new(get_settings(app)?));
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let (app, settings, stop, input) = (app.clone(), settings.clone(), stop.clone(), input.to_string());
+        thread::Builder::new()
+            .name("tuner".to_string())
+            .spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    thread::sleep(UPDATE_INTERVAL);
+                    let reference_hz = settings.lock().map(|s| s.reference_hz).unwrap_or_else(|_| default_reference_hz());
+                    let _ = app.emit("tuner-reading", read(&input, &history, reference_hz));
+                }
+            })
+            .map_err(|e| format!("Failed to start the tuner: {}", e))?
+    };
+
+    *RUNNING.lock().map_err(|e| e.to_string())? = Some(Running {
+        input: input.to_string(),
+        settings,
+        stop,
+        thread,
+        _tap: tap,
+    });
+    get_status(app)
+}
+
+/// Stops listening, closing the capture when nothing else uses it.
+pub fn stop() -> Result<(), String> {
+    let running = RUNNING.lock().map_err(|e| e.to_string())?.take();
+    if let Some(running) = running {
+        running.stop.store(true, Ordering::Relaxed);
+        let _ = running.thread.join();
+    }
+    Ok(())
+}
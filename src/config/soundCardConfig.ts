@@ -157,16 +157,8 @@ export const babyfaceProConfCombatabilityMode: SoundcardConfig = {
             stereoCoupled: false
         }
     ],
-    outputs: [
-        {
-            displayName: "Headphones",
-            controlNameLeft: "PCM-AN1-PH3",
-            controlNameRight: "PCM-AN2-PH4",
-            routeNameLeft: "PH3",
-            routeNameRight: "PH4",
-            type: OutputType.HEADPHONES
-        }
-    ],
+    // No volume controls on these kernels, the backend scales the crosspoints feeding each output
+    outputs: babyfaceProConf.outputs,
     playback: { // Control names are all outputs that should be affected by PCM playback
         displayName: "Playback",
         controlNameMonitorLeft: "PCM-AN1-AN1",
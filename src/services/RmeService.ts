@@ -3,7 +3,7 @@ import { useRmeStore } from "../stores/rmeStore";
 import { formatControls } from "../utils/formatAlsaOutput";
 import { invoke } from "@tauri-apps/api/core";
 import { alsaToDB, dbToALSA } from "../utils/alsaValConversion";
-import { InputType, TauriInputChannelConfig } from "../types/config.types";
+import { DeviceCapabilities, HotkeyBinding, HotkeyConflict, InputType, TauriInputChannelConfig } from "../types/config.types";
 
 export class RmeService {
   private store: ReturnType<typeof useRmeStore>;
@@ -18,11 +18,16 @@ export class RmeService {
       const formattedControls = formatControls(rawControls);
       this.store.setControls(formattedControls);
 
-      const micConf = this.store.soundCardConfig.inputs.find(input => input.type === InputType.MIC)
-      if (micConf?.switchNames.gain){
-
-        const supportsGain = this.store.alsaControls[micConf.switchNames.gain] ? true : false
-        if (!supportsGain) {
+      try {
+        const capabilities = (await invoke("get_capabilities")) as DeviceCapabilities
+        if (capabilities.compatibility_mode) {
+          console.log(`No gain or main volume controls on kernel ${capabilities.kernel.release}. Setting compatability mode`)
+          this.store.setCompatabilityMode()
+        }
+      } catch (error) {
+        console.warn("Could not detect device capabilities, checking the gain controls instead:", error);
+        const micConf = this.store.soundCardConfig.inputs.find(input => input.type === InputType.MIC)
+        if (micConf?.switchNames.gain && !this.store.alsaControls[micConf.switchNames.gain]) {
           console.log('No support for input gain. Setting compatability mode')
           this.store.setCompatabilityMode()
         }
@@ -161,7 +166,7 @@ export class RmeService {
     try {
       await invoke("set_alsa_volume", {
         controlName,
-        level: alsaValue,
+        volume: alsaValue,
       });
 
       return 1
@@ -237,6 +242,46 @@ export class RmeService {
     }
   }
 
+  public getHotkeys = async () => {
+    try {
+      return (await invoke("get_hotkeys")) as HotkeyBinding[];
+    } catch (error) {
+      console.error("Failed to get hotkeys:", error);
+      return null
+    }
+  }
+
+  public setHotkeys = async (hotkeys: HotkeyBinding[]) => {
+    try {
+      const conflicts = (await invoke("set_hotkeys", { hotkeys })) as HotkeyConflict[];
+      if (conflicts.length) {
+        console.warn("Some hotkeys could not be registered:", conflicts);
+      }
+      return conflicts;
+    } catch (error) {
+      console.error("Failed to set hotkeys:", error);
+      throw error;
+    }
+  }
+
+  public getHotkeyConflicts = async () => {
+    try {
+      return (await invoke("get_hotkey_conflicts")) as HotkeyConflict[];
+    } catch (error) {
+      console.error("Failed to get hotkey conflicts:", error);
+      return null
+    }
+  }
+
+  public getConfigWarning = async () => {
+    try {
+      return (await invoke("get_config_warning")) as string | null;
+    } catch (error) {
+      console.error("Failed to get config warning:", error);
+      return null
+    }
+  }
+
   public getLineSensitivity = async (inputIndex: number) => {
     if (inputIndex > this.store.soundCardConfig.inputs.length) return
     if (!this.store.soundCardConfig.inputs[inputIndex].switchNames.lineSens) {
@@ -399,6 +444,21 @@ export class RmeService {
 
       return true;
     } catch (error) {
+      // The backend refuses 48V on ribbon mic channels until the user confirms
+      if (String(error).startsWith("ribbon_confirmation_required")) {
+        const displayName = this.store.soundCardConfig.inputs[inputIndex].displayName
+        if (!window.confirm(`${displayName} is marked as a ribbon mic. Turn on 48V anyway?`)) {
+          return false;
+        }
+
+        await invoke("set_phantom_power", {
+          controlName: switchName,
+          newState: newState,
+          confirmed: true,
+        });
+        return true;
+      }
+
       console.error("Failed to set phantom power:", error);
       throw error;
     }
@@ -74,5 +74,38 @@ export interface AudioProfile {
 export interface TauriInputChannelConfig {
   control_name: string, 
   display_name: string, 
-  stereo_coupled: boolean
-}
\ No newline at end of file
+  stereo_coupled: boolean,
+  ribbon_mic?: boolean
+}
+
+export type HotkeyOutput = "main" | "headphones" | "spdif" | "adat34" | "adat56" | "adat78"
+
+export type HotkeyAction =
+  | { type: "step_volume", output: HotkeyOutput, step_db: number }
+  | { type: "toggle_mute" }
+  | { type: "toggle_dim" }
+  | { type: "toggle_mono" }
+  | { type: "toggle_speakers" }
+  | { type: "toggle_pad", control_name: string }
+  | { type: "recall_preset", index: number }
+  | { type: "cycle_quantum" }
+
+export interface HotkeyBinding {
+  shortcut: string,
+  action: HotkeyAction
+}
+
+export interface HotkeyConflict {
+  shortcut: string,
+  reason: string
+}
+
+export interface DeviceCapabilities {
+  kernel: { release: string, major: number, minor: number, patch: number },
+  input_gain: string[],
+  output_volume: string[],
+  compatibility_mode: boolean,
+  switches: { phantom: string[], pad: string[], line_sensitivity: string[] },
+  mixer: { controls: number, sources: number, outputs: number, crosspoints: number },
+  pipewire: { available: boolean, version: string | null, card: boolean }
+}
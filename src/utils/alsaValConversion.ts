@@ -1,4 +1,4 @@
-const MIN_DB = -65;
+export const MIN_DB = -65;
 const MAX_DB = 6;
 const MIN_ALSA = 0;
 const MAX_ALSA = 65535;